@@ -0,0 +1,134 @@
+//! Benchmarks for compiling and instantiating `add.wasm`, calling into it
+//! through the typed, dynamic and C paths, and copying memory.
+//!
+//! ```sh
+//! cargo bench --bench runtime                         # run everything
+//! cargo bench --bench runtime -- call                 # only names containing "call"
+//! cargo bench --bench runtime -- --save-baseline main # store results as "main"
+//! cargo bench --bench runtime -- --baseline main      # compare against "main"
+//! ```
+
+use adder::ffi::*;
+use adder::{Engine, Value};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::ffi::CString;
+use std::ptr;
+
+const ADD_WASM: &[u8] = include_bytes!("../src/add.wasm");
+
+/// Calls made per iteration of the batch benchmarks.
+const BATCH: usize = 1000;
+
+fn lifecycle(c: &mut Criterion) {
+    let engine = Engine::new();
+    c.bench_function("compile", |b| b.iter(|| engine.compile(ADD_WASM).unwrap()));
+    let module = engine.compile(ADD_WASM).unwrap();
+    c.bench_function("instantiate", |b| b.iter(|| module.instantiate().unwrap()));
+}
+
+fn calls(c: &mut Criterion) {
+    let instance = Engine::new()
+        .compile(ADD_WASM)
+        .unwrap()
+        .instantiate()
+        .unwrap();
+    let wasmer = wasmer_runtime::instantiate(ADD_WASM, &adder::imports! {}).unwrap();
+    let add_one = wasmer.func::<i32, i32>("add_one").unwrap();
+    let ffi = FfiInstance::new(ADD_WASM);
+    let name = CString::new("add_one").unwrap();
+
+    let mut group = c.benchmark_group("call");
+    group.bench_function("typed", |b| b.iter(|| add_one.call(black_box(42)).unwrap()));
+    group.bench_function("dynamic", |b| {
+        b.iter(|| {
+            instance
+                .call("add_one", &[Value::I32(black_box(42))])
+                .unwrap()
+        })
+    });
+    group.bench_function("ffi", |b| b.iter(|| ffi.add_one(&name, black_box(42))));
+    group.finish();
+
+    let mut group = c.benchmark_group("batch");
+    group.throughput(Throughput::Elements(BATCH as u64));
+    group.bench_function("typed", |b| {
+        b.iter(|| (0..BATCH as i32).fold(0, |acc, x| acc ^ add_one.call(x).unwrap()))
+    });
+    group.bench_function("dynamic", |b| {
+        b.iter(|| {
+            (0..BATCH as i32).fold(0, |acc, x| {
+                match instance.call("add_one", &[Value::I32(x)]).unwrap()[..] {
+                    [Value::I32(result)] => acc ^ result,
+                    _ => unreachable!(),
+                }
+            })
+        })
+    });
+    group.bench_function("ffi", |b| {
+        b.iter(|| (0..BATCH as i32).fold(0, |acc, x| acc ^ ffi.add_one(&name, x)))
+    });
+    group.finish();
+}
+
+fn memory(c: &mut Criterion) {
+    let instance = Engine::new()
+        .compile(ADD_WASM)
+        .unwrap()
+        .instantiate()
+        .unwrap();
+    let mut group = c.benchmark_group("memory");
+    for &size in &[64, 4096, 65536] {
+        let data = vec![0xa5; size];
+        let mut buf = vec![0; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("write", size), &data, |b, data| {
+            b.iter(|| instance.write_memory(1024, black_box(data)).unwrap())
+        });
+        group.bench_function(BenchmarkId::new("read", size), |b| {
+            b.iter(|| instance.read_memory(1024, black_box(&mut buf)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// An instance created through the C ABI, as the Dart side sees it.
+struct FfiInstance(*mut WasmInstance);
+
+impl FfiInstance {
+    fn new(wasm: &[u8]) -> FfiInstance {
+        let mut module = ptr::null_mut();
+        let mut instance = ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                wasm_module_new(wasm.as_ptr(), wasm.len(), &mut module),
+                WasmStatus::Ok
+            );
+            assert_eq!(wasm_instance_new(module, &mut instance), WasmStatus::Ok);
+            wasm_module_delete(module);
+        }
+        FfiInstance(instance)
+    }
+
+    fn add_one(&self, name: &CString, x: i32) -> i32 {
+        let arg = WasmValue {
+            kind: WASM_I32,
+            of: WasmValueOf { i32: x },
+        };
+        let mut result = WasmValue {
+            kind: WASM_I32,
+            of: WasmValueOf { i32: 0 },
+        };
+        let status = unsafe { wasm_instance_call(self.0, name.as_ptr(), &arg, 1, &mut result, 1) };
+        assert_eq!(status, WasmStatus::Ok);
+        unsafe { result.of.i32 }
+    }
+}
+
+impl Drop for FfiInstance {
+    fn drop(&mut self) {
+        unsafe { wasm_instance_delete(self.0) };
+    }
+}
+
+criterion_group!(benches, lifecycle, calls, memory);
+criterion_main!(benches);
@@ -0,0 +1,118 @@
+//! Calls the exports of the modules in `tests/fixtures` through
+//! `wasm_instance_call` with arbitrary names, arguments and result buffers.
+//!
+//! The input is read as:
+//!
+//! - one byte selecting the module,
+//! - one byte `b` choosing the export: below 0x80 it picks a name from
+//!   `NAMES`, otherwise `b - 0x80` bytes of raw name follow,
+//! - one byte giving the number of result slots,
+//! - the arguments, 9 bytes each: a kind byte, taken modulo 6 so that two
+//!   unknown kinds are covered, and 8 bytes of payload.
+
+#![no_main]
+
+use adder::ffi::*;
+use libfuzzer_sys::fuzz_target;
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+const MODULES: &[&str] = &[
+    include_str!("../../tests/fixtures/arithmetic.wat"),
+    include_str!("../../tests/fixtures/globals.wat"),
+    include_str!("../../tests/fixtures/memory.wat"),
+    include_str!("../../tests/fixtures/traps.wat"),
+];
+
+/// Every export of the modules above, so most inputs reach a real function.
+const NAMES: &[&str] = &[
+    "add_one",
+    "add_i64",
+    "mul_f32",
+    "div_f64",
+    "div_u",
+    "nothing",
+    "bump",
+    "set",
+    "scaled",
+    "size",
+    "grow",
+    "store",
+    "load",
+    "unreachable",
+    "div_by_zero",
+    "overflow",
+    "bad_truncate",
+    "null_call",
+    "ok",
+];
+
+fuzz_target!(|data: &[u8]| {
+    let (&module, data) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let (&selector, data) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let (name, data) = if selector < 0x80 {
+        (NAMES[selector as usize % NAMES.len()].as_bytes(), data)
+    } else if data.len() >= (selector - 0x80) as usize {
+        data.split_at((selector - 0x80) as usize)
+    } else {
+        return;
+    };
+    let (&results_len, data) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let name: Vec<u8> = name.iter().copied().take_while(|&byte| byte != 0).collect();
+    let name = CString::new(name).unwrap();
+    let args: Vec<WasmValue> = data
+        .chunks_exact(9)
+        .map(|chunk| {
+            let mut bits = [0; 8];
+            bits.copy_from_slice(&chunk[1..]);
+            WasmValue {
+                kind: chunk[0] as u32 % 6,
+                of: WasmValueOf {
+                    i64: i64::from_le_bytes(bits),
+                },
+            }
+        })
+        .collect();
+    let mut results = vec![
+        WasmValue {
+            kind: WASM_I32,
+            of: WasmValueOf { i64: 0 },
+        };
+        results_len as usize % 4
+    ];
+
+    let wat = MODULES[module as usize % MODULES.len()];
+    let mut compiled = ptr::null_mut();
+    let mut instance = ptr::null_mut();
+    unsafe {
+        assert_eq!(
+            wasm_module_new(wat.as_ptr(), wat.len(), &mut compiled),
+            WasmStatus::Ok
+        );
+        assert_eq!(wasm_instance_new(compiled, &mut instance), WasmStatus::Ok);
+        wasm_module_delete(compiled);
+
+        let status = wasm_instance_call(
+            instance,
+            name.as_ptr(),
+            args.as_ptr(),
+            args.len(),
+            results.as_mut_ptr(),
+            results.len(),
+        );
+        if status == WasmStatus::Panic {
+            panic!("{}", CStr::from_ptr(wasm_last_error_message()).to_string_lossy());
+        }
+        wasm_instance_delete(instance);
+    }
+});
@@ -0,0 +1,93 @@
+//! Generates valid modules with `wasm-smith`, limited to the proposals the
+//! runtime implements, calls every exported function with arbitrary
+//! arguments and checks that all backends agree.
+//!
+//! Every loop and call is given a fuel budget, so generated modules always
+//! terminate, and NaNs are canonicalized so memory contents do not depend on
+//! a backend's choice of NaN payload.
+
+#![no_main]
+
+use adder::differential::{self, Call};
+use adder::{Engine, ExternType, Value, ValueType};
+use libfuzzer_sys::arbitrary::{Result, Unstructured};
+use libfuzzer_sys::fuzz_target;
+use wasm_smith::{Config, Module};
+
+/// Rounds of calls to every exported function.
+const MAX_ROUNDS: usize = 3;
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let wasm = match module(&mut u) {
+        Ok(wasm) => wasm,
+        Err(_) => return,
+    };
+    let calls = match calls(&wasm, &mut u) {
+        Ok(calls) => calls,
+        Err(_) => return,
+    };
+    if let Err(divergence) = differential::run(&wasm, &calls) {
+        panic!("{}", divergence);
+    }
+});
+
+fn module(u: &mut Unstructured) -> Result<Vec<u8>> {
+    let config = Config {
+        max_imports: 0,
+        max_memories: 1,
+        max_tables: 1,
+        export_everything: true,
+        canonicalize_nans: true,
+        bulk_memory_enabled: false,
+        reference_types_enabled: false,
+        simd_enabled: false,
+        relaxed_simd_enabled: false,
+        multi_value_enabled: false,
+        saturating_float_to_int_enabled: true,
+        sign_extension_ops_enabled: true,
+        memory64_enabled: false,
+        threads_enabled: false,
+        exceptions_enabled: false,
+        tail_call_enabled: false,
+        gc_enabled: false,
+        custom_page_sizes_enabled: false,
+        wide_arithmetic_enabled: false,
+        extended_const_enabled: false,
+        ..Config::default()
+    };
+    let mut module = Module::new(config, u)?;
+    module.ensure_termination(1000).unwrap();
+    Ok(module.to_bytes())
+}
+
+fn calls(wasm: &[u8], u: &mut Unstructured) -> Result<Vec<Call>> {
+    let exports = match Engine::new().compile(wasm) {
+        Ok(module) => module.exports(),
+        // Backends must then agree that the module is invalid.
+        Err(_) => return Ok(Vec::new()),
+    };
+    let mut calls = Vec::new();
+    for _ in 0..u.int_in_range(1..=MAX_ROUNDS)? {
+        for export in &exports {
+            if let ExternType::Function { params, .. } = &export.ty {
+                let args = params
+                    .iter()
+                    .map(|ty| value(*ty, u))
+                    .collect::<Result<Vec<_>>>()?;
+                calls.push(Call::new(&export.name, &args));
+            }
+        }
+    }
+    Ok(calls)
+}
+
+fn value(ty: ValueType, u: &mut Unstructured) -> Result<Value> {
+    Ok(match ty {
+        ValueType::I32 => Value::I32(u.arbitrary()?),
+        ValueType::I64 => Value::I64(u.arbitrary()?),
+        ValueType::F32 => Value::F32(f32::from_bits(u.arbitrary()?)),
+        ValueType::F64 => Value::F64(f64::from_bits(u.arbitrary()?)),
+        ValueType::V128 => Value::V128(u.arbitrary()?),
+    })
+}
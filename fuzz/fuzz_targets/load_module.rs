@@ -0,0 +1,24 @@
+//! Feeds arbitrary bytes to `wasm_module_new`, which must report an error
+//! status rather than crash or panic.
+//!
+//! Modules that compile are not instantiated, since a start function may
+//! loop forever.
+
+#![no_main]
+
+use adder::ffi::*;
+use libfuzzer_sys::fuzz_target;
+use std::ffi::CStr;
+use std::ptr;
+
+fuzz_target!(|data: &[u8]| {
+    let mut module = ptr::null_mut();
+    match unsafe { wasm_module_new(data.as_ptr(), data.len(), &mut module) } {
+        WasmStatus::Ok => unsafe { wasm_module_delete(module) },
+        WasmStatus::Panic => {
+            let message = unsafe { CStr::from_ptr(wasm_last_error_message()) };
+            panic!("{}", message.to_string_lossy());
+        }
+        _ => assert!(module.is_null()),
+    }
+});
@@ -0,0 +1,453 @@
+#ifndef ADDER_H
+#define ADDER_H
+
+/* Generated by cbindgen from the Rust sources; do not edit by hand.
+ * Regenerate with `UPDATE_HEADER=1 cargo test --test header`. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// `WasmValue::kind` of a 32-bit integer.
+#define WASM_I32 0
+
+// `WasmValue::kind` of a 64-bit integer.
+#define WASM_I64 1
+
+// `WasmValue::kind` of a 32-bit float.
+#define WASM_F32 2
+
+// `WasmValue::kind` of a 64-bit float.
+#define WASM_F64 3
+
+// `wasm_pool_new` reset policy: keep instance state between calls.
+#define WASM_POOL_RESET_KEEP 0
+
+// `wasm_pool_new` reset policy: restore each instance's initial state.
+#define WASM_POOL_RESET_SNAPSHOT 1
+
+// `wasm_pool_new` reset policy: replace each instance after every call.
+#define WASM_POOL_RESET_REINSTANTIATE 2
+
+// `wasm_pool_new` exhaustion policy: wait for an instance to be returned.
+#define WASM_POOL_BLOCK 0
+
+// `wasm_pool_new` exhaustion policy: fail with `WasmStatus::PoolExhausted`.
+#define WASM_POOL_FAIL 1
+
+// `wasm_pool_new` exhaustion policy: create an instance beyond the maximum.
+#define WASM_POOL_GROW 2
+
+// `wasm_registry_list` kind: registered modules.
+#define WASM_REGISTRY_MODULES 0
+
+// `wasm_registry_list` kind: registered instances.
+#define WASM_REGISTRY_INSTANCES 1
+
+// Result of every fallible FFI function. On anything other than `Ok`, a
+// description of the failure is available from `wasm_last_error_message`.
+typedef enum WasmStatus {
+  WASM_STATUS_OK = 0,
+  // A required pointer was null, a name was not UTF-8, or a value had an
+  // unknown kind.
+  WASM_STATUS_INVALID_ARGUMENT = 1,
+  // The input is neither a wasm binary nor valid WebAssembly text.
+  WASM_STATUS_PARSE_ERROR = 2,
+  WASM_STATUS_COMPILE_ERROR = 3,
+  WASM_STATUS_INSTANTIATE_ERROR = 4,
+  WASM_STATUS_EXPORT_NOT_FOUND = 5,
+  // The arguments or result buffer do not match the export's signature.
+  WASM_STATUS_SIGNATURE_MISMATCH = 6,
+  WASM_STATUS_TRAP = 7,
+  // The instance has no memory, or an access was out of bounds.
+  WASM_STATUS_MEMORY_ERROR = 8,
+  // The runtime panicked. Any module or instance passed to the call may
+  // be in an inconsistent state and should only be deleted.
+  WASM_STATUS_PANIC = 9,
+  // Every instance in a pool is in use and the pool may not grow.
+  WASM_STATUS_POOL_EXHAUSTED = 10,
+  // Nothing is registered under the name and version, or linked under
+  // the instance name.
+  WASM_STATUS_NOT_FOUND = 11,
+} WasmStatus;
+
+// An instance of a module, created by `wasm_instance_new`.
+typedef struct WasmInstance WasmInstance;
+
+// Instances linked so that each can import the exports of those before it,
+// created by `wasm_linker_new`.
+typedef struct WasmLinker WasmLinker;
+
+// A compiled module, created by `wasm_module_new`.
+typedef struct WasmModule WasmModule;
+
+// A pool of instances of a module that can be called from any thread,
+// created by `wasm_pool_new`.
+typedef struct WasmPool WasmPool;
+
+// Modules and instances by name and version, created by
+// `wasm_registry_new`. It may be used from any thread.
+typedef struct WasmRegistry WasmRegistry;
+
+// A module being compiled from chunks, created by `wasm_stream_new`.
+typedef struct WasmStream WasmStream;
+
+// The payload of a `WasmValue`, selected by its `kind`.
+typedef union WasmValueOf {
+  int32_t i32;
+  int64_t i64;
+  float f32;
+  double f64;
+} WasmValueOf;
+
+// A WebAssembly value passed to or returned from `wasm_instance_call`.
+typedef struct WasmValue {
+  // One of `WASM_I32`, `WASM_I64`, `WASM_F32` or `WASM_F64`.
+  uint32_t kind;
+  union WasmValueOf of;
+} WasmValue;
+
+// Runs `add_one(42)` from the bundled `add.wasm` and prints the result.
+//
+// Returns 0 on success, or 1 if the module could not be loaded or called.
+int32_t load_wasm(void);
+
+// Returns a description of the last failure on this thread, or null if
+// nothing has failed yet. The string stays valid until the next failure.
+const char *wasm_last_error_message(void);
+
+// Compiles `len` bytes of binary or text module at `bytes`, storing a new
+// module in `*module_out`. Free it with `wasm_module_delete`.
+//
+// # Safety
+//
+// `bytes` must point to `len` readable bytes and `module_out` must be
+// writable.
+enum WasmStatus wasm_module_new(const uint8_t *bytes, size_t len, struct WasmModule **module_out);
+
+// Frees a module. Instances created from it remain valid.
+//
+// # Safety
+//
+// `module` must be null or a pointer returned by `wasm_module_new` that has
+// not been freed yet.
+void wasm_module_delete(struct WasmModule *module);
+
+// Starts compiling a binary module from chunks, storing the stream in
+// `*stream_out`. If the module's total length is known, pass it as
+// `expected_len` to allocate the buffer once; otherwise pass 0. Feed it
+// with `wasm_stream_push` and end it with `wasm_stream_finish`, or free it
+// with `wasm_stream_delete` to give up.
+//
+// # Safety
+//
+// `stream_out` must be writable.
+enum WasmStatus wasm_stream_new(size_t expected_len, struct WasmStream **stream_out);
+
+// Appends the `len` bytes at `bytes` to the module and validates the
+// sections they complete, failing as soon as the module is known to be
+// invalid. Once a push fails, every later push and `wasm_stream_finish`
+// fail the same way.
+//
+// # Safety
+//
+// `stream` must be a live stream and `bytes` must point to `len` readable
+// bytes.
+enum WasmStatus wasm_stream_push(struct WasmStream *stream, const uint8_t *bytes, size_t len);
+
+// Compiles the module pushed to `stream`, storing it in `*module_out`, and
+// frees the stream whether or not it succeeds.
+//
+// # Safety
+//
+// `stream` must be a live stream, which must not be used afterwards, and
+// `module_out` must be writable.
+enum WasmStatus wasm_stream_finish(struct WasmStream *stream, struct WasmModule **module_out);
+
+// Frees a stream without compiling it.
+//
+// # Safety
+//
+// `stream` must be null or a pointer returned by `wasm_stream_new` that has
+// not been finished or freed yet.
+void wasm_stream_delete(struct WasmStream *stream);
+
+// Instantiates `module` with no imports, storing a new instance in
+// `*instance_out`. Free it with `wasm_instance_delete`.
+//
+// # Safety
+//
+// `module` must be a live module and `instance_out` must be writable.
+enum WasmStatus wasm_instance_new(const struct WasmModule *module,
+                                  struct WasmInstance **instance_out);
+
+// Frees an instance.
+//
+// # Safety
+//
+// `instance` must be null or a pointer returned by `wasm_instance_new`
+// that has not been freed yet.
+void wasm_instance_delete(struct WasmInstance *instance);
+
+// Replaces `instance` with an instance of the module in the `len` bytes at
+// `bytes`, carrying over its memories and mutable globals if the new module
+// is compatible, as described in the `reload` module of the Rust API.
+// `*carried_out` is set to whether state was carried over. If
+// `report_out` is not null, it receives a description of the added,
+// removed and changed exports, to be freed with `wasm_string_delete`. On
+// failure the instance is left as it was.
+//
+// # Safety
+//
+// `instance` must be a live instance, `bytes` must point to `len` readable
+// bytes, `carried_out` must be writable, and `report_out` null or
+// writable.
+enum WasmStatus wasm_instance_reload(struct WasmInstance *instance,
+                                     const uint8_t *bytes,
+                                     size_t len,
+                                     bool *carried_out,
+                                     char **report_out);
+
+// Frees a string returned by this library, such as the report from
+// `wasm_instance_reload`.
+//
+// # Safety
+//
+// `string` must be null or a string returned by this library that has not
+// been freed yet.
+void wasm_string_delete(char *string);
+
+// Calls the exported function `name` with `args_len` arguments, writing
+// its results to `results`, which must hold exactly as many values as the
+// function returns.
+//
+// # Safety
+//
+// `instance` must be a live instance, `name` a NUL-terminated string,
+// `args` must point to `args_len` values and `results` to `results_len`
+// writable values. Either array may be null when its length is zero.
+enum WasmStatus wasm_instance_call(struct WasmInstance *instance,
+                                   const char *name,
+                                   const struct WasmValue *args,
+                                   size_t args_len,
+                                   struct WasmValue *results,
+                                   size_t results_len);
+
+// Creates an empty linker, storing it in `*linker_out`. Free it with
+// `wasm_linker_delete`.
+//
+// # Safety
+//
+// `linker_out` must be writable.
+enum WasmStatus wasm_linker_new(struct WasmLinker **linker_out);
+
+// Frees a linker and every instance in it.
+//
+// # Safety
+//
+// `linker` must be null or a pointer returned by `wasm_linker_new` that has
+// not been freed yet.
+void wasm_linker_delete(struct WasmLinker *linker);
+
+// Instantiates `module`, resolving its imports from the exports of the
+// instances already in `linker`, each under the namespace it was named.
+// The new instance is added to the linker as `name`.
+//
+// # Safety
+//
+// `linker` and `module` must be live and `name` a NUL-terminated string.
+enum WasmStatus wasm_linker_instantiate(struct WasmLinker *linker,
+                                        const char *name,
+                                        const struct WasmModule *module);
+
+// Like `wasm_instance_call`, on the instance named `instance` in `linker`.
+// Returns `WASM_STATUS_NOT_FOUND` if nothing is linked under that name.
+//
+// # Safety
+//
+// As for `wasm_instance_call`, with `linker` a live linker and `instance` a
+// NUL-terminated string.
+enum WasmStatus wasm_linker_call(const struct WasmLinker *linker,
+                                 const char *instance,
+                                 const char *name,
+                                 const struct WasmValue *args,
+                                 size_t args_len,
+                                 struct WasmValue *results,
+                                 size_t results_len);
+
+// Makes `linker` resolve imports from namespaces with nothing linked
+// under them by instantiating the module registered under that name in
+// `registry`, at its highest version.
+//
+// # Safety
+//
+// `linker` and `registry` must be live.
+enum WasmStatus wasm_linker_use_registry(struct WasmLinker *linker,
+                                         const struct WasmRegistry *registry);
+
+// Creates an empty registry, storing it in `*registry_out`. Free it with
+// `wasm_registry_delete`.
+//
+// # Safety
+//
+// `registry_out` must be writable.
+enum WasmStatus wasm_registry_new(struct WasmRegistry **registry_out);
+
+// Frees a registry. Linkers using it keep what they need alive.
+//
+// # Safety
+//
+// `registry` must be null or a pointer returned by `wasm_registry_new` that
+// has not been freed yet, and must not be in use by another thread.
+void wasm_registry_delete(struct WasmRegistry *registry);
+
+// Registers `module` as `name` at `version`, a `major.minor.patch` string.
+// The caller keeps its module handle.
+//
+// # Safety
+//
+// `registry` and `module` must be live, and `name` and `version`
+// NUL-terminated strings.
+enum WasmStatus wasm_registry_register_module(const struct WasmRegistry *registry,
+                                              const char *name,
+                                              const char *version,
+                                              const struct WasmModule *module);
+
+// Looks up the module registered as `name` at `version`, or at its highest
+// version if `version` is null, storing a new handle to it in
+// `*module_out`. Free the handle with `wasm_module_delete`.
+//
+// # Safety
+//
+// `registry` must be live, `name` a NUL-terminated string, `version` null or
+// a NUL-terminated string, and `module_out` writable.
+enum WasmStatus wasm_registry_module(const struct WasmRegistry *registry,
+                                     const char *name,
+                                     const char *version,
+                                     struct WasmModule **module_out);
+
+// Removes the module registered as `name` at `version`. Handles to it and
+// its instances stay valid.
+//
+// # Safety
+//
+// `registry` must be live, and `name` and `version` NUL-terminated strings.
+enum WasmStatus wasm_registry_unload_module(const struct WasmRegistry *registry,
+                                            const char *name,
+                                            const char *version);
+
+// Registers `instance` as `name` at `version`. The registry takes ownership
+// of the instance, even on failure: do not call `wasm_instance_delete` on it
+// afterwards.
+//
+// # Safety
+//
+// `registry` must be live, `instance` a pointer returned by
+// `wasm_instance_new` that has not been freed yet, and `name` and `version`
+// NUL-terminated strings.
+enum WasmStatus wasm_registry_register_instance(const struct WasmRegistry *registry,
+                                                const char *name,
+                                                const char *version,
+                                                struct WasmInstance *instance);
+
+// Like `wasm_instance_call`, on the instance registered as `instance` at
+// `version`, or at its highest version if `version` is null. Calls to the
+// same instance from several threads take turns.
+//
+// # Safety
+//
+// As for `wasm_instance_call`, with `registry` live, `instance` a
+// NUL-terminated string and `version` null or a NUL-terminated string.
+enum WasmStatus wasm_registry_call(const struct WasmRegistry *registry,
+                                   const char *instance,
+                                   const char *version,
+                                   const char *name,
+                                   const struct WasmValue *args,
+                                   size_t args_len,
+                                   struct WasmValue *results,
+                                   size_t results_len);
+
+// Removes the instance registered as `name` at `version`. It is freed once
+// calls already running on it return.
+//
+// # Safety
+//
+// `registry` must be live, and `name` and `version` NUL-terminated strings.
+enum WasmStatus wasm_registry_unload_instance(const struct WasmRegistry *registry,
+                                              const char *name,
+                                              const char *version);
+
+// Lists the registered modules (`WASM_REGISTRY_MODULES`) or instances
+// (`WASM_REGISTRY_INSTANCES`) as NUL-terminated text with one
+// `name version` line per entry, in order. `*len_out` is set to the
+// length of the text including the NUL. If that is more than `buffer_len`,
+// nothing is written and `WASM_STATUS_INVALID_ARGUMENT` is returned, so
+// call again with a larger buffer.
+//
+// # Safety
+//
+// `registry` must be live, `buffer` must point to `buffer_len` writable
+// bytes, and `len_out` must be writable.
+enum WasmStatus wasm_registry_list(const struct WasmRegistry *registry,
+                                   uint32_t kind,
+                                   char *buffer,
+                                   size_t buffer_len,
+                                   size_t *len_out);
+
+// Creates a pool of instances of `module` with no imports, storing it in
+// `*pool_out`. `reset` is one of the `WASM_POOL_RESET_*` constants and
+// `exhaustion` one of `WASM_POOL_BLOCK`, `WASM_POOL_FAIL` or
+// `WASM_POOL_GROW`. Free the pool with `wasm_pool_delete`.
+//
+// # Safety
+//
+// `module` must be a live module and `pool_out` must be writable.
+enum WasmStatus wasm_pool_new(const struct WasmModule *module,
+                              size_t min,
+                              size_t max,
+                              uint32_t reset,
+                              uint32_t exhaustion,
+                              struct WasmPool **pool_out);
+
+// Frees a pool. It must not be in use by another thread.
+//
+// # Safety
+//
+// `pool` must be null or a pointer returned by `wasm_pool_new` that has not
+// been freed yet.
+void wasm_pool_delete(struct WasmPool *pool);
+
+// Like `wasm_instance_call`, but on an instance checked out of `pool` for
+// the duration of the call. Any number of threads may call into the same
+// pool at once.
+//
+// # Safety
+//
+// As for `wasm_instance_call`, with `pool` a live pool.
+enum WasmStatus wasm_pool_call(const struct WasmPool *pool,
+                               const char *name,
+                               const struct WasmValue *args,
+                               size_t args_len,
+                               struct WasmValue *results,
+                               size_t results_len);
+
+// Runs the export `name` over `data` split across instances of `pool` on
+// worker threads, replacing `data` with the output, as described in the
+// `parallel` module of the Rust API. `chunk_len`, `align` and `workers` of 0
+// pick the defaults. The output must be as long as the input.
+//
+// # Safety
+//
+// `pool` must be a live pool, `name` a NUL-terminated string, and `data`
+// must point to `len` writable bytes.
+enum WasmStatus wasm_pool_map(const struct WasmPool *pool,
+                              const char *name,
+                              uint8_t *data,
+                              size_t len,
+                              size_t chunk_len,
+                              size_t align,
+                              size_t workers);
+
+#endif  /* ADDER_H */
@@ -0,0 +1,60 @@
+use adder::{dartgen, source};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+const USAGE: &str =
+    "usage: wasmer-dartgen <module.wasm|module.wat> [--class <Name>] [-o <out.dart>]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut path = None;
+    let mut class = None;
+    let mut output = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "-h" | "--help" => usage(),
+            "--class" => class = Some(rest.next().unwrap_or_else(|| usage()).clone()),
+            "-o" => output = Some(rest.next().unwrap_or_else(|| usage()).clone()),
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => usage(),
+        }
+    }
+    let path = path.unwrap_or_else(|| usage());
+
+    if let Err(err) = run(&path, class, output) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn run(path: &str, class: Option<String>, output: Option<String>) -> Result<(), String> {
+    let class = match class {
+        Some(class) if !dartgen::is_class_name(&class) => {
+            return Err(format!("error: `{}` is not a valid Dart class name", class));
+        }
+        Some(class) => class,
+        None => {
+            let stem = Path::new(path).file_stem().unwrap_or_default();
+            dartgen::class_name(&stem.to_string_lossy())
+        }
+    };
+    let input = fs::read(path).map_err(|err| format!("error: could not read {}: {}", path, err))?;
+    let wasm = source::wasm_bytes(&input).map_err(|err| format!("error: {}", err))?;
+    let dart = dartgen::generate(&wasm, &class).map_err(|err| format!("error: {}", err))?;
+    match output {
+        Some(output) => fs::write(&output, dart)
+            .map_err(|err| format!("error: could not write {}: {}", output, err)),
+        None => {
+            print!("{}", dart);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use adder::{cli, reload, Engine, Error, ExternType, ImportObject, Instance, Value, ValueType};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+const HELP: &str = "commands:
+  exports                        list exports with their types
+  call <export> [args...]        call an exported function
+  mem size                       show memory size in pages and bytes
+  mem read <addr> [len]          hex dump memory (default 64 bytes)
+  mem write <addr> <byte>...     write hex bytes, e.g. `mem write 16 de ad`
+  mem grow <pages>               grow memory by a number of pages
+  global <name> [value]          show or set an exported global
+  reload                         recompile the module, keeping state if compatible
+  help                           show this message
+  quit                           exit";
+
+struct Repl {
+    path: String,
+    instance: Instance,
+}
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) if path != "-h" && path != "--help" => path,
+        _ => {
+            eprintln!("usage: wasmer-repl <module.wasm|module.wat>");
+            process::exit(2);
+        }
+    };
+    let mut repl = match load(&path) {
+        Ok(instance) => Repl { path, instance },
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let mut editor = DefaultEditor::new().unwrap_or_else(|err| {
+        eprintln!("error: could not start line editor: {}", err);
+        process::exit(1);
+    });
+    let history = history_path();
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
+    println!("loaded {}; type `help` for commands", repl.path);
+    loop {
+        match editor.readline("wasm> ") {
+            Ok(line) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let _ = editor.add_history_entry(line);
+                let words: Vec<String> = line.split_whitespace().map(String::from).collect();
+                if words[0] == "quit" || words[0] == "exit" {
+                    break;
+                }
+                match repl.execute(&words) {
+                    Ok(output) if output.is_empty() => {}
+                    Ok(output) => println!("{}", output),
+                    Err(err) => eprintln!("{}", err),
+                }
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                break;
+            }
+        }
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+}
+
+fn load(path: &str) -> Result<Instance, String> {
+    let input = read(path)?;
+    Engine::new()
+        .compile(&input)
+        .and_then(|module| module.instantiate())
+        .map_err(|err| format!("error: {}", err))
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|err| format!("error: could not read {}: {}", path, err))
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".wasmer_repl_history"))
+}
+
+impl Repl {
+    fn execute(&mut self, words: &[String]) -> Result<String, String> {
+        let args: Vec<&str> = words[1..].iter().map(String::as_str).collect();
+        match (words[0].as_str(), args.as_slice()) {
+            ("help", _) => Ok(HELP.to_string()),
+            ("exports", []) => Ok(self.exports()),
+            ("call", [export, ..]) => self.call(export, &words[2..]),
+            ("mem", ["size"]) => Ok(format!(
+                "{} pages ({} bytes)",
+                self.instance.memory_pages().map_err(error)?,
+                self.instance.memory_len().map_err(error)?
+            )),
+            ("mem", ["read", addr]) => self.read(parse_u32(addr)?, 64),
+            ("mem", ["read", addr, len]) => self.read(parse_u32(addr)?, parse_u32(len)?),
+            ("mem", ["write", addr, bytes @ ..]) if !bytes.is_empty() => {
+                self.write(parse_u32(addr)?, bytes)
+            }
+            ("mem", ["grow", pages]) => {
+                let previous = self
+                    .instance
+                    .grow_memory(parse_u32(pages)?)
+                    .map_err(error)?;
+                Ok(format!("grew from {} pages", previous))
+            }
+            ("global", [name]) => self.global(name, None),
+            ("global", [name, value]) => self.global(name, Some(value)),
+            ("reload", []) => {
+                let input = read(&self.path)?;
+                let report = reload::reload(
+                    &mut self.instance,
+                    &Engine::new(),
+                    &input,
+                    &ImportObject::new(),
+                )
+                .map_err(error)?;
+                Ok(format!("reloaded {}\n{}", self.path, report))
+            }
+            _ => Err(format!(
+                "error: unknown command `{}`; type `help` for commands",
+                words.join(" ")
+            )),
+        }
+    }
+
+    fn exports(&self) -> String {
+        let mut lines = Vec::new();
+        for export in self.instance.exports() {
+            let name = &export.name;
+            let description = match export.ty {
+                ExternType::Function { params, results } => format!(
+                    "func {} ({}) -> ({})",
+                    name,
+                    cli::type_list(&params),
+                    cli::type_list(&results)
+                ),
+                ExternType::Memory { .. } => format!(
+                    "memory {} {} pages",
+                    name,
+                    self.instance.memory_pages().unwrap_or_default()
+                ),
+                ExternType::Table { minimum, .. } => {
+                    format!("table {} {} elements", name, minimum)
+                }
+                ExternType::Global { ty, mutable } => format!(
+                    "global {} {}{} = {}",
+                    name,
+                    if mutable { "mut " } else { "" },
+                    ty,
+                    self.instance
+                        .global(name)
+                        .map(|value| cli::format_value(&value))
+                        .unwrap_or_default()
+                ),
+            };
+            lines.push(description);
+        }
+        lines.join("\n")
+    }
+
+    fn call(&self, export: &str, args: &[String]) -> Result<String, String> {
+        let params = cli::func_params(&self.instance, export).map_err(error)?;
+        let params =
+            cli::parse_args(args, &params).map_err(|err| format!("error: {}: {}", export, err))?;
+        match self.instance.call(export, &params) {
+            Ok(results) => Ok(results
+                .iter()
+                .map(cli::format_value)
+                .collect::<Vec<_>>()
+                .join(" ")),
+            Err(err @ Error::Trap(_)) => Err(err.to_string()),
+            Err(err) => Err(error(err)),
+        }
+    }
+
+    fn read(&self, addr: u32, len: u32) -> Result<String, String> {
+        let start = addr as usize;
+        // Check before allocating, so a huge length can't exhaust the host.
+        let size = self.instance.memory_len().map_err(error)?;
+        if start.checked_add(len as usize).is_none_or(|end| end > size) {
+            return Err(error(cli::out_of_bounds(start, len as usize, size)));
+        }
+        let mut bytes = vec![0; len as usize];
+        self.instance
+            .read_memory(start, &mut bytes)
+            .map_err(error)?;
+        let lines: Vec<String> = bytes
+            .chunks(16)
+            .enumerate()
+            .map(|(row, chunk)| {
+                let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+                let ascii: String = chunk
+                    .iter()
+                    .map(|&byte| {
+                        if byte.is_ascii_graphic() || byte == b' ' {
+                            byte as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                format!("{:08x}  {:<47}  {}", start + row * 16, hex.join(" "), ascii)
+            })
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    fn write(&self, addr: u32, bytes: &[&str]) -> Result<String, String> {
+        let bytes = bytes
+            .iter()
+            .map(|byte| {
+                u8::from_str_radix(byte.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("error: `{}` is not a hex byte", byte))
+            })
+            .collect::<Result<Vec<u8>, String>>()?;
+        self.instance
+            .write_memory(addr as usize, &bytes)
+            .map_err(error)?;
+        Ok(format!("wrote {} bytes", bytes.len()))
+    }
+
+    fn global(&self, name: &str, value: Option<&str>) -> Result<String, String> {
+        if let Some(value) = value {
+            let ty = match self.instance.export(name) {
+                Some(ExternType::Global { ty, .. }) => ty,
+                _ => return Err(format!("error: no exported global `{}`", name)),
+            };
+            let value = cli::parse_value(value, ty).map_err(|err| format!("error: {}", err))?;
+            self.instance.set_global(name, value).map_err(error)?;
+        }
+        self.instance
+            .global(name)
+            .map(|value| cli::format_value(&value))
+            .map_err(|_| format!("error: no exported global `{}`", name))
+    }
+}
+
+fn error(err: Error) -> String {
+    format!("error: {}", err)
+}
+
+fn parse_u32(text: &str) -> Result<u32, String> {
+    match cli::parse_value(text, ValueType::I32) {
+        Ok(Value::I32(value)) if !text.starts_with('-') => Ok(value as u32),
+        _ => Err(format!("error: `{}` is not a valid address or size", text)),
+    }
+}
@@ -0,0 +1,70 @@
+use adder::{cli, inspect, source, Engine, Error};
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "usage:
+  wasmer-run [run] <module.wasm|module.wat> <export> [args...]
+  wasmer-run inspect [--json] <module.wasm|module.wat>";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("-h") | Some("--help") | None => usage(),
+        Some("inspect") => match &args[1..] {
+            [flag, path] if flag == "--json" => inspect(path, true),
+            [path] => inspect(path, false),
+            _ => usage(),
+        },
+        Some("run") if args.len() >= 3 => run(&args[1], &args[2], &args[3..]),
+        Some(_) if args.len() >= 2 && args[0] != "run" => run(&args[0], &args[1], &args[2..]),
+        Some(_) => usage(),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|err| format!("error: could not read {}: {}", path, err))
+}
+
+fn run(path: &str, export: &str, args: &[String]) -> Result<(), String> {
+    let input = read(path)?;
+    let instance = Engine::new()
+        .compile(&input)
+        .and_then(|module| module.instantiate())
+        .map_err(|err| format!("error: {}", err))?;
+    let params = cli::func_params(&instance, export).map_err(|err| format!("error: {}", err))?;
+    let params =
+        cli::parse_args(args, &params).map_err(|err| format!("error: {}: {}", export, err))?;
+
+    match instance.call(export, &params) {
+        Ok(results) => {
+            for result in &results {
+                println!("{}", cli::format_value(result));
+            }
+            Ok(())
+        }
+        Err(err @ Error::Trap(_)) => Err(err.to_string()),
+        Err(err) => Err(format!("error: {}", err)),
+    }
+}
+
+fn inspect(path: &str, json: bool) -> Result<(), String> {
+    let input = read(path)?;
+    let wasm = source::wasm_bytes(&input).map_err(|err| format!("error: {}", err))?;
+    let report = inspect::inspect(&wasm).map_err(|err| format!("error: {}", err))?;
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
@@ -0,0 +1,216 @@
+//! Helpers shared by the command-line tools in `src/bin`.
+
+use crate::{Error, ExternType, Instance, Value, ValueType};
+
+pub use crate::instance::out_of_bounds;
+pub use crate::module::type_list;
+
+/// Parses `text` as a value of type `ty`.
+///
+/// Integers may be written in decimal or as `0x` hex, and either signed or
+/// unsigned, so `-1` and `4294967295` are the same `i32`. Floats accept
+/// anything Rust's float parser does, including `nan` and `inf`.
+pub fn parse_value(text: &str, ty: ValueType) -> Result<Value, String> {
+    let invalid = || format!("`{}` is not a valid {}", text, ty);
+    match ty {
+        ValueType::I32 => parse_int(text, 32)
+            .map(|bits| Value::I32(bits as u32 as i32))
+            .ok_or_else(invalid),
+        ValueType::I64 => parse_int(text, 64)
+            .map(|bits| Value::I64(bits as u64 as i64))
+            .ok_or_else(invalid),
+        ValueType::F32 => text.parse().map(Value::F32).map_err(|_| invalid()),
+        ValueType::F64 => text.parse().map(Value::F64).map_err(|_| invalid()),
+        ValueType::V128 => parse_int(text, 128).map(Value::V128).ok_or_else(invalid),
+    }
+}
+
+/// Parses one command-line argument per parameter type.
+pub fn parse_args(args: &[String], params: &[ValueType]) -> Result<Vec<Value>, String> {
+    if args.len() != params.len() {
+        return Err(format!(
+            "expected {} argument(s) of type ({}), got {}",
+            params.len(),
+            type_list(params),
+            args.len()
+        ));
+    }
+    args.iter()
+        .zip(params)
+        .map(|(arg, ty)| parse_value(arg, *ty))
+        .collect()
+}
+
+/// Returns the parameter types of the exported function `name`.
+pub fn func_params(instance: &Instance, name: &str) -> Result<Vec<ValueType>, Error> {
+    match instance.export(name) {
+        Some(ExternType::Function { params, .. }) => Ok(params),
+        _ => Err(Error::ExportNotFound(name.to_string())),
+    }
+}
+
+/// Formats a value the way `parse_value` reads it back.
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::I32(x) => x.to_string(),
+        Value::I64(x) => x.to_string(),
+        Value::F32(x) => x.to_string(),
+        Value::F64(x) => x.to_string(),
+        Value::V128(x) => format!("{:#034x}", x),
+    }
+}
+
+/// Parses a signed or unsigned integer that fits in `bits` bits, returning
+/// its two's complement representation.
+fn parse_int(text: &str, bits: u32) -> Option<u128> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (digits, radix) = match digits.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (digits, 10),
+    };
+    // The integer parser takes a sign of its own, which would let `-+1` and
+    // `0x+1` through.
+    if (negative || radix == 16) && digits.starts_with('+') {
+        return None;
+    }
+    let magnitude = u128::from_str_radix(digits, radix).ok()?;
+    let mask = if bits == 128 {
+        u128::MAX
+    } else {
+        (1u128 << bits) - 1
+    };
+    if negative {
+        // The most negative value has a magnitude one past the signed maximum.
+        if magnitude > 1u128 << (bits - 1) {
+            return None;
+        }
+        Some(magnitude.wrapping_neg() & mask)
+    } else if magnitude > mask {
+        None
+    } else {
+        Some(magnitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(text: &str, bits: u32) -> Option<u128> {
+        parse_int(text, bits)
+    }
+
+    #[test]
+    fn integers_parse_signed_unsigned_and_hex() {
+        assert_eq!(int("0", 32), Some(0));
+        assert_eq!(int("42", 32), Some(42));
+        assert_eq!(int("-1", 32), Some(0xffff_ffff));
+        assert_eq!(int("4294967295", 32), Some(0xffff_ffff));
+        assert_eq!(int("0x7f", 32), Some(0x7f));
+        assert_eq!(int("0xFFFFFFFF", 32), Some(0xffff_ffff));
+        assert_eq!(int("-0x1", 32), Some(0xffff_ffff));
+        assert_eq!(int("-0x10", 64), Some(u64::MAX as u128 - 15));
+        assert_eq!(int("-0", 32), Some(0));
+        assert_eq!(int("-0x0", 64), Some(0));
+        assert_eq!(int("+7", 32), Some(7));
+    }
+
+    #[test]
+    fn integers_out_of_range_are_rejected() {
+        assert_eq!(int("4294967296", 32), None);
+        assert_eq!(int("0x100000000", 32), None);
+        assert_eq!(int("18446744073709551616", 64), None);
+        assert_eq!(int("-0x80000001", 32), None);
+        assert_eq!(int("-2147483649", 32), None);
+        assert_eq!(int("-9223372036854775809", 64), None);
+        assert_eq!(int(&format!("0x1{}", "0".repeat(32)), 128), None);
+    }
+
+    #[test]
+    fn malformed_integers_are_rejected() {
+        for text in [
+            "", "-", "0x", "-0x", "--1", "-+1", "0x+1", "0x-1", "1.0", "0b1", " 1", "x1",
+        ] {
+            assert_eq!(int(text, 32), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn the_most_negative_integers_parse() {
+        let min = |text, ty| parse_value(text, ty).unwrap();
+        assert_eq!(min("-2147483648", ValueType::I32), Value::I32(i32::MIN));
+        assert_eq!(min("-0x80000000", ValueType::I32), Value::I32(i32::MIN));
+        assert_eq!(min("2147483648", ValueType::I32), Value::I32(i32::MIN));
+        assert_eq!(
+            min("-9223372036854775808", ValueType::I64),
+            Value::I64(i64::MIN)
+        );
+        assert_eq!(
+            min("-0x8000000000000000", ValueType::I64),
+            Value::I64(i64::MIN)
+        );
+        assert_eq!(
+            min(&format!("-0x8{}", "0".repeat(31)), ValueType::V128),
+            Value::V128(1 << 127)
+        );
+    }
+
+    #[test]
+    fn values_report_the_type_they_failed_to_parse_as() {
+        assert_eq!(
+            parse_value("4294967296", ValueType::I32),
+            Err("`4294967296` is not a valid i32".to_string())
+        );
+        assert_eq!(
+            parse_value("one", ValueType::F64),
+            Err("`one` is not a valid f64".to_string())
+        );
+    }
+
+    #[test]
+    fn formatted_values_parse_back() {
+        let values = [
+            Value::I32(i32::MIN),
+            Value::I32(-1),
+            Value::I32(i32::MAX),
+            Value::I64(i64::MIN),
+            Value::I64(i64::MAX),
+            Value::F32(0.1),
+            Value::F32(-0.0),
+            Value::F32(f32::MIN_POSITIVE),
+            Value::F32(f32::MAX),
+            Value::F32(f32::INFINITY),
+            Value::F64(1.0 / 3.0),
+            Value::F64(-0.0),
+            Value::F64(5e-324),
+            Value::F64(f64::NEG_INFINITY),
+            Value::V128(0),
+            Value::V128(u128::MAX),
+        ];
+        for value in &values {
+            let text = format_value(value);
+            let parsed = parse_value(&text, value.ty()).unwrap();
+            // Compare bits, so that `-0.0` doesn't equal `0.0`.
+            assert_eq!(parsed.to_bits(), value.to_bits(), "{}", text);
+        }
+
+        let nan = parse_value(&format_value(&Value::F64(f64::NAN)), ValueType::F64);
+        assert!(matches!(nan, Ok(Value::F64(x)) if x.is_nan()));
+    }
+
+    #[test]
+    fn arguments_are_checked_against_the_parameters() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            parse_args(&args(&["1", "0x2"]), &[ValueType::I32, ValueType::I64]),
+            Ok(vec![Value::I32(1), Value::I64(2)])
+        );
+        assert_eq!(
+            parse_args(&args(&["1"]), &[ValueType::I32, ValueType::F32]),
+            Err("expected 2 argument(s) of type (i32, f32), got 1".to_string())
+        );
+    }
+}
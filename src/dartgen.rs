@@ -0,0 +1,512 @@
+//! Generates a typed Dart wrapper for a module's exported functions.
+//!
+//! The generated class loads the module through `wasm_module_new` and
+//! `wasm_instance_new` and calls every export through `wasm_instance_call`,
+//! so it works with any module and needs nothing beyond `dart:ffi` and
+//! `package:ffi`.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use wasmparser::{ExternalKind, FuncType, Parser, Payload, TypeRef, ValType};
+
+/// Dart reserved words and built-in identifiers that cannot name a method,
+/// plus the members every generated class already has.
+const RESERVED: &[&str] = &[
+    "abstract",
+    "as",
+    "assert",
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "covariant",
+    "default",
+    "deferred",
+    "do",
+    "dynamic",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "extension",
+    "external",
+    "factory",
+    "false",
+    "final",
+    "finally",
+    "for",
+    "Function",
+    "get",
+    "hide",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "interface",
+    "is",
+    "late",
+    "library",
+    "mixin",
+    "new",
+    "null",
+    "on",
+    "operator",
+    "part",
+    "required",
+    "rethrow",
+    "return",
+    "set",
+    "show",
+    "static",
+    "super",
+    "switch",
+    "sync",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typedef",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+    "dispose",
+    "load",
+];
+
+/// An exported function as seen by the generator.
+struct Export {
+    name: String,
+    params: Vec<ValType>,
+    results: Vec<ValType>,
+}
+
+/// Generates a Dart class named `class_name` with one method per exported
+/// function of `wasm`, which must be a binary module.
+///
+/// Method names are the export names in lowerCamelCase. Functions whose
+/// signature cannot be expressed through the FFI (`v128` values or more than
+/// one result) are listed in a comment instead.
+pub fn generate(wasm: &[u8], class_name: &str) -> Result<String, wasmparser::BinaryReaderError> {
+    let exports = exported_functions(wasm)?;
+
+    let mut methods = String::new();
+    let mut skipped = Vec::new();
+    let mut used = HashSet::new();
+    let mut uses_f32 = false;
+    let mut uses_f64 = false;
+    for export in &exports {
+        let types = export.params.iter().chain(&export.results);
+        if export.results.len() > 1 || types.clone().any(|ty| dart_type(*ty).is_none()) {
+            skipped.push(&export.name);
+            continue;
+        }
+        uses_f32 |= types.clone().any(|ty| *ty == ValType::F32);
+        uses_f64 |= types.clone().any(|ty| *ty == ValType::F64);
+        let method = unique_name(method_name(&export.name), &mut used);
+        write_method(&mut methods, export, &method);
+    }
+
+    let mut out = String::new();
+    out.push_str(&PRELUDE.replace("$Class", class_name));
+    for name in skipped {
+        writeln!(
+            out,
+            "  // Skipped `{}`: unsupported signature.",
+            dart_comment(name)
+        )
+        .unwrap();
+    }
+    out.push_str(&methods);
+    out.push_str("}\n");
+    if uses_f32 {
+        out.push_str(F32_HELPERS);
+    }
+    if uses_f64 {
+        out.push_str(F64_HELPERS);
+    }
+    if uses_f32 || uses_f64 {
+        out.push_str("\nfinal _scratch = ByteData(8);\n");
+    }
+    Ok(out)
+}
+
+/// Derives a Dart class name from a file stem: `add_one` becomes `AddOne`.
+pub fn class_name(stem: &str) -> String {
+    let name: String = words(stem).iter().map(|word| capitalize(word)).collect();
+    match name.chars().next() {
+        Some(first) if first.is_ascii_alphabetic() => name,
+        _ => format!("Wasm{}", name),
+    }
+}
+
+/// Whether `name` can be passed to [`generate`] as the class name: a Dart
+/// identifier that is not a reserved word. `$` is left out, since the
+/// generated code names the class inside string literals.
+pub fn is_class_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&name)
+}
+
+fn exported_functions(wasm: &[u8]) -> Result<Vec<Export>, wasmparser::BinaryReaderError> {
+    // Function types by type index and the type index of every function,
+    // starting with imported functions.
+    let mut types: Vec<FuncType> = Vec::new();
+    let mut functions: Vec<u32> = Vec::new();
+    let mut exports = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::TypeSection(reader) => {
+                for ty in reader.into_iter_err_on_gc_types() {
+                    types.push(ty?);
+                }
+            }
+            Payload::ImportSection(reader) => {
+                for import in reader.into_imports() {
+                    if let TypeRef::Func(index) | TypeRef::FuncExact(index) = import?.ty {
+                        functions.push(index);
+                    }
+                }
+            }
+            Payload::FunctionSection(reader) => {
+                for index in reader {
+                    functions.push(index?);
+                }
+            }
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    let export = export?;
+                    if !matches!(export.kind, ExternalKind::Func | ExternalKind::FuncExact) {
+                        continue;
+                    }
+                    let ty = functions
+                        .get(export.index as usize)
+                        .and_then(|ty| types.get(*ty as usize));
+                    if let Some(ty) = ty {
+                        exports.push(Export {
+                            name: export.name.to_string(),
+                            params: ty.params().to_vec(),
+                            results: ty.results().to_vec(),
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(exports)
+}
+
+fn write_method(out: &mut String, export: &Export, method: &str) {
+    let result_type = export
+        .results
+        .first()
+        .map_or("void", |ty| dart_type(*ty).unwrap());
+    let params: Vec<String> = export
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| format!("{} arg{}", dart_type(*ty).unwrap(), i))
+        .collect();
+    let wasm_types = |types: &[ValType]| {
+        types
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    writeln!(
+        out,
+        "\n  /// Calls `{}`: ({}) -> ({}).",
+        dart_comment(&export.name),
+        wasm_types(&export.params),
+        wasm_types(&export.results)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  {} {}({}) {{",
+        result_type,
+        method,
+        params.join(", ")
+    )
+    .unwrap();
+    // `allocate` cannot return an empty block, so always ask for one value.
+    writeln!(
+        out,
+        "    final args = allocate<_WasmValue>(count: {});",
+        export.params.len().max(1)
+    )
+    .unwrap();
+    writeln!(out, "    final results = allocate<_WasmValue>(count: 1);").unwrap();
+    writeln!(
+        out,
+        "    final name = Utf8.toUtf8('{}');",
+        dart_string(&export.name)
+    )
+    .unwrap();
+    writeln!(out, "    try {{").unwrap();
+    for (i, ty) in export.params.iter().enumerate() {
+        let (kind, bits) = match ty {
+            ValType::I32 | ValType::I64 => (kind(*ty), format!("arg{}", i)),
+            ValType::F32 => (kind(*ty), format!("_f32ToBits(arg{})", i)),
+            _ => (kind(*ty), format!("_f64ToBits(arg{})", i)),
+        };
+        writeln!(out, "      args.elementAt({}).ref", i).unwrap();
+        writeln!(out, "        ..kind = {}", kind).unwrap();
+        writeln!(out, "        ..bits = {};", bits).unwrap();
+    }
+    writeln!(
+        out,
+        "      _check(_call(_instance, name, args, {}, results, {}), _lastError);",
+        export.params.len(),
+        export.results.len()
+    )
+    .unwrap();
+    match export.results.first() {
+        Some(ValType::I32) => writeln!(out, "      return results.ref.bits.toSigned(32);").unwrap(),
+        Some(ValType::I64) => writeln!(out, "      return results.ref.bits;").unwrap(),
+        Some(ValType::F32) => {
+            writeln!(out, "      return _f32FromBits(results.ref.bits);").unwrap()
+        }
+        Some(_) => writeln!(out, "      return _f64FromBits(results.ref.bits);").unwrap(),
+        None => {}
+    }
+    writeln!(out, "    }} finally {{").unwrap();
+    writeln!(out, "      free(args);").unwrap();
+    writeln!(out, "      free(results);").unwrap();
+    writeln!(out, "      free(name);").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "  }}").unwrap();
+}
+
+fn dart_type(ty: ValType) -> Option<&'static str> {
+    match ty {
+        ValType::I32 | ValType::I64 => Some("int"),
+        ValType::F32 | ValType::F64 => Some("double"),
+        _ => None,
+    }
+}
+
+/// The `WASM_*` kind constant for `ty`.
+fn kind(ty: ValType) -> u32 {
+    match ty {
+        ValType::I32 => crate::ffi::WASM_I32,
+        ValType::I64 => crate::ffi::WASM_I64,
+        ValType::F32 => crate::ffi::WASM_F32,
+        _ => crate::ffi::WASM_F64,
+    }
+}
+
+/// Converts an export name to a lowerCamelCase Dart identifier.
+fn method_name(export: &str) -> String {
+    let words = words(export);
+    let mut name = String::new();
+    for (i, word) in words.iter().enumerate() {
+        if i == 0 {
+            name.push_str(&word.to_lowercase());
+        } else {
+            name.push_str(&capitalize(word));
+        }
+    }
+    if name.is_empty() {
+        name.push_str("export");
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, 'f');
+    }
+    if RESERVED.contains(&name.as_str()) {
+        name.push('_');
+    }
+    name
+}
+
+/// Appends a number to `name` until it does not clash with an earlier method.
+fn unique_name(name: String, used: &mut HashSet<String>) -> String {
+    let mut candidate = name.clone();
+    let mut n = 2;
+    while !used.insert(candidate.clone()) {
+        candidate = format!("{}{}", name, n);
+        n += 1;
+    }
+    candidate
+}
+
+/// Splits a name on anything that cannot appear in a Dart identifier.
+fn words(name: &str) -> Vec<&str> {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/// Escapes `text` for a single-quoted Dart string literal.
+fn dart_string(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' | '\'' | '$' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if (c as u32) < 0x20 => write!(out, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escapes `text` for a line comment, so that a line break in an export
+/// name cannot end the comment and start code.
+fn dart_comment(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => write!(out, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+const PRELUDE: &str = r#"// Generated by wasmer-dartgen. Do not edit by hand.
+
+import 'dart:ffi' as ffi;
+import 'dart:typed_data';
+
+import 'package:ffi/ffi.dart';
+
+/// Mirrors `WasmValue` from `include/adder.h`.
+class _WasmValue extends ffi.Struct {
+  @ffi.Uint32()
+  int kind;
+
+  @ffi.Uint64()
+  int bits;
+}
+
+typedef _ModuleNewNative = ffi.Int32 Function(
+    ffi.Pointer<ffi.Uint8>, ffi.IntPtr, ffi.Pointer<ffi.Pointer<ffi.Void>>);
+typedef _ModuleNew = int Function(
+    ffi.Pointer<ffi.Uint8>, int, ffi.Pointer<ffi.Pointer<ffi.Void>>);
+typedef _InstanceNewNative = ffi.Int32 Function(
+    ffi.Pointer<ffi.Void>, ffi.Pointer<ffi.Pointer<ffi.Void>>);
+typedef _InstanceNew = int Function(
+    ffi.Pointer<ffi.Void>, ffi.Pointer<ffi.Pointer<ffi.Void>>);
+typedef _DeleteNative = ffi.Void Function(ffi.Pointer<ffi.Void>);
+typedef _Delete = void Function(ffi.Pointer<ffi.Void>);
+typedef _CallNative = ffi.Int32 Function(
+    ffi.Pointer<ffi.Void>,
+    ffi.Pointer<Utf8>,
+    ffi.Pointer<_WasmValue>,
+    ffi.IntPtr,
+    ffi.Pointer<_WasmValue>,
+    ffi.IntPtr);
+typedef _Call = int Function(ffi.Pointer<ffi.Void>, ffi.Pointer<Utf8>,
+    ffi.Pointer<_WasmValue>, int, ffi.Pointer<_WasmValue>, int);
+typedef _LastError = ffi.Pointer<Utf8> Function();
+
+/// Thrown when the module cannot be loaded or a call fails.
+class $ClassException implements Exception {
+  /// The `WasmStatus` returned by the native call.
+  final int status;
+  final String message;
+
+  $ClassException(this.status, this.message);
+
+  @override
+  String toString() => '$ClassException($status): $message';
+}
+
+void _check(int status, _LastError lastError) {
+  if (status == 0) return;
+  final message = lastError();
+  throw $ClassException(
+      status, message.address == 0 ? '' : Utf8.fromUtf8(message));
+}
+
+class $Class {
+  final ffi.Pointer<ffi.Void> _instance;
+  final _Call _call;
+  final _Delete _deleteInstance;
+  final _LastError _lastError;
+
+  $Class._(this._instance, this._call, this._deleteInstance, this._lastError);
+
+  /// Compiles and instantiates [wasm] using the native library [lib].
+  factory $Class.load(ffi.DynamicLibrary lib, Uint8List wasm) {
+    final moduleNew = lib
+        .lookupFunction<_ModuleNewNative, _ModuleNew>('wasm_module_new');
+    final moduleDelete =
+        lib.lookupFunction<_DeleteNative, _Delete>('wasm_module_delete');
+    final instanceNew = lib
+        .lookupFunction<_InstanceNewNative, _InstanceNew>('wasm_instance_new');
+    final instanceDelete =
+        lib.lookupFunction<_DeleteNative, _Delete>('wasm_instance_delete');
+    final call = lib.lookupFunction<_CallNative, _Call>('wasm_instance_call');
+    final lastError = lib.lookupFunction<_LastError, _LastError>(
+        'wasm_last_error_message');
+
+    final bytes = allocate<ffi.Uint8>(count: wasm.isEmpty ? 1 : wasm.length);
+    final out = allocate<ffi.Pointer<ffi.Void>>();
+    try {
+      bytes.asTypedList(wasm.length).setAll(0, wasm);
+      _check(moduleNew(bytes, wasm.length, out), lastError);
+      final module = out.value;
+      try {
+        _check(instanceNew(module, out), lastError);
+      } finally {
+        moduleDelete(module);
+      }
+      return $Class._(out.value, call, instanceDelete, lastError);
+    } finally {
+      free(bytes);
+      free(out);
+    }
+  }
+
+  /// Frees the native instance. The object must not be used afterwards.
+  void dispose() => _deleteInstance(_instance);
+"#;
+
+const F32_HELPERS: &str = r#"
+int _f32ToBits(double value) {
+  _scratch.setFloat32(0, value, Endian.little);
+  return _scratch.getUint32(0, Endian.little);
+}
+
+double _f32FromBits(int bits) {
+  _scratch.setUint32(0, bits & 0xffffffff, Endian.little);
+  return _scratch.getFloat32(0, Endian.little);
+}
+"#;
+
+const F64_HELPERS: &str = r#"
+int _f64ToBits(double value) {
+  _scratch.setFloat64(0, value, Endian.little);
+  return _scratch.getInt64(0, Endian.little);
+}
+
+double _f64FromBits(int bits) {
+  _scratch.setInt64(0, bits, Endian.little);
+  return _scratch.getFloat64(0, Endian.little);
+}
+"#;
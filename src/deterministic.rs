@@ -0,0 +1,348 @@
+//! Support for running modules deterministically, so that a replayed plugin
+//! produces bit-identical results and memory on every run and machine.
+//!
+//! WebAssembly is deterministic apart from two things this module removes:
+//!
+//! * The bit pattern of a NaN produced by float arithmetic is left to the
+//!   hardware. [`canonicalize_nans`] rewrites a module so that every such
+//!   NaN is the canonical one; [`Engine::canonicalize_nans`] applies it to
+//!   every module the engine compiles.
+//! * Host imports such as clocks and random number generators. [`Host`]
+//!   provides the WASI ones backed by a virtual clock and a seeded
+//!   generator.
+//!
+//! ```
+//! use adder::deterministic::Host;
+//! use adder::Engine;
+//!
+//! let wat = r#"(module
+//!     (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+//!     (memory (export "memory") 1)
+//!     (func (export "roll") (result i32)
+//!         (drop (call $random_get (i32.const 0) (i32.const 4)))
+//!         (i32.load (i32.const 0))))"#;
+//! let module = Engine::new().canonicalize_nans(true).compile(wat.as_bytes())?;
+//! let roll = || {
+//!     let instance = module.instantiate_with(&Host::new(7).imports())?;
+//!     instance.call("roll", &[])
+//! };
+//! assert_eq!(roll()?, roll()?);
+//! # Ok::<(), adder::Error>(())
+//! ```
+//!
+//! [`Engine::canonicalize_nans`]: crate::Engine::canonicalize_nans
+
+use crate::instance::ctx_memory;
+use crate::{func, Error, ImportObject, Result};
+use std::cell::Cell;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use wasm_encoder::reencode::{self, Reencode, RoundtripReencoder};
+use wasm_encoder::{CodeSection, Function, Ieee32, Ieee64, Instruction, RawSection, ValType};
+use wasmer_runtime::{Ctx, Memory};
+use wasmer_runtime_core::import::Namespace;
+use wasmparser::{BinaryReaderError, FunctionBody, Operator, Parser, Payload};
+
+/// The NaN every float NaN is replaced with: positive, quiet, no payload.
+pub const CANONICAL_NAN_F32: u32 = 0x7fc0_0000;
+pub const CANONICAL_NAN_F64: u64 = 0x7ff8_0000_0000_0000;
+
+/// The namespace [`Host`] registers its imports in.
+pub const WASI_NAMESPACE: &str = "wasi_snapshot_preview1";
+
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_FAULT: i32 = 21;
+const ERRNO_INVAL: i32 = 28;
+
+/// Number of clocks WASI defines: realtime, monotonic, process and thread
+/// CPU time.
+const WASI_CLOCKS: i32 = 4;
+
+/// Rewrites `wasm` so that every float operation that can produce a NaN
+/// produces [`CANONICAL_NAN_F32`] or [`CANONICAL_NAN_F64`].
+///
+/// Operations that only move bits, such as loads, stores, `neg`, `abs`,
+/// `copysign` and reinterpretations, are left alone: they are already
+/// deterministic and can still produce any NaN from one passed in. Each
+/// rewritten function gets two extra locals, so the output is slightly
+/// larger and slower than the input.
+///
+/// Fails with [`Error::Compile`] if `wasm` is not a well-formed binary
+/// module.
+pub fn canonicalize_nans(wasm: &[u8]) -> Result<Vec<u8>> {
+    let mut module = wasm_encoder::Module::new();
+    let mut param_counts = Vec::new();
+    let mut function_types = Vec::new();
+    let mut code = CodeSection::new();
+    let mut remaining = 0;
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload.map_err(parse_error)?;
+        match &payload {
+            Payload::TypeSection(reader) => {
+                for ty in reader.clone().into_iter_err_on_gc_types() {
+                    param_counts.push(ty.map_err(parse_error)?.params().len() as u32);
+                }
+            }
+            Payload::FunctionSection(reader) => {
+                for ty in reader.clone() {
+                    function_types.push(ty.map_err(parse_error)?);
+                }
+            }
+            Payload::CodeSectionStart { count, .. } if *count > 0 => {
+                remaining = *count;
+                continue;
+            }
+            Payload::CodeSectionEntry(body) => {
+                let index = function_types.len() - remaining as usize;
+                let params = function_types
+                    .get(index)
+                    .and_then(|&ty| param_counts.get(ty as usize))
+                    .copied()
+                    .ok_or_else(|| Error::Compile("function body has no type".to_string()))?;
+                code.function(&canonicalize_body(body, params)?);
+                remaining -= 1;
+                if remaining == 0 {
+                    module.section(&code);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if let Some((id, range)) = payload.as_section() {
+            module.section(&RawSection {
+                id,
+                data: &wasm[range],
+            });
+        }
+    }
+    Ok(module.finish())
+}
+
+fn canonicalize_body(body: &FunctionBody, params: u32) -> Result<Function> {
+    let mut reencoder = RoundtripReencoder;
+    let mut locals = Vec::new();
+    let mut count = params;
+    for local in body.get_locals_reader().map_err(parse_error)? {
+        let (n, ty) = local.map_err(parse_error)?;
+        locals.push((n, reencoder.val_type(ty).map_err(reencode_error)?));
+        count += n;
+    }
+    let (f32_scratch, f64_scratch) = (count, count + 1);
+    locals.push((1, ValType::F32));
+    locals.push((1, ValType::F64));
+
+    let mut function = Function::new(locals);
+    let mut reader = body.get_operators_reader().map_err(parse_error)?;
+    while !reader.eof() {
+        let op = reader.read().map_err(parse_error)?;
+        let produces = produces_nan(&op);
+        function.instruction(&reencoder.instruction(op).map_err(reencode_error)?);
+        // x  ->  x == x ? x : canonical NaN
+        match produces {
+            Some(ValType::F32) => {
+                function.instruction(&Instruction::LocalTee(f32_scratch));
+                function.instruction(&Instruction::F32Const(Ieee32::new(CANONICAL_NAN_F32)));
+                function.instruction(&Instruction::LocalGet(f32_scratch));
+                function.instruction(&Instruction::LocalGet(f32_scratch));
+                function.instruction(&Instruction::F32Eq);
+                function.instruction(&Instruction::Select);
+            }
+            Some(_) => {
+                function.instruction(&Instruction::LocalTee(f64_scratch));
+                function.instruction(&Instruction::F64Const(Ieee64::new(CANONICAL_NAN_F64)));
+                function.instruction(&Instruction::LocalGet(f64_scratch));
+                function.instruction(&Instruction::LocalGet(f64_scratch));
+                function.instruction(&Instruction::F64Eq);
+                function.instruction(&Instruction::Select);
+            }
+            None => {}
+        }
+    }
+    Ok(function)
+}
+
+/// Returns the type of the NaN `op` can produce from non-NaN operands or
+/// with a payload chosen by the hardware.
+fn produces_nan(op: &Operator) -> Option<ValType> {
+    use Operator::*;
+    match op {
+        F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Sqrt | F32Ceil | F32Floor
+        | F32Trunc | F32Nearest | F32DemoteF64 => Some(ValType::F32),
+        F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Sqrt | F64Ceil | F64Floor
+        | F64Trunc | F64Nearest | F64PromoteF32 => Some(ValType::F64),
+        _ => None,
+    }
+}
+
+fn parse_error(err: BinaryReaderError) -> Error {
+    Error::Compile(err.to_string())
+}
+
+fn reencode_error(err: reencode::Error) -> Error {
+    Error::Compile(err.to_string())
+}
+
+/// Deterministic replacements for the WASI clock and random imports.
+///
+/// Every clock reads the same virtual time, which starts at
+/// [`Host::start_time`] and advances by [`Host::tick`] on every read, and
+/// `random_get` fills buffers from a generator seeded with the seed given to
+/// [`Host::new`]. Two hosts created with the same settings therefore hand out
+/// the same values in the same order. A host is cheap to clone and every
+/// clone shares the same clock and generator.
+#[derive(Clone)]
+pub struct Host {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    rng: u64,
+    now: u64,
+    tick: u64,
+}
+
+impl Host {
+    /// Creates a host whose clock starts at zero and advances by one
+    /// millisecond per read.
+    pub fn new(seed: u64) -> Host {
+        Host {
+            state: Arc::new(Mutex::new(State {
+                rng: seed,
+                now: 0,
+                tick: 1_000_000,
+            })),
+        }
+    }
+
+    /// Sets the virtual time, in nanoseconds, the next clock read returns.
+    pub fn start_time(self, nanos: u64) -> Host {
+        self.lock().now = nanos;
+        self
+    }
+
+    /// Sets how far the virtual clock advances on every read.
+    pub fn tick(self, tick: Duration) -> Host {
+        self.lock().tick = tick.as_nanos() as u64;
+        self
+    }
+
+    /// Returns an import object with only this host's imports.
+    pub fn imports(&self) -> ImportObject {
+        let mut imports = ImportObject::new();
+        self.register(&mut imports);
+        imports
+    }
+
+    /// Adds this host's imports to `imports`, replacing anything already
+    /// registered in the [`WASI_NAMESPACE`] namespace.
+    pub fn register(&self, imports: &mut ImportObject) {
+        let mut namespace = Namespace::new();
+        let host = self.clone();
+        namespace.insert(
+            "clock_time_get",
+            func!(
+                move |ctx: &mut Ctx, id: i32, _precision: i64, out: i32| -> i32 {
+                    if !(0..WASI_CLOCKS).contains(&id) {
+                        return ERRNO_INVAL;
+                    }
+                    // Hold the lock across the write, so the clock only
+                    // advances once a read has reached the guest.
+                    let mut state = host.lock();
+                    let errno = write(ctx_memory(ctx), out, &state.now.to_le_bytes());
+                    if errno == ERRNO_SUCCESS {
+                        state.now = state.now.wrapping_add(state.tick);
+                    }
+                    errno
+                }
+            ),
+        );
+        let host = self.clone();
+        namespace.insert(
+            "clock_res_get",
+            func!(move |ctx: &mut Ctx, id: i32, out: i32| -> i32 {
+                if !(0..WASI_CLOCKS).contains(&id) {
+                    return ERRNO_INVAL;
+                }
+                let tick = host.lock().tick.max(1);
+                write(ctx_memory(ctx), out, &tick.to_le_bytes())
+            }),
+        );
+        let host = self.clone();
+        namespace.insert(
+            "random_get",
+            func!(move |ctx: &mut Ctx, buf: i32, len: i32| -> i32 {
+                let view = match ctx_memory(ctx) {
+                    Some(memory) => memory.view::<u8>(),
+                    None => return ERRNO_FAULT,
+                };
+                match guest_range(&view, buf, len as u32 as usize) {
+                    Some(cells) => {
+                        host.fill_cells(cells);
+                        ERRNO_SUCCESS
+                    }
+                    None => ERRNO_FAULT,
+                }
+            }),
+        );
+        imports.register(WASI_NAMESPACE, namespace);
+    }
+
+    /// Fills `bytes` from the seeded generator, as `random_get` does.
+    pub fn fill(&self, bytes: &mut [u8]) {
+        let mut state = self.lock();
+        for chunk in bytes.chunks_mut(8) {
+            let word = splitmix64(&mut state.rng).to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+    }
+
+    /// Fills guest memory in place with the bytes [`fill`](Self::fill)
+    /// would produce.
+    fn fill_cells(&self, cells: &[Cell<u8>]) {
+        let mut state = self.lock();
+        for chunk in cells.chunks(8) {
+            let word = splitmix64(&mut state.rng).to_le_bytes();
+            for (cell, byte) in chunk.iter().zip(&word) {
+                cell.set(*byte);
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Returns the `len` bytes of guest memory at `offset`, or `None` if they
+/// are out of bounds.
+fn guest_range(view: &[Cell<u8>], offset: i32, len: usize) -> Option<&[Cell<u8>]> {
+    view.get(offset as u32 as usize..)?.get(..len)
+}
+
+/// Copies `bytes` into guest memory at `offset`, returning a WASI errno.
+/// A module without a memory faults.
+fn write(memory: Option<&Memory>, offset: i32, bytes: &[u8]) -> i32 {
+    let view = match memory {
+        Some(memory) => memory.view::<u8>(),
+        None => return ERRNO_FAULT,
+    };
+    let cells = match guest_range(&view, offset, bytes.len()) {
+        Some(cells) => cells,
+        None => return ERRNO_FAULT,
+    };
+    for (cell, byte) in cells.iter().zip(bytes) {
+        cell.set(*byte);
+    }
+    ERRNO_SUCCESS
+}
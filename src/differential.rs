@@ -0,0 +1,243 @@
+//! Runs the same module and calls on every [`Backend`] and reports where
+//! their observable behaviour differs.
+//!
+//! Each run is summarised as an [`Observation`]: whether the module could be
+//! compiled and instantiated, the outcome of every call, and the final
+//! values of the exported globals and contents of the default memory. Two
+//! observations diverge if any of those differ, except that traps are
+//! compared without their messages and any NaN equals any other NaN of the
+//! same type, since the spec leaves NaN payloads nondeterministic.
+//!
+//! ```
+//! use adder::differential::{self, Call};
+//! use adder::Value;
+//!
+//! let wat = r#"(module (func (export "double") (param i32) (result i32)
+//!     local.get 0
+//!     local.get 0
+//!     i32.add))"#;
+//! let calls = [Call::new("double", &[Value::I32(21)])];
+//! let observations = differential::run(wat.as_bytes(), &calls)?;
+//! assert_eq!(observations[0].calls, [differential::Outcome::Returned(vec![Value::I32(42)])]);
+//! # Ok::<(), differential::Divergence>(())
+//! ```
+
+use crate::{Backend, Engine, Error, ExternType, Instance, Value};
+use std::fmt;
+use std::mem;
+
+/// A call to make after instantiating the module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub export: String,
+    pub args: Vec<Value>,
+}
+
+impl Call {
+    pub fn new(export: &str, args: &[Value]) -> Call {
+        Call {
+            export: export.to_string(),
+            args: args.to_vec(),
+        }
+    }
+}
+
+/// What a single call did.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Returned(Vec<Value>),
+    Trapped,
+    /// The call was rejected before running, e.g. because the export does
+    /// not exist or the arguments do not match its signature.
+    Rejected(Error),
+}
+
+/// Everything observable about running a module on one backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub backend: Backend,
+    /// Why the module could not be compiled or instantiated. If set, no
+    /// calls were made and `calls`, `globals` and `memory` are empty.
+    pub setup_error: Option<Error>,
+    pub calls: Vec<Outcome>,
+    /// The exported globals after the last call, in export order.
+    pub globals: Vec<(String, Value)>,
+    /// The default memory after the last call, if the module has one.
+    pub memory: Option<Vec<u8>>,
+}
+
+/// Two backends behaved differently.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub left: Backend,
+    pub right: Backend,
+    /// What differed, e.g. "call 2 (`add`): returned [I32(1)], then [I32(2)]".
+    pub what: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} and {} diverge: {}", self.left, self.right, self.what)
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Runs `input`, which must not import anything, on every backend in
+/// [`Backend::ALL`] and checks that they agree.
+///
+/// Returns one observation per backend, or the first divergence from the
+/// first backend's behaviour.
+pub fn run(input: &[u8], calls: &[Call]) -> Result<Vec<Observation>, Divergence> {
+    let observations: Vec<Observation> = Backend::ALL
+        .iter()
+        .map(|&backend| observe(backend, input, calls))
+        .collect();
+    for other in &observations[1..] {
+        if let Some(divergence) = compare(&observations[0], other, calls) {
+            return Err(divergence);
+        }
+    }
+    Ok(observations)
+}
+
+/// Compiles and instantiates `input` with `backend`, makes `calls` in order
+/// and records what happened.
+pub fn observe(backend: Backend, input: &[u8], calls: &[Call]) -> Observation {
+    let mut observation = Observation {
+        backend,
+        setup_error: None,
+        calls: Vec::new(),
+        globals: Vec::new(),
+        memory: None,
+    };
+    let instance = match Engine::with_backend(backend)
+        .compile(input)
+        .and_then(|module| module.instantiate())
+    {
+        Ok(instance) => instance,
+        Err(err) => {
+            observation.setup_error = Some(err);
+            return observation;
+        }
+    };
+
+    observation.calls = calls
+        .iter()
+        .map(|call| match instance.call(&call.export, &call.args) {
+            Ok(results) => Outcome::Returned(results),
+            Err(Error::Trap(_)) => Outcome::Trapped,
+            Err(err) => Outcome::Rejected(err),
+        })
+        .collect();
+    observation.globals = instance
+        .exports()
+        .into_iter()
+        .filter(|export| matches!(export.ty, ExternType::Global { .. }))
+        .filter_map(|export| {
+            let value = instance.global(&export.name).ok()?;
+            Some((export.name, value))
+        })
+        .collect();
+    observation.memory = memory(&instance);
+    observation
+}
+
+fn memory(instance: &Instance) -> Option<Vec<u8>> {
+    let mut bytes = vec![0; instance.memory_len().ok()?];
+    instance.read_memory(0, &mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// Returns how `right` differs from `left`, which were both made with
+/// `calls`, or `None` if they agree.
+pub fn compare(left: &Observation, right: &Observation, calls: &[Call]) -> Option<Divergence> {
+    let what = diff(left, right, calls)?;
+    Some(Divergence {
+        left: left.backend,
+        right: right.backend,
+        what,
+    })
+}
+
+fn diff(left: &Observation, right: &Observation, calls: &[Call]) -> Option<String> {
+    match (&left.setup_error, &right.setup_error) {
+        (None, None) => {}
+        (Some(a), Some(b)) if mem::discriminant(a) == mem::discriminant(b) => return None,
+        (a, b) => {
+            let describe = |err: &Option<Error>| match err {
+                Some(err) => err.to_string(),
+                None => "instantiated".to_string(),
+            };
+            return Some(format!("setup: {}, then {}", describe(a), describe(b)));
+        }
+    }
+
+    for (i, (a, b)) in left.calls.iter().zip(&right.calls).enumerate() {
+        let same = match (a, b) {
+            (Outcome::Returned(a), Outcome::Returned(b)) => same_values(a, b),
+            (Outcome::Trapped, Outcome::Trapped) => true,
+            (Outcome::Rejected(a), Outcome::Rejected(b)) => a == b,
+            _ => false,
+        };
+        if !same {
+            let export = calls.get(i).map_or("?", |call| call.export.as_str());
+            return Some(format!(
+                "call {} (`{}`): {}, then {}",
+                i,
+                export,
+                describe(a),
+                describe(b)
+            ));
+        }
+    }
+    if left.calls.len() != right.calls.len() {
+        return Some(format!(
+            "made {} calls, then {}",
+            left.calls.len(),
+            right.calls.len()
+        ));
+    }
+
+    for ((name, a), (_, b)) in left.globals.iter().zip(&right.globals) {
+        if !same_value(a, b) {
+            return Some(format!("global `{}`: {:?}, then {:?}", name, a, b));
+        }
+    }
+
+    match (&left.memory, &right.memory) {
+        (Some(a), Some(b)) if a.len() != b.len() => {
+            Some(format!("memory size: {} bytes, then {}", a.len(), b.len()))
+        }
+        (Some(a), Some(b)) => a.iter().zip(b).position(|(x, y)| x != y).map(|offset| {
+            format!(
+                "memory at {}: {:#04x}, then {:#04x}",
+                offset, a[offset], b[offset]
+            )
+        }),
+        (None, None) => None,
+        _ => Some("only one instance has a memory".to_string()),
+    }
+}
+
+fn describe(outcome: &Outcome) -> String {
+    match outcome {
+        Outcome::Returned(values) => format!("returned {:?}", values),
+        Outcome::Trapped => "trapped".to_string(),
+        Outcome::Rejected(err) => err.to_string(),
+    }
+}
+
+fn same_values(a: &[Value], b: &[Value]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(a, b))
+}
+
+/// Compares bit patterns, so `0.0` and `-0.0` differ, but treats all NaNs of
+/// a type as equal.
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::F32(a), Value::F32(b)) if a.is_nan() && b.is_nan() => true,
+        (Value::F64(a), Value::F64(b)) if a.is_nan() && b.is_nan() => true,
+        _ => a.ty() == b.ty() && a.to_bits() == b.to_bits(),
+    }
+}
@@ -0,0 +1,215 @@
+use crate::error::{Error, Result};
+use crate::module::Module;
+use crate::streaming::StreamingCompile;
+use crate::{deterministic, source};
+use std::borrow::Cow;
+use std::fmt;
+use wasm_encoder::reencode::{Reencode, RoundtripReencoder};
+use wasm_encoder::{
+    CodeSection, Function, FunctionSection, Instruction, RawSection, StartSection, TypeSection,
+};
+use wasmparser::{ExternalKind, Parser, Payload, TypeRef};
+
+/// A code generator modules can be compiled with.
+///
+/// Only the backends enabled in the runtime's features are listed; see
+/// [`Backend::ALL`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Backend {
+    #[default]
+    Cranelift,
+}
+
+impl Backend {
+    /// Every backend this build can compile with.
+    pub const ALL: &'static [Backend] = &[Backend::Cranelift];
+
+    fn to_wasmer(self) -> wasmer_runtime::Backend {
+        match self {
+            Backend::Cranelift => wasmer_runtime::Backend::Cranelift,
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Backend::Cranelift => "cranelift",
+        })
+    }
+}
+
+/// Compiles modules.
+///
+/// Modules are compiled with the engine's [`Backend`], Cranelift unless
+/// another is chosen with [`Engine::with_backend`]. An `Engine` is cheap to
+/// create and can be shared between threads.
+#[derive(Debug, Clone, Default)]
+pub struct Engine {
+    backend: Backend,
+    canonicalize_nans: bool,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an engine that compiles with `backend`.
+    pub fn with_backend(backend: Backend) -> Self {
+        Engine {
+            backend,
+            canonicalize_nans: false,
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Makes every NaN produced by float arithmetic in compiled modules the
+    /// canonical NaN, as described in [`deterministic::canonicalize_nans`].
+    pub fn canonicalize_nans(mut self, enable: bool) -> Self {
+        self.canonicalize_nans = enable;
+        self
+    }
+
+    /// Compiles `input`, which may be a binary module or a module in the
+    /// WebAssembly text format.
+    pub fn compile(&self, input: &[u8]) -> Result<Module> {
+        let mut wasm = source::wasm_bytes(input).map_err(Error::Parse)?;
+        if self.canonicalize_nans {
+            wasm = deterministic::canonicalize_nans(&wasm)?.into();
+        }
+        let compiler = wasmer_runtime::compiler_for_backend(self.backend.to_wasmer())
+            .expect("every `Backend` variant is enabled in the runtime");
+        wasmer_runtime::compile_with(&with_entry_point(&wasm), compiler.as_ref())
+            .map(Module::from_wasmer)
+            .map_err(|err| Error::Compile(err.to_string()))
+    }
+
+    /// Starts compiling a binary module that arrives in chunks, as described
+    /// in [`streaming`](crate::streaming).
+    pub fn begin_streaming(&self) -> StreamingCompile {
+        StreamingCompile::new(self)
+    }
+
+    /// Checks that `input` is a valid module without compiling it.
+    pub fn validate(&self, input: &[u8]) -> Result<()> {
+        let wasm = source::wasm_bytes(input).map_err(Error::Parse)?;
+        wasmer_runtime_core::validate_and_report_errors(&wasm).map_err(Error::Compile)
+    }
+}
+
+/// Gives a module that exports no function and has no start function an
+/// empty start function.
+///
+/// The runtime maps one block of memory for the entry points of a module's
+/// exported functions and start function, and for a module with none it
+/// builds a slice over the null pointer left for an empty block, which is
+/// undefined behavior. An empty start function keeps it off that path
+/// without changing the module's imports, exports or behavior. Malformed
+/// input is returned as is for the runtime to reject.
+pub(crate) fn with_entry_point(wasm: &[u8]) -> Cow<'_, [u8]> {
+    match add_start(wasm) {
+        Some(padded) => Cow::Owned(padded),
+        None => Cow::Borrowed(wasm),
+    }
+}
+
+/// Returns `wasm` with an empty start function, or `None` if it doesn't
+/// need one or can't be parsed.
+fn add_start(wasm: &[u8]) -> Option<Vec<u8>> {
+    let mut sections = Vec::new();
+    let mut types = TypeSection::new();
+    let mut type_count = 0;
+    let mut functions = FunctionSection::new();
+    let mut function_count = 0;
+    let mut code = CodeSection::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload.ok()?;
+        match &payload {
+            Payload::StartSection { .. } => return None,
+            Payload::ExportSection(reader) => {
+                for export in reader.clone() {
+                    if export.ok()?.kind == ExternalKind::Func {
+                        return None;
+                    }
+                }
+            }
+            Payload::ImportSection(reader) => {
+                for import in reader.clone().into_imports() {
+                    if let TypeRef::Func(_) | TypeRef::FuncExact(_) = import.ok()?.ty {
+                        function_count += 1;
+                    }
+                }
+            }
+            Payload::TypeSection(reader) => {
+                type_count = reader.count();
+                RoundtripReencoder
+                    .parse_type_section(&mut types, reader.clone())
+                    .ok()?;
+                continue;
+            }
+            Payload::FunctionSection(reader) => {
+                function_count += reader.count();
+                RoundtripReencoder
+                    .parse_function_section(&mut functions, reader.clone())
+                    .ok()?;
+                continue;
+            }
+            Payload::CodeSectionStart { .. } => continue,
+            Payload::CodeSectionEntry(body) => {
+                code.raw(body.as_bytes());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some((id, range)) = payload.as_section() {
+            sections.push((id, range));
+        }
+    }
+
+    types.ty().function([], []);
+    functions.function(type_count);
+    let mut function = Function::new([]);
+    function.instruction(&Instruction::End);
+    code.function(&function);
+    let start = StartSection {
+        function_index: function_count,
+    };
+
+    // Known sections must appear in this order. Custom sections stay after
+    // the section they followed.
+    const ORDER: [u8; 13] = [1, 2, 3, 4, 5, 13, 6, 7, 8, 9, 12, 10, 11];
+    let position = |id: u8| ORDER.iter().position(|&known| known == id);
+    let mut module = wasm_encoder::Module::new();
+    let mut pending = vec![1, 3, 8, 10];
+    let emit = |module: &mut wasm_encoder::Module, id: u8| {
+        match id {
+            1 => module.section(&types),
+            3 => module.section(&functions),
+            8 => module.section(&start),
+            _ => module.section(&code),
+        };
+    };
+    for (id, range) in sections {
+        if let Some(at) = position(id) {
+            while let Some(&next) = pending.first() {
+                if position(next) >= Some(at) {
+                    break;
+                }
+                emit(&mut module, next);
+                pending.remove(0);
+            }
+        }
+        module.section(&RawSection {
+            id,
+            data: &wasm[range],
+        });
+    }
+    for id in pending {
+        emit(&mut module, id);
+    }
+    Some(module.finish())
+}
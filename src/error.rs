@@ -0,0 +1,54 @@
+use crate::source::ParseError;
+use std::fmt;
+use wasmer_runtime::error::CallError;
+
+/// Errors produced by [`Engine`](crate::Engine), [`Module`](crate::Module)
+/// and [`Instance`](crate::Instance).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The input is neither a wasm binary nor valid WebAssembly text.
+    Parse(ParseError),
+    /// The module failed validation or could not be compiled.
+    Compile(String),
+    /// The module's imports could not be resolved, or its start function
+    /// trapped.
+    Instantiate(String),
+    /// The instance has no export with this name, or it has the wrong kind.
+    ExportNotFound(String),
+    /// The arguments passed to a function, or a value written to a global,
+    /// do not match its type.
+    SignatureMismatch(String),
+    /// Execution trapped.
+    Trap(String),
+    /// The instance has no memory, an access was out of bounds, or memory
+    /// could not be grown.
+    Memory(String),
+}
+
+/// The result type used throughout the crate's safe API.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(err) => write!(f, "invalid WebAssembly text: {}", err),
+            Error::Compile(err) => write!(f, "could not compile module: {}", err),
+            Error::Instantiate(err) => write!(f, "could not instantiate module: {}", err),
+            Error::ExportNotFound(name) => write!(f, "export not found: {}", name),
+            Error::SignatureMismatch(err) => write!(f, "signature mismatch: {}", err),
+            Error::Trap(err) => write!(f, "trap: {}", err),
+            Error::Memory(err) => write!(f, "memory error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<CallError> for Error {
+    fn from(err: CallError) -> Self {
+        match err {
+            CallError::Runtime(err) => Error::Trap(err.to_string()),
+            CallError::Resolve(err) => Error::SignatureMismatch(err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,1177 @@
+//! The C ABI over the safe API, declared in `include/adder.h`.
+//!
+//! Every fallible function returns a [`WasmStatus`] and leaves a description
+//! of the failure for [`wasm_last_error_message`]. Modules, streams,
+//! instances, linkers, pools and registries are handed out as opaque
+//! pointers that the caller frees.
+//!
+//! No panic unwinds into the caller: a panic in the runtime is caught at the
+//! boundary and reported as [`WasmStatus::Panic`].
+
+use crate::pool::{Exhaustion, Pool, PoolError, Reset};
+use crate::registry::{Registry, RegistryError, Version};
+use crate::streaming::StreamingCompile;
+use crate::{
+    reload, Engine, Error, ExternType, ImportObject, Instance, Linker, Module, Result, Value,
+};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// Runs `add_one(42)` from the bundled `add.wasm` and prints the result.
+///
+/// Returns 0 on success, or 1 if the module could not be loaded or called.
+#[no_mangle]
+pub extern "C" fn load_wasm() -> i32 {
+    match panic::catch_unwind(run_add_one) {
+        Ok(Ok(())) => 0,
+        Ok(Err(err)) => {
+            eprintln!("load_wasm failed: {}", err);
+            1
+        }
+        Err(_) => 1,
+    }
+}
+
+fn run_add_one() -> Result<()> {
+    // Let's get the .wasm file as bytes
+    let wasm_bytes = include_bytes!("add.wasm");
+
+    // Let's compile the module and create an instance of it. We're not
+    // importing anything, so it is instantiated without imports.
+    let instance = Engine::new().compile(wasm_bytes)?.instantiate()?;
+
+    // Let's call `add_one`, which takes one `u32` and returns one `u32`
+    let results = instance.call("add_one", &[Value::from(42u32)])?;
+    let result = match results[..] {
+        [Value::I32(result)] => result as u32,
+        _ => {
+            return Err(Error::SignatureMismatch(
+                "`add_one` should return one i32".to_string(),
+            ))
+        }
+    };
+
+    // Log the new value
+    println!("Result: {}", result);
+
+    // Asserting that the returned value from the function is our expected value.
+    assert_eq!(result, 43);
+
+    // Return OK since everything executed successfully!
+    Ok(())
+}
+
+/// `WasmValue::kind` of a 32-bit integer.
+pub const WASM_I32: u32 = 0;
+/// `WasmValue::kind` of a 64-bit integer.
+pub const WASM_I64: u32 = 1;
+/// `WasmValue::kind` of a 32-bit float.
+pub const WASM_F32: u32 = 2;
+/// `WasmValue::kind` of a 64-bit float.
+pub const WASM_F64: u32 = 3;
+
+/// `wasm_pool_new` reset policy: keep instance state between calls.
+pub const WASM_POOL_RESET_KEEP: u32 = 0;
+/// `wasm_pool_new` reset policy: restore each instance's initial state.
+pub const WASM_POOL_RESET_SNAPSHOT: u32 = 1;
+/// `wasm_pool_new` reset policy: replace each instance after every call.
+pub const WASM_POOL_RESET_REINSTANTIATE: u32 = 2;
+
+/// `wasm_pool_new` exhaustion policy: wait for an instance to be returned.
+pub const WASM_POOL_BLOCK: u32 = 0;
+/// `wasm_pool_new` exhaustion policy: fail with `WasmStatus::PoolExhausted`.
+pub const WASM_POOL_FAIL: u32 = 1;
+/// `wasm_pool_new` exhaustion policy: create an instance beyond the maximum.
+pub const WASM_POOL_GROW: u32 = 2;
+
+/// `wasm_registry_list` kind: registered modules.
+pub const WASM_REGISTRY_MODULES: u32 = 0;
+/// `wasm_registry_list` kind: registered instances.
+pub const WASM_REGISTRY_INSTANCES: u32 = 1;
+
+/// Result of every fallible FFI function. On anything other than `Ok`, a
+/// description of the failure is available from `wasm_last_error_message`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmStatus {
+    Ok = 0,
+    /// A required pointer was null, a name was not UTF-8, or a value had an
+    /// unknown kind.
+    InvalidArgument = 1,
+    /// The input is neither a wasm binary nor valid WebAssembly text.
+    ParseError = 2,
+    CompileError = 3,
+    InstantiateError = 4,
+    ExportNotFound = 5,
+    /// The arguments or result buffer do not match the export's signature.
+    SignatureMismatch = 6,
+    Trap = 7,
+    /// The instance has no memory, or an access was out of bounds.
+    MemoryError = 8,
+    /// The runtime panicked. Any module or instance passed to the call may
+    /// be in an inconsistent state and should only be deleted.
+    Panic = 9,
+    /// Every instance in a pool is in use and the pool may not grow.
+    PoolExhausted = 10,
+    /// Nothing is registered under the name and version, or linked under
+    /// the instance name.
+    NotFound = 11,
+}
+
+impl From<&Error> for WasmStatus {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::Parse(_) => WasmStatus::ParseError,
+            Error::Compile(_) => WasmStatus::CompileError,
+            Error::Instantiate(_) => WasmStatus::InstantiateError,
+            Error::ExportNotFound(_) => WasmStatus::ExportNotFound,
+            Error::SignatureMismatch(_) => WasmStatus::SignatureMismatch,
+            Error::Trap(_) => WasmStatus::Trap,
+            Error::Memory(_) => WasmStatus::MemoryError,
+        }
+    }
+}
+
+/// The payload of a `WasmValue`, selected by its `kind`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union WasmValueOf {
+    pub i32: i32,
+    pub i64: i64,
+    pub f32: f32,
+    pub f64: f64,
+}
+
+/// A WebAssembly value passed to or returned from `wasm_instance_call`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct WasmValue {
+    /// One of `WASM_I32`, `WASM_I64`, `WASM_F32` or `WASM_F64`.
+    pub kind: u32,
+    pub of: WasmValueOf,
+}
+
+impl fmt::Debug for WasmValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match value_from_ffi(self) {
+            Some(value) => value.fmt(f),
+            None => write!(f, "WasmValue {{ kind: {} }}", self.kind),
+        }
+    }
+}
+
+/// A compiled module, created by `wasm_module_new`.
+pub struct WasmModule(Module);
+
+/// A module being compiled from chunks, created by `wasm_stream_new`.
+pub struct WasmStream(StreamingCompile);
+
+/// An instance of a module, created by `wasm_instance_new`.
+pub struct WasmInstance(Instance);
+
+/// Instances linked so that each can import the exports of those before it,
+/// created by `wasm_linker_new`.
+pub struct WasmLinker(Linker);
+
+/// Modules and instances by name and version, created by
+/// `wasm_registry_new`. It may be used from any thread.
+pub struct WasmRegistry(Registry);
+
+/// A pool of instances of a module that can be called from any thread,
+/// created by `wasm_pool_new`.
+pub struct WasmPool(Pool);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(status: WasmStatus, message: impl ToString) -> WasmStatus {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    status
+}
+
+fn fail_with(err: Error) -> WasmStatus {
+    fail(WasmStatus::from(&err), err)
+}
+
+/// Runs the body of an FFI function, turning a panic into
+/// `WasmStatus::Panic` instead of unwinding into C.
+fn guard(body: impl FnOnce() -> WasmStatus) -> WasmStatus {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        fail(WasmStatus::Panic, format!("panic: {}", message))
+    })
+}
+
+/// Returns a description of the last failure on this thread, or null if
+/// nothing has failed yet. The string stays valid until the next failure.
+#[no_mangle]
+pub extern "C" fn wasm_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Compiles `len` bytes of binary or text module at `bytes`, storing a new
+/// module in `*module_out`. Free it with `wasm_module_delete`.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes and `module_out` must be
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_new(
+    bytes: *const u8,
+    len: usize,
+    module_out: *mut *mut WasmModule,
+) -> WasmStatus {
+    if bytes.is_null() || module_out.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let input = slice::from_raw_parts(bytes, len);
+    guard(|| match Engine::new().compile(input) {
+        Ok(module) => {
+            *module_out = Box::into_raw(Box::new(WasmModule(module)));
+            WasmStatus::Ok
+        }
+        Err(err) => fail_with(err),
+    })
+}
+
+/// Frees a module. Instances created from it remain valid.
+///
+/// # Safety
+///
+/// `module` must be null or a pointer returned by `wasm_module_new` that has
+/// not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_delete(module: *mut WasmModule) {
+    if !module.is_null() {
+        drop(Box::from_raw(module));
+    }
+}
+
+/// Starts compiling a binary module from chunks, storing the stream in
+/// `*stream_out`. If the module's total length is known, pass it as
+/// `expected_len` to allocate the buffer once; otherwise pass 0. Feed it
+/// with `wasm_stream_push` and end it with `wasm_stream_finish`, or free it
+/// with `wasm_stream_delete` to give up.
+///
+/// # Safety
+///
+/// `stream_out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_stream_new(
+    expected_len: usize,
+    stream_out: *mut *mut WasmStream,
+) -> WasmStatus {
+    if stream_out.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    guard(|| {
+        let mut stream = Engine::new().begin_streaming();
+        stream.reserve(expected_len);
+        *stream_out = Box::into_raw(Box::new(WasmStream(stream)));
+        WasmStatus::Ok
+    })
+}
+
+/// Appends the `len` bytes at `bytes` to the module and validates the
+/// sections they complete, failing as soon as the module is known to be
+/// invalid. Once a push fails, every later push and `wasm_stream_finish`
+/// fail the same way.
+///
+/// # Safety
+///
+/// `stream` must be a live stream and `bytes` must point to `len` readable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_stream_push(
+    stream: *mut WasmStream,
+    bytes: *const u8,
+    len: usize,
+) -> WasmStatus {
+    if stream.is_null() || bytes.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let stream = &mut (*stream).0;
+    let chunk = slice::from_raw_parts(bytes, len);
+    guard(|| match stream.push(chunk) {
+        Ok(()) => WasmStatus::Ok,
+        Err(err) => fail_with(err),
+    })
+}
+
+/// Compiles the module pushed to `stream`, storing it in `*module_out`, and
+/// frees the stream whether or not it succeeds.
+///
+/// # Safety
+///
+/// `stream` must be a live stream, which must not be used afterwards, and
+/// `module_out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_stream_finish(
+    stream: *mut WasmStream,
+    module_out: *mut *mut WasmModule,
+) -> WasmStatus {
+    if stream.is_null() || module_out.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let stream = Box::from_raw(stream).0;
+    guard(|| match stream.finish() {
+        Ok(module) => {
+            *module_out = Box::into_raw(Box::new(WasmModule(module)));
+            WasmStatus::Ok
+        }
+        Err(err) => fail_with(err),
+    })
+}
+
+/// Frees a stream without compiling it.
+///
+/// # Safety
+///
+/// `stream` must be null or a pointer returned by `wasm_stream_new` that has
+/// not been finished or freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_stream_delete(stream: *mut WasmStream) {
+    if !stream.is_null() {
+        drop(Box::from_raw(stream));
+    }
+}
+
+/// Instantiates `module` with no imports, storing a new instance in
+/// `*instance_out`. Free it with `wasm_instance_delete`.
+///
+/// # Safety
+///
+/// `module` must be a live module and `instance_out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_instance_new(
+    module: *const WasmModule,
+    instance_out: *mut *mut WasmInstance,
+) -> WasmStatus {
+    if module.is_null() || instance_out.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let module = &(*module).0;
+    guard(|| match module.instantiate() {
+        Ok(instance) => {
+            *instance_out = Box::into_raw(Box::new(WasmInstance(instance)));
+            WasmStatus::Ok
+        }
+        Err(err) => fail_with(err),
+    })
+}
+
+/// Frees an instance.
+///
+/// # Safety
+///
+/// `instance` must be null or a pointer returned by `wasm_instance_new`
+/// that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_instance_delete(instance: *mut WasmInstance) {
+    if !instance.is_null() {
+        drop(Box::from_raw(instance));
+    }
+}
+
+/// Replaces `instance` with an instance of the module in the `len` bytes at
+/// `bytes`, carrying over its memories and mutable globals if the new module
+/// is compatible, as described in the `reload` module of the Rust API.
+/// `*carried_out` is set to whether state was carried over. If
+/// `report_out` is not null, it receives a description of the added,
+/// removed and changed exports, to be freed with `wasm_string_delete`. On
+/// failure the instance is left as it was.
+///
+/// # Safety
+///
+/// `instance` must be a live instance, `bytes` must point to `len` readable
+/// bytes, `carried_out` must be writable, and `report_out` null or
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_instance_reload(
+    instance: *mut WasmInstance,
+    bytes: *const u8,
+    len: usize,
+    carried_out: *mut bool,
+    report_out: *mut *mut c_char,
+) -> WasmStatus {
+    if instance.is_null() || bytes.is_null() || carried_out.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let instance = &mut (*instance).0;
+    let input = slice::from_raw_parts(bytes, len);
+    guard(
+        || match reload::reload(instance, &Engine::new(), input, &ImportObject::new()) {
+            Ok(report) => {
+                *carried_out = report.carried();
+                if !report_out.is_null() {
+                    let text = report.to_string().replace('\0', " ");
+                    *report_out = CString::new(text).unwrap().into_raw();
+                }
+                WasmStatus::Ok
+            }
+            Err(err) => fail_with(err),
+        },
+    )
+}
+
+/// Frees a string returned by this library, such as the report from
+/// `wasm_instance_reload`.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by this library that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_string_delete(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Calls the exported function `name` with `args_len` arguments, writing
+/// its results to `results`, which must hold exactly as many values as the
+/// function returns.
+///
+/// # Safety
+///
+/// `instance` must be a live instance, `name` a NUL-terminated string,
+/// `args` must point to `args_len` values and `results` to `results_len`
+/// writable values. Either array may be null when its length is zero.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_instance_call(
+    instance: *mut WasmInstance,
+    name: *const c_char,
+    args: *const WasmValue,
+    args_len: usize,
+    results: *mut WasmValue,
+    results_len: usize,
+) -> WasmStatus {
+    if instance.is_null()
+        || name.is_null()
+        || (args.is_null() && args_len > 0)
+        || (results.is_null() && results_len > 0)
+    {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(name) => name,
+        Err(_) => return fail(WasmStatus::InvalidArgument, "export name is not UTF-8"),
+    };
+    let args = if args_len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(args, args_len)
+    };
+
+    let instance = &(*instance).0;
+    guard(|| call(instance, name, args, results, results_len))
+}
+
+/// Creates an empty linker, storing it in `*linker_out`. Free it with
+/// `wasm_linker_delete`.
+///
+/// # Safety
+///
+/// `linker_out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_linker_new(linker_out: *mut *mut WasmLinker) -> WasmStatus {
+    if linker_out.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    guard(|| {
+        *linker_out = Box::into_raw(Box::new(WasmLinker(Linker::new())));
+        WasmStatus::Ok
+    })
+}
+
+/// Frees a linker and every instance in it.
+///
+/// # Safety
+///
+/// `linker` must be null or a pointer returned by `wasm_linker_new` that has
+/// not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_linker_delete(linker: *mut WasmLinker) {
+    if !linker.is_null() {
+        drop(Box::from_raw(linker));
+    }
+}
+
+/// Instantiates `module`, resolving its imports from the exports of the
+/// instances already in `linker`, each under the namespace it was named.
+/// The new instance is added to the linker as `name`.
+///
+/// # Safety
+///
+/// `linker` and `module` must be live and `name` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_linker_instantiate(
+    linker: *mut WasmLinker,
+    name: *const c_char,
+    module: *const WasmModule,
+) -> WasmStatus {
+    if linker.is_null() || module.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let name = match str_arg(name, "instance name") {
+        Ok(name) => name,
+        Err(status) => return status,
+    };
+    let linker = &mut (*linker).0;
+    let module = &(*module).0;
+    guard(|| match linker.instantiate(name, module) {
+        Ok(_) => WasmStatus::Ok,
+        Err(err) => fail_with(err),
+    })
+}
+
+/// Like `wasm_instance_call`, on the instance named `instance` in `linker`.
+/// Returns `WASM_STATUS_NOT_FOUND` if nothing is linked under that name.
+///
+/// # Safety
+///
+/// As for `wasm_instance_call`, with `linker` a live linker and `instance` a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_linker_call(
+    linker: *const WasmLinker,
+    instance: *const c_char,
+    name: *const c_char,
+    args: *const WasmValue,
+    args_len: usize,
+    results: *mut WasmValue,
+    results_len: usize,
+) -> WasmStatus {
+    if linker.is_null()
+        || (args.is_null() && args_len > 0)
+        || (results.is_null() && results_len > 0)
+    {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let (instance, name) = match (
+        str_arg(instance, "instance name"),
+        str_arg(name, "export name"),
+    ) {
+        (Ok(instance), Ok(name)) => (instance, name),
+        (Err(status), _) | (_, Err(status)) => return status,
+    };
+    let instance = match (*linker).0.get(instance) {
+        Some(instance) => instance,
+        None => {
+            return fail(
+                WasmStatus::NotFound,
+                format!("no instance is linked as `{}`", instance),
+            )
+        }
+    };
+    let args = if args_len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(args, args_len)
+    };
+    guard(|| call(instance, name, args, results, results_len))
+}
+
+/// Makes `linker` resolve imports from namespaces with nothing linked
+/// under them by instantiating the module registered under that name in
+/// `registry`, at its highest version.
+///
+/// # Safety
+///
+/// `linker` and `registry` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_linker_use_registry(
+    linker: *mut WasmLinker,
+    registry: *const WasmRegistry,
+) -> WasmStatus {
+    if linker.is_null() || registry.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let linker = &mut (*linker).0;
+    let registry = &(*registry).0;
+    guard(|| {
+        linker.registry(registry.clone());
+        WasmStatus::Ok
+    })
+}
+
+/// Reads a NUL-terminated UTF-8 argument, recording an error if it is null
+/// or not UTF-8.
+unsafe fn str_arg<'a>(ptr: *const c_char, what: &str) -> std::result::Result<&'a str, WasmStatus> {
+    if ptr.is_null() {
+        return Err(fail(WasmStatus::InvalidArgument, "null pointer"));
+    }
+    CStr::from_ptr(ptr).to_str().map_err(|_| {
+        fail(
+            WasmStatus::InvalidArgument,
+            format!("{} is not UTF-8", what),
+        )
+    })
+}
+
+/// Creates an empty registry, storing it in `*registry_out`. Free it with
+/// `wasm_registry_delete`.
+///
+/// # Safety
+///
+/// `registry_out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_registry_new(registry_out: *mut *mut WasmRegistry) -> WasmStatus {
+    if registry_out.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    guard(|| {
+        *registry_out = Box::into_raw(Box::new(WasmRegistry(Registry::new())));
+        WasmStatus::Ok
+    })
+}
+
+/// Frees a registry. Linkers using it keep what they need alive.
+///
+/// # Safety
+///
+/// `registry` must be null or a pointer returned by `wasm_registry_new` that
+/// has not been freed yet, and must not be in use by another thread.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_registry_delete(registry: *mut WasmRegistry) {
+    if !registry.is_null() {
+        drop(Box::from_raw(registry));
+    }
+}
+
+/// Registers `module` as `name` at `version`, a `major.minor.patch` string.
+/// The caller keeps its module handle.
+///
+/// # Safety
+///
+/// `registry` and `module` must be live, and `name` and `version`
+/// NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_registry_register_module(
+    registry: *const WasmRegistry,
+    name: *const c_char,
+    version: *const c_char,
+    module: *const WasmModule,
+) -> WasmStatus {
+    if registry.is_null() || module.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let (name, version) = match name_version(name, version, true) {
+        Ok((name, version)) => (name, version.unwrap()),
+        Err(status) => return status,
+    };
+    let registry = &(*registry).0;
+    let module = &(*module).0;
+    guard(
+        || match registry.register_module(name, version, module.clone()) {
+            Ok(()) => WasmStatus::Ok,
+            Err(err) => fail_with_registry(err),
+        },
+    )
+}
+
+/// Looks up the module registered as `name` at `version`, or at its highest
+/// version if `version` is null, storing a new handle to it in
+/// `*module_out`. Free the handle with `wasm_module_delete`.
+///
+/// # Safety
+///
+/// `registry` must be live, `name` a NUL-terminated string, `version` null or
+/// a NUL-terminated string, and `module_out` writable.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_registry_module(
+    registry: *const WasmRegistry,
+    name: *const c_char,
+    version: *const c_char,
+    module_out: *mut *mut WasmModule,
+) -> WasmStatus {
+    if registry.is_null() || module_out.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let (name, version) = match name_version(name, version, false) {
+        Ok(args) => args,
+        Err(status) => return status,
+    };
+    let registry = &(*registry).0;
+    guard(|| match registry.module(name, version) {
+        Some(module) => {
+            *module_out = Box::into_raw(Box::new(WasmModule(module)));
+            WasmStatus::Ok
+        }
+        None => not_registered(name, version),
+    })
+}
+
+/// Removes the module registered as `name` at `version`. Handles to it and
+/// its instances stay valid.
+///
+/// # Safety
+///
+/// `registry` must be live, and `name` and `version` NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_registry_unload_module(
+    registry: *const WasmRegistry,
+    name: *const c_char,
+    version: *const c_char,
+) -> WasmStatus {
+    if registry.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let (name, version) = match name_version(name, version, true) {
+        Ok((name, version)) => (name, version.unwrap()),
+        Err(status) => return status,
+    };
+    let registry = &(*registry).0;
+    guard(|| match registry.unload_module(name, version) {
+        Ok(_) => WasmStatus::Ok,
+        Err(err) => fail_with_registry(err),
+    })
+}
+
+/// Registers `instance` as `name` at `version`. The registry takes ownership
+/// of the instance, even on failure: do not call `wasm_instance_delete` on it
+/// afterwards.
+///
+/// # Safety
+///
+/// `registry` must be live, `instance` a pointer returned by
+/// `wasm_instance_new` that has not been freed yet, and `name` and `version`
+/// NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_registry_register_instance(
+    registry: *const WasmRegistry,
+    name: *const c_char,
+    version: *const c_char,
+    instance: *mut WasmInstance,
+) -> WasmStatus {
+    if instance.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let instance = Box::from_raw(instance).0;
+    if registry.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let (name, version) = match name_version(name, version, true) {
+        Ok((name, version)) => (name, version.unwrap()),
+        Err(status) => return status,
+    };
+    let registry = &(*registry).0;
+    guard(
+        || match registry.register_instance(name, version, instance) {
+            Ok(()) => WasmStatus::Ok,
+            Err(err) => fail_with_registry(err),
+        },
+    )
+}
+
+/// Like `wasm_instance_call`, on the instance registered as `instance` at
+/// `version`, or at its highest version if `version` is null. Calls to the
+/// same instance from several threads take turns.
+///
+/// # Safety
+///
+/// As for `wasm_instance_call`, with `registry` live, `instance` a
+/// NUL-terminated string and `version` null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_registry_call(
+    registry: *const WasmRegistry,
+    instance: *const c_char,
+    version: *const c_char,
+    name: *const c_char,
+    args: *const WasmValue,
+    args_len: usize,
+    results: *mut WasmValue,
+    results_len: usize,
+) -> WasmStatus {
+    if registry.is_null()
+        || (args.is_null() && args_len > 0)
+        || (results.is_null() && results_len > 0)
+    {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let (instance, version) = match name_version(instance, version, false) {
+        Ok(args) => args,
+        Err(status) => return status,
+    };
+    let name = match str_arg(name, "export name") {
+        Ok(name) => name,
+        Err(status) => return status,
+    };
+    let shared = match (*registry).0.instance(instance, version) {
+        Some(shared) => shared,
+        None => return not_registered(instance, version),
+    };
+    let args = if args_len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(args, args_len)
+    };
+    guard(|| {
+        let instance = shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        call(&instance, name, args, results, results_len)
+    })
+}
+
+/// Removes the instance registered as `name` at `version`. It is freed once
+/// calls already running on it return.
+///
+/// # Safety
+///
+/// `registry` must be live, and `name` and `version` NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_registry_unload_instance(
+    registry: *const WasmRegistry,
+    name: *const c_char,
+    version: *const c_char,
+) -> WasmStatus {
+    if registry.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let (name, version) = match name_version(name, version, true) {
+        Ok((name, version)) => (name, version.unwrap()),
+        Err(status) => return status,
+    };
+    let registry = &(*registry).0;
+    guard(|| match registry.unload_instance(name, version) {
+        Ok(_) => WasmStatus::Ok,
+        Err(err) => fail_with_registry(err),
+    })
+}
+
+/// Lists the registered modules (`WASM_REGISTRY_MODULES`) or instances
+/// (`WASM_REGISTRY_INSTANCES`) as NUL-terminated text with one
+/// `name version` line per entry, in order. `*len_out` is set to the
+/// length of the text including the NUL. If that is more than `buffer_len`,
+/// nothing is written and `WASM_STATUS_INVALID_ARGUMENT` is returned, so
+/// call again with a larger buffer.
+///
+/// # Safety
+///
+/// `registry` must be live, `buffer` must point to `buffer_len` writable
+/// bytes, and `len_out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_registry_list(
+    registry: *const WasmRegistry,
+    kind: u32,
+    buffer: *mut c_char,
+    buffer_len: usize,
+    len_out: *mut usize,
+) -> WasmStatus {
+    if registry.is_null() || len_out.is_null() || (buffer.is_null() && buffer_len > 0) {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let registry = &(*registry).0;
+    guard(|| {
+        let entries = match kind {
+            WASM_REGISTRY_MODULES => registry.modules(),
+            WASM_REGISTRY_INSTANCES => registry.instances(),
+            _ => return fail(WasmStatus::InvalidArgument, "unknown registry list kind"),
+        };
+        let mut text = String::new();
+        for (name, version) in entries {
+            text.push_str(&format!("{} {}\n", name, version));
+        }
+        *len_out = text.len() + 1;
+        if text.len() + 1 > buffer_len {
+            return fail(
+                WasmStatus::InvalidArgument,
+                format!("the list needs a buffer of {} bytes", text.len() + 1),
+            );
+        }
+        ptr::copy_nonoverlapping(text.as_ptr(), buffer as *mut u8, text.len());
+        *buffer.add(text.len()) = 0;
+        WasmStatus::Ok
+    })
+}
+
+/// Reads a name and a version argument. The version may be null unless
+/// `required` is set.
+unsafe fn name_version<'a>(
+    name: *const c_char,
+    version: *const c_char,
+    required: bool,
+) -> std::result::Result<(&'a str, Option<Version>), WasmStatus> {
+    let name = str_arg(name, "name")?;
+    if version.is_null() && !required {
+        return Ok((name, None));
+    }
+    match str_arg(version, "version")?.parse() {
+        Ok(version) => Ok((name, Some(version))),
+        Err(err) => Err(fail_with_registry(err)),
+    }
+}
+
+fn not_registered(name: &str, version: Option<Version>) -> WasmStatus {
+    match version {
+        Some(version) => fail(
+            WasmStatus::NotFound,
+            format!("`{}` {} is not registered", name, version),
+        ),
+        None => fail(
+            WasmStatus::NotFound,
+            format!("`{}` is not registered", name),
+        ),
+    }
+}
+
+fn fail_with_registry(err: RegistryError) -> WasmStatus {
+    match err {
+        RegistryError::NotFound { .. } => fail(WasmStatus::NotFound, err),
+        RegistryError::AlreadyRegistered { .. } | RegistryError::InvalidVersion(_) => {
+            fail(WasmStatus::InvalidArgument, err)
+        }
+    }
+}
+
+/// Creates a pool of instances of `module` with no imports, storing it in
+/// `*pool_out`. `reset` is one of the `WASM_POOL_RESET_*` constants and
+/// `exhaustion` one of `WASM_POOL_BLOCK`, `WASM_POOL_FAIL` or
+/// `WASM_POOL_GROW`. Free the pool with `wasm_pool_delete`.
+///
+/// # Safety
+///
+/// `module` must be a live module and `pool_out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_pool_new(
+    module: *const WasmModule,
+    min: usize,
+    max: usize,
+    reset: u32,
+    exhaustion: u32,
+    pool_out: *mut *mut WasmPool,
+) -> WasmStatus {
+    if module.is_null() || pool_out.is_null() {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    if max == 0 || min > max {
+        return fail(
+            WasmStatus::InvalidArgument,
+            "pool sizes must satisfy min <= max and max > 0",
+        );
+    }
+    let reset = match reset {
+        WASM_POOL_RESET_KEEP => Reset::Keep,
+        WASM_POOL_RESET_SNAPSHOT => Reset::Snapshot,
+        WASM_POOL_RESET_REINSTANTIATE => Reset::Reinstantiate,
+        _ => return fail(WasmStatus::InvalidArgument, "unknown reset policy"),
+    };
+    let exhaustion = match exhaustion {
+        WASM_POOL_BLOCK => Exhaustion::Block,
+        WASM_POOL_FAIL => Exhaustion::Fail,
+        WASM_POOL_GROW => Exhaustion::Grow,
+        _ => return fail(WasmStatus::InvalidArgument, "unknown exhaustion policy"),
+    };
+    let module = (*module).0.clone();
+    guard(|| {
+        let pool = Pool::builder(module)
+            .min(min)
+            .max(max)
+            .reset(reset)
+            .on_exhaustion(exhaustion)
+            .build();
+        match pool {
+            Ok(pool) => {
+                *pool_out = Box::into_raw(Box::new(WasmPool(pool)));
+                WasmStatus::Ok
+            }
+            Err(err) => fail_with_pool(err),
+        }
+    })
+}
+
+/// Frees a pool. It must not be in use by another thread.
+///
+/// # Safety
+///
+/// `pool` must be null or a pointer returned by `wasm_pool_new` that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_pool_delete(pool: *mut WasmPool) {
+    if !pool.is_null() {
+        drop(Box::from_raw(pool));
+    }
+}
+
+/// Like `wasm_instance_call`, but on an instance checked out of `pool` for
+/// the duration of the call. Any number of threads may call into the same
+/// pool at once.
+///
+/// # Safety
+///
+/// As for `wasm_instance_call`, with `pool` a live pool.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_pool_call(
+    pool: *const WasmPool,
+    name: *const c_char,
+    args: *const WasmValue,
+    args_len: usize,
+    results: *mut WasmValue,
+    results_len: usize,
+) -> WasmStatus {
+    if pool.is_null()
+        || name.is_null()
+        || (args.is_null() && args_len > 0)
+        || (results.is_null() && results_len > 0)
+    {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(name) => name,
+        Err(_) => return fail(WasmStatus::InvalidArgument, "export name is not UTF-8"),
+    };
+    let args = if args_len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(args, args_len)
+    };
+
+    let pool = &(*pool).0;
+    guard(|| match pool.get() {
+        Ok(instance) => call(&instance, name, args, results, results_len),
+        Err(err) => fail_with_pool(err),
+    })
+}
+
+/// Runs the export `name` over `data` split across instances of `pool` on
+/// worker threads, replacing `data` with the output, as described in the
+/// `parallel` module of the Rust API. `chunk_len`, `align` and `workers` of 0
+/// pick the defaults. The output must be as long as the input.
+///
+/// # Safety
+///
+/// `pool` must be a live pool, `name` a NUL-terminated string, and `data`
+/// must point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_pool_map(
+    pool: *const WasmPool,
+    name: *const c_char,
+    data: *mut u8,
+    len: usize,
+    chunk_len: usize,
+    align: usize,
+    workers: usize,
+) -> WasmStatus {
+    if pool.is_null() || name.is_null() || (data.is_null() && len > 0) {
+        return fail(WasmStatus::InvalidArgument, "null pointer");
+    }
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(name) => name,
+        Err(_) => return fail(WasmStatus::InvalidArgument, "export name is not UTF-8"),
+    };
+    let data = if len == 0 {
+        &mut []
+    } else {
+        slice::from_raw_parts_mut(data, len)
+    };
+
+    let mut map = (*pool).0.map(name);
+    if chunk_len > 0 {
+        map = map.chunk_len(chunk_len);
+    }
+    if align > 0 {
+        map = map.align(align);
+    }
+    if workers > 0 {
+        map = map.workers(workers);
+    }
+    guard(|| match map.run(data) {
+        Ok(output) if output.len() == data.len() => {
+            data.copy_from_slice(&output);
+            WasmStatus::Ok
+        }
+        Ok(output) => fail(
+            WasmStatus::SignatureMismatch,
+            format!(
+                "`{}` produced {} bytes from {}",
+                name,
+                output.len(),
+                data.len()
+            ),
+        ),
+        Err(err) => fail_with_pool(err),
+    })
+}
+
+fn fail_with_pool(err: PoolError) -> WasmStatus {
+    match err {
+        PoolError::Exhausted => fail(WasmStatus::PoolExhausted, err),
+        PoolError::Instantiate(err) | PoolError::Call(err) => fail_with(err),
+    }
+}
+
+unsafe fn call(
+    instance: &Instance,
+    name: &str,
+    args: &[WasmValue],
+    results: *mut WasmValue,
+    results_len: usize,
+) -> WasmStatus {
+    let results_expected = match instance.export(name) {
+        Some(ExternType::Function { results, .. }) => results.len(),
+        _ => return fail_with(Error::ExportNotFound(name.to_string())),
+    };
+    if results_len != results_expected {
+        return fail(
+            WasmStatus::SignatureMismatch,
+            format!("`{}` returns {} value(s)", name, results_expected),
+        );
+    }
+    let params = match args.iter().map(value_from_ffi).collect::<Option<Vec<_>>>() {
+        Some(params) => params,
+        None => return fail(WasmStatus::InvalidArgument, "unknown value kind"),
+    };
+
+    match instance.call(name, &params) {
+        Ok(values) => {
+            for (i, value) in values.iter().enumerate() {
+                match value_to_ffi(value) {
+                    Some(value) => *results.add(i) = value,
+                    None => return fail(WasmStatus::SignatureMismatch, "unsupported result type"),
+                }
+            }
+            WasmStatus::Ok
+        }
+        Err(err) => fail_with(err),
+    }
+}
+
+fn value_from_ffi(value: &WasmValue) -> Option<Value> {
+    unsafe {
+        match value.kind {
+            WASM_I32 => Some(Value::I32(value.of.i32)),
+            WASM_I64 => Some(Value::I64(value.of.i64)),
+            WASM_F32 => Some(Value::F32(value.of.f32)),
+            WASM_F64 => Some(Value::F64(value.of.f64)),
+            _ => None,
+        }
+    }
+}
+
+fn value_to_ffi(value: &Value) -> Option<WasmValue> {
+    let (kind, of) = match *value {
+        Value::I32(x) => (WASM_I32, WasmValueOf { i32: x }),
+        Value::I64(x) => (WASM_I64, WasmValueOf { i64: x }),
+        Value::F32(x) => (WASM_F32, WasmValueOf { f32: x }),
+        Value::F64(x) => (WASM_F64, WasmValueOf { f64: x }),
+        Value::V128(_) => return None,
+    };
+    Some(WasmValue { kind, of })
+}
@@ -0,0 +1,400 @@
+use serde::Serialize;
+use std::fmt;
+use wasmparser::{
+    ExternalKind, FuncType, GlobalType, KnownCustom, MemoryType, Parser, Payload, TableType,
+    TypeRef,
+};
+
+/// A description of a module's interface and layout, as printed by
+/// `wasmer-run inspect`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModuleReport {
+    /// Size of the whole binary in bytes.
+    pub size: usize,
+    pub imports: Vec<ImportInfo>,
+    pub exports: Vec<ExportInfo>,
+    pub memories: Vec<LimitsInfo>,
+    pub tables: Vec<LimitsInfo>,
+    pub custom_sections: Vec<SectionInfo>,
+    /// Fields of the `producers` custom section, if present.
+    pub producers: Vec<ProducerField>,
+    /// The module header and every section in the order they appear. Section
+    /// sizes include their id and length, so together they add up to `size`.
+    pub sections: Vec<SectionInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportInfo {
+    pub module: String,
+    pub name: String,
+    pub kind: &'static str,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportInfo {
+    pub name: String,
+    pub kind: &'static str,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// Limits of a memory (in pages) or table (in elements).
+#[derive(Debug, Clone, Serialize)]
+pub struct LimitsInfo {
+    pub index: u32,
+    pub imported: bool,
+    pub minimum: u64,
+    pub maximum: Option<u64>,
+    pub shared: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SectionInfo {
+    pub name: String,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProducerField {
+    pub name: String,
+    pub values: Vec<ProducerValue>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProducerValue {
+    pub name: String,
+    pub version: String,
+}
+
+impl ModuleReport {
+    /// Renders the report as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report is always serializable")
+    }
+}
+
+/// Describes the module `wasm`, which must be a binary module.
+pub fn inspect(wasm: &[u8]) -> Result<ModuleReport, wasmparser::BinaryReaderError> {
+    let mut report = ModuleReport {
+        size: wasm.len(),
+        imports: Vec::new(),
+        exports: Vec::new(),
+        memories: Vec::new(),
+        tables: Vec::new(),
+        custom_sections: Vec::new(),
+        producers: Vec::new(),
+        sections: Vec::new(),
+    };
+    // Function types by type index, the type index of every function, and
+    // every global's type. Function and global index spaces start with imports.
+    let mut types: Vec<FuncType> = Vec::new();
+    let mut functions: Vec<u32> = Vec::new();
+    let mut globals: Vec<GlobalType> = Vec::new();
+    // Where the last section ended. The parser gives each section's payload
+    // range, and its id and length sit between that and the previous end.
+    let mut end = 0;
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload?;
+        if let Some((id, range)) = payload.as_section() {
+            let name = match &payload {
+                Payload::CustomSection(section) => format!("custom \"{}\"", section.name()),
+                _ => section_name(id).to_string(),
+            };
+            report.sections.push(SectionInfo {
+                name,
+                size: range.end - end,
+            });
+            end = range.end;
+        }
+
+        match payload {
+            Payload::Version { range, .. } => {
+                report.sections.push(SectionInfo {
+                    name: "header".to_string(),
+                    size: range.len(),
+                });
+                end = range.end;
+            }
+            Payload::TypeSection(reader) => {
+                for ty in reader.into_iter_err_on_gc_types() {
+                    types.push(ty?);
+                }
+            }
+            Payload::ImportSection(reader) => {
+                for import in reader.into_imports() {
+                    let import = import?;
+                    let (kind, ty) = match import.ty {
+                        TypeRef::Func(index) | TypeRef::FuncExact(index) => {
+                            functions.push(index);
+                            ("func", func_signature(&types, index))
+                        }
+                        TypeRef::Table(table) => {
+                            report
+                                .tables
+                                .push(table_limits(&report.tables, table, true));
+                            ("table", limits(table.initial, table.maximum))
+                        }
+                        TypeRef::Memory(memory) => {
+                            report
+                                .memories
+                                .push(memory_limits(&report.memories, memory, true));
+                            ("memory", limits(memory.initial, memory.maximum))
+                        }
+                        TypeRef::Global(global) => {
+                            globals.push(global);
+                            ("global", global_type(&global))
+                        }
+                        TypeRef::Tag(_) => ("tag", String::new()),
+                    };
+                    report.imports.push(ImportInfo {
+                        module: import.module.to_string(),
+                        name: import.name.to_string(),
+                        kind,
+                        ty,
+                    });
+                }
+            }
+            Payload::FunctionSection(reader) => {
+                for index in reader {
+                    functions.push(index?);
+                }
+            }
+            Payload::TableSection(reader) => {
+                for table in reader {
+                    let table = table?.ty;
+                    report
+                        .tables
+                        .push(table_limits(&report.tables, table, false));
+                }
+            }
+            Payload::MemorySection(reader) => {
+                for memory in reader {
+                    let memory = memory?;
+                    report
+                        .memories
+                        .push(memory_limits(&report.memories, memory, false));
+                }
+            }
+            Payload::GlobalSection(reader) => {
+                for global in reader {
+                    globals.push(global?.ty);
+                }
+            }
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    let export = export?;
+                    let index = export.index as usize;
+                    let (kind, ty) = match export.kind {
+                        ExternalKind::Func | ExternalKind::FuncExact => (
+                            "func",
+                            functions
+                                .get(index)
+                                .map(|ty| func_signature(&types, *ty))
+                                .unwrap_or_default(),
+                        ),
+                        ExternalKind::Table => (
+                            "table",
+                            report
+                                .tables
+                                .get(index)
+                                .map(|table| limits(table.minimum, table.maximum))
+                                .unwrap_or_default(),
+                        ),
+                        ExternalKind::Memory => (
+                            "memory",
+                            report
+                                .memories
+                                .get(index)
+                                .map(|memory| limits(memory.minimum, memory.maximum))
+                                .unwrap_or_default(),
+                        ),
+                        ExternalKind::Global => (
+                            "global",
+                            globals.get(index).map(global_type).unwrap_or_default(),
+                        ),
+                        ExternalKind::Tag => ("tag", String::new()),
+                    };
+                    report.exports.push(ExportInfo {
+                        name: export.name.to_string(),
+                        kind,
+                        ty,
+                    });
+                }
+            }
+            Payload::CustomSection(section) => {
+                report.custom_sections.push(SectionInfo {
+                    name: section.name().to_string(),
+                    size: section.data().len(),
+                });
+                if let KnownCustom::Producers(reader) = section.as_known() {
+                    for field in reader {
+                        let field = field?;
+                        let values = field
+                            .values
+                            .into_iter()
+                            .map(|value| {
+                                value.map(|value| ProducerValue {
+                                    name: value.name.to_string(),
+                                    version: value.version.to_string(),
+                                })
+                            })
+                            .collect::<Result<_, _>>()?;
+                        report.producers.push(ProducerField {
+                            name: field.name.to_string(),
+                            values,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(report)
+}
+
+impl fmt::Display for ModuleReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "size: {} bytes", self.size)?;
+
+        writeln!(f, "\nimports ({}):", self.imports.len())?;
+        for import in &self.imports {
+            writeln!(
+                f,
+                "  {} {}.{} {}",
+                import.kind, import.module, import.name, import.ty
+            )?;
+        }
+
+        writeln!(f, "\nexports ({}):", self.exports.len())?;
+        for export in &self.exports {
+            writeln!(f, "  {} {} {}", export.kind, export.name, export.ty)?;
+        }
+
+        writeln!(f, "\nmemories ({}):", self.memories.len())?;
+        for memory in &self.memories {
+            write_limits(f, memory, "pages")?;
+        }
+
+        writeln!(f, "\ntables ({}):", self.tables.len())?;
+        for table in &self.tables {
+            write_limits(f, table, "elements")?;
+        }
+
+        writeln!(f, "\ncustom sections ({}):", self.custom_sections.len())?;
+        for section in &self.custom_sections {
+            writeln!(f, "  {} ({} bytes)", section.name, section.size)?;
+        }
+
+        if !self.producers.is_empty() {
+            writeln!(f, "\nproducers:")?;
+            for field in &self.producers {
+                let values: Vec<String> = field
+                    .values
+                    .iter()
+                    .map(|value| {
+                        format!("{} {}", value.name, value.version)
+                            .trim()
+                            .to_string()
+                    })
+                    .collect();
+                writeln!(f, "  {}: {}", field.name, values.join(", "))?;
+            }
+        }
+
+        writeln!(f, "\nsections:")?;
+        for section in &self.sections {
+            let percent = section.size as f64 * 100.0 / self.size.max(1) as f64;
+            writeln!(
+                f,
+                "  {:<24} {:>10} bytes {:>5.1}%",
+                section.name, section.size, percent
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn write_limits(f: &mut fmt::Formatter, info: &LimitsInfo, unit: &str) -> fmt::Result {
+    writeln!(
+        f,
+        "  {}: {} {}{}{}",
+        info.index,
+        limits(info.minimum, info.maximum),
+        unit,
+        if info.shared { ", shared" } else { "" },
+        if info.imported { ", imported" } else { "" }
+    )
+}
+
+fn memory_limits(existing: &[LimitsInfo], memory: MemoryType, imported: bool) -> LimitsInfo {
+    LimitsInfo {
+        index: existing.len() as u32,
+        imported,
+        minimum: memory.initial,
+        maximum: memory.maximum,
+        shared: memory.shared,
+    }
+}
+
+fn table_limits(existing: &[LimitsInfo], table: TableType, imported: bool) -> LimitsInfo {
+    LimitsInfo {
+        index: existing.len() as u32,
+        imported,
+        minimum: table.initial,
+        maximum: table.maximum,
+        shared: table.shared,
+    }
+}
+
+fn limits(minimum: u64, maximum: Option<u64>) -> String {
+    match maximum {
+        Some(maximum) => format!("{}..{}", minimum, maximum),
+        None => format!("{}..", minimum),
+    }
+}
+
+fn func_signature(types: &[FuncType], index: u32) -> String {
+    match types.get(index as usize) {
+        Some(ty) => {
+            let list = |types: &[wasmparser::ValType]| {
+                types
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            format!("({}) -> ({})", list(ty.params()), list(ty.results()))
+        }
+        None => format!("type {}", index),
+    }
+}
+
+fn global_type(global: &GlobalType) -> String {
+    if global.mutable {
+        format!("mut {}", global.content_type)
+    } else {
+        global.content_type.to_string()
+    }
+}
+
+fn section_name(id: u8) -> &'static str {
+    match id {
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "datacount",
+        13 => "tag",
+        _ => "unknown",
+    }
+}
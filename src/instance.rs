@@ -0,0 +1,188 @@
+use crate::error::{Error, Result};
+use crate::module::{self, type_list, ExportType, ExternType};
+use crate::value::{Value, ValueType};
+use wasmer_runtime::error::ResolveError;
+use wasmer_runtime::units::Pages;
+use wasmer_runtime::{Ctx, Export, Global, Memory};
+
+/// An instance of a [`Module`](crate::Module), with its own memory, globals
+/// and tables.
+///
+/// Memory accessors operate on the instance's default memory, the one at
+/// index 0, which is the only memory an MVP module can have.
+pub struct Instance {
+    inner: wasmer_runtime::Instance,
+}
+
+impl Instance {
+    pub(crate) fn from_wasmer(inner: wasmer_runtime::Instance) -> Instance {
+        Instance { inner }
+    }
+
+    /// Lists the instance's exports in the order they are declared.
+    pub fn exports(&self) -> Vec<ExportType> {
+        module::export_types(&self.inner.module.info)
+    }
+
+    /// Returns the type of the export `name`, if there is one.
+    pub fn export(&self, name: &str) -> Option<ExternType> {
+        let info = &self.inner.module.info;
+        info.exports
+            .get(name)
+            .map(|index| module::export_type(info, index))
+    }
+
+    /// Calls the exported function `name`.
+    ///
+    /// The arguments must match the function's parameters exactly; integers
+    /// are not converted between widths and floats are not converted to
+    /// integers.
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Vec<Value>> {
+        let func = self.inner.dyn_func(name).map_err(|err| match err {
+            ResolveError::ExportNotFound { .. } | ResolveError::ExportWrongType { .. } => {
+                Error::ExportNotFound(name.to_string())
+            }
+            err => Error::SignatureMismatch(err.to_string()),
+        })?;
+        let params: Vec<ValueType> = func
+            .signature()
+            .params()
+            .iter()
+            .map(|ty| ValueType::from_wasmer(*ty))
+            .collect();
+        let types: Vec<ValueType> = args.iter().map(Value::ty).collect();
+        if types != params {
+            return Err(Error::SignatureMismatch(format!(
+                "`{}` takes ({}), got ({})",
+                name,
+                type_list(&params),
+                type_list(&types)
+            )));
+        }
+
+        let args: Vec<wasmer_runtime::Value> = args.iter().map(|arg| arg.to_wasmer()).collect();
+        let results = func.call(&args)?;
+        Ok(results.iter().map(Value::from_wasmer).collect())
+    }
+
+    /// Returns the size of the default memory in 64 KiB pages.
+    pub fn memory_pages(&self) -> Result<u32> {
+        Ok(self.memory()?.size().0)
+    }
+
+    /// Returns the size of the default memory in bytes.
+    pub fn memory_len(&self) -> Result<usize> {
+        Ok(self.memory()?.size().bytes().0)
+    }
+
+    /// Copies `buf.len()` bytes starting at `offset` out of the default memory.
+    pub fn read_memory(&self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        let view = self.memory()?.view::<u8>();
+        let cells = view
+            .get(offset..)
+            .and_then(|cells| cells.get(..buf.len()))
+            .ok_or_else(|| out_of_bounds(offset, buf.len(), view.len()))?;
+        for (byte, cell) in buf.iter_mut().zip(cells) {
+            *byte = cell.get();
+        }
+        Ok(())
+    }
+
+    /// Copies `data` into the default memory starting at `offset`.
+    pub fn write_memory(&self, offset: usize, data: &[u8]) -> Result<()> {
+        let view = self.memory()?.view::<u8>();
+        let cells = view
+            .get(offset..)
+            .and_then(|cells| cells.get(..data.len()))
+            .ok_or_else(|| out_of_bounds(offset, data.len(), view.len()))?;
+        for (cell, byte) in cells.iter().zip(data) {
+            cell.set(*byte);
+        }
+        Ok(())
+    }
+
+    /// Grows the default memory by `pages`, returning its previous size in
+    /// pages.
+    pub fn grow_memory(&self, pages: u32) -> Result<u32> {
+        self.memory()?
+            .grow(Pages(pages))
+            .map(|previous| previous.0)
+            .map_err(|err| Error::Memory(format!("could not grow memory: {:?}", err)))
+    }
+
+    /// Returns the value of the exported global `name`.
+    pub fn global(&self, name: &str) -> Result<Value> {
+        Ok(Value::from_wasmer(&self.exported_global(name)?.get()))
+    }
+
+    /// Sets the exported global `name`, which must be mutable and of the
+    /// same type as `value`.
+    pub fn set_global(&self, name: &str, value: Value) -> Result<()> {
+        let global = self.exported_global(name)?;
+        let descriptor = global.descriptor();
+        if !descriptor.mutable {
+            return Err(Error::SignatureMismatch(format!(
+                "global `{}` is immutable",
+                name
+            )));
+        }
+        let ty = ValueType::from_wasmer(descriptor.ty);
+        if value.ty() != ty {
+            return Err(Error::SignatureMismatch(format!(
+                "global `{}` is {}, got {}",
+                name,
+                ty,
+                value.ty()
+            )));
+        }
+        global.set(value.to_wasmer());
+        Ok(())
+    }
+
+    pub(crate) fn as_wasmer(&self) -> &wasmer_runtime::Instance {
+        &self.inner
+    }
+
+    pub(crate) fn as_wasmer_mut(&mut self) -> &mut wasmer_runtime::Instance {
+        &mut self.inner
+    }
+
+    fn memory(&self) -> Result<&Memory> {
+        let info = &self.inner.module.info;
+        if info.memories.len() + info.imported_memories.len() == 0 {
+            return Err(Error::Memory("instance has no memory".to_string()));
+        }
+        Ok(self.inner.context().memory(0))
+    }
+
+    fn exported_global(&self, name: &str) -> Result<Global> {
+        self.inner
+            .exports()
+            .find_map(|(export, item)| match item {
+                Export::Global(global) if export == name => Some(global),
+                _ => None,
+            })
+            .ok_or_else(|| Error::ExportNotFound(name.to_string()))
+    }
+}
+
+/// Returns the default memory of the instance a host function was called
+/// from, or `None` if it has none. `Ctx::memory` would panic then.
+pub(crate) fn ctx_memory(ctx: &Ctx) -> Option<&Memory> {
+    // Safety: `ctx.module` points at the module of the instance `ctx`
+    // belongs to, which outlives the call.
+    let info = unsafe { &(*ctx.module).info };
+    if info.memories.len() + info.imported_memories.len() == 0 {
+        return None;
+    }
+    Some(ctx.memory(0))
+}
+
+/// The error for an access of `len` bytes at `offset` into a memory of
+/// `size` bytes.
+pub fn out_of_bounds(offset: usize, len: usize, size: usize) -> Error {
+    Error::Memory(format!(
+        "{} bytes at {} is out of bounds ({} bytes)",
+        len, offset, size
+    ))
+}
@@ -1,27 +1,44 @@
-use wasmer_runtime::{error, imports, instantiate, Func};
+//! Runs WebAssembly modules with Wasmer, for Flutter apps through the C ABI
+//! in [`ffi`] and for Rust code through the safe API re-exported here.
+//!
+//! ```
+//! use adder::{Engine, Value};
+//!
+//! let module = Engine::new().compile(include_bytes!("add.wasm"))?;
+//! let instance = module.instantiate()?;
+//! assert_eq!(instance.call("add_one", &[Value::I32(42)])?, vec![Value::I32(43)]);
+//! # Ok::<(), adder::Error>(())
+//! ```
 
-#[no_mangle]
-pub extern "C" fn load_wasm() -> error::Result<()> {
-    // Let's get the .wasm file as bytes
-    let wasm_bytes = include_bytes!("add.wasm");
+pub mod cli;
+pub mod dartgen;
+pub mod deterministic;
+pub mod differential;
+mod engine;
+mod error;
+pub mod ffi;
+pub mod inspect;
+mod instance;
+mod linker;
+mod module;
+pub mod parallel;
+pub mod pool;
+mod probestack;
+pub mod profile;
+pub mod registry;
+pub mod reload;
+pub mod replay;
+pub mod snapshot;
+pub mod source;
+pub mod state_file;
+pub mod streaming;
+mod value;
+pub mod wizer;
 
-    // Our import object, that allows exposing functions to our Wasm module.
-    // We're not importing anything, so make an empty import object.
-    let import_object = imports! {};
-
-    // Let's create an instance of Wasm module running in the wasmer-runtime
-    let instance = instantiate(wasm_bytes, &import_object)?;
-
-    // Let's get `add_one` as a function which takes one `u32` and returns one `u32`
-    let add_one: Func<u32, u32> = instance.func("add_one")?;
-    let result = add_one.call(42)?;
-
-    // Log the new value
-    println!("Result: {}", result);
-
-    // Asserting that the returned value from the function is our expected value.
-    assert_eq!(result, 43);
-
-    // Return OK since everything executed successfully!
-    Ok(())
-}
+pub use engine::{Backend, Engine};
+pub use error::{Error, Result};
+pub use instance::Instance;
+pub use linker::Linker;
+pub use module::{ExportType, ExternType, ImportType, Module};
+pub use value::{Value, ValueType};
+pub use wasmer_runtime::{func, imports, ImportObject};
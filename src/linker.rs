@@ -0,0 +1,209 @@
+use crate::error::{Error, Result};
+use crate::instance::Instance;
+use crate::module::Module;
+use crate::registry::Registry;
+use wasmer_runtime::ImportObject;
+
+/// Instantiates modules whose imports are other instances' exports.
+///
+/// Every instance linked under a name provides its exported functions,
+/// memories, globals and tables as imports from the namespace of that name.
+/// Later modules can import them alongside any host imports, and can in turn
+/// be linked for modules after them:
+///
+/// ```
+/// use adder::{Engine, Linker, Value};
+///
+/// let engine = Engine::new();
+/// let runtime = engine.compile(br#"(module
+///     (func (export "double") (param i32) (result i32)
+///         (i32.mul (local.get 0) (i32.const 2))))"#)?;
+/// let feature = engine.compile(br#"(module
+///     (import "runtime" "double" (func $double (param i32) (result i32)))
+///     (func (export "quadruple") (param i32) (result i32)
+///         (call $double (call $double (local.get 0)))))"#)?;
+///
+/// let mut linker = Linker::new();
+/// linker.instantiate("runtime", &runtime)?;
+/// let feature = linker.instantiate("feature", &feature)?;
+/// assert_eq!(feature.call("quadruple", &[Value::I32(5)])?, [Value::I32(20)]);
+/// # Ok::<(), adder::Error>(())
+/// ```
+///
+/// Imported functions run in the instance that exports them, so the linker
+/// owns every instance it links and they live as long as it does.
+pub struct Linker {
+    imports: ImportObject,
+    instances: Vec<(String, Instance)>,
+    registry: Option<Registry>,
+}
+
+impl Default for Linker {
+    fn default() -> Linker {
+        Linker::new()
+    }
+}
+
+impl Linker {
+    pub fn new() -> Linker {
+        Linker {
+            imports: ImportObject::new(),
+            instances: Vec::new(),
+            registry: None,
+        }
+    }
+
+    /// Resolves imports from a namespace that nothing is linked under by
+    /// instantiating the module registered under that name, at its highest
+    /// version, and linking it there first. Its own imports are resolved the
+    /// same way.
+    pub fn registry(&mut self, registry: Registry) -> &mut Linker {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Adds host imports for the modules instantiated from now on. Imports
+    /// with the same namespace and name as earlier host imports replace
+    /// them.
+    ///
+    /// Fails if `imports` defines a name in the namespace of a linked
+    /// instance.
+    pub fn imports(&mut self, imports: ImportObject) -> Result<&mut Linker> {
+        let imports: Vec<_> = imports.into_iter().collect();
+        if let Some((namespace, name, _)) = imports
+            .iter()
+            .find(|(namespace, _, _)| self.get(namespace).is_some())
+        {
+            return Err(Error::Instantiate(format!(
+                "`{}.{}` is in the namespace of a linked instance",
+                namespace, name
+            )));
+        }
+        self.imports.extend(imports);
+        Ok(self)
+    }
+
+    /// Instantiates `module` with the linked instances and host imports, and
+    /// links the new instance under `name`.
+    ///
+    /// If instantiating it or a registered module it depends on fails, the
+    /// registered modules already linked for it are unlinked again, leaving
+    /// the linker as it was.
+    pub fn instantiate(&mut self, name: &str, module: &Module) -> Result<&Instance> {
+        self.check_name(name)?;
+        let linked = self.instances.len();
+        let instance = self
+            .resolve(module, &mut vec![name.to_string()])
+            .and_then(|()| module.instantiate_with(&self.imports));
+        match instance {
+            Ok(instance) => Ok(self.link(name, instance)),
+            Err(err) => {
+                self.unlink(linked);
+                Err(err)
+            }
+        }
+    }
+
+    /// Links an instance created elsewhere under `name`, so that modules
+    /// instantiated from now on can import its exports.
+    pub fn instance(&mut self, name: &str, instance: Instance) -> Result<&Instance> {
+        self.check_name(name)?;
+        Ok(self.link(name, instance))
+    }
+
+    /// Returns the instance linked under `name`.
+    pub fn get(&self, name: &str) -> Option<&Instance> {
+        self.instances
+            .iter()
+            .find(|(linked, _)| linked == name)
+            .map(|(_, instance)| instance)
+    }
+
+    /// Lists the names of the linked instances in the order they were
+    /// linked.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.instances.iter().map(|(name, _)| name.as_str())
+    }
+
+    fn check_name(&self, name: &str) -> Result<()> {
+        if self.get(name).is_some() {
+            return Err(Error::Instantiate(format!(
+                "an instance is already linked as `{}`",
+                name
+            )));
+        }
+        if self.imports.with_namespace(name, |_| ()).is_some() {
+            return Err(Error::Instantiate(format!(
+                "`{}` is already a namespace of host imports",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Links the registered modules that `module` imports from, given the
+    /// names of the modules waiting on them.
+    fn resolve(&mut self, module: &Module, resolving: &mut Vec<String>) -> Result<()> {
+        let registry = match &self.registry {
+            Some(registry) => registry.clone(),
+            None => return Ok(()),
+        };
+        for import in module.imports() {
+            let namespace = import.module;
+            if self.check_name(&namespace).is_err() {
+                continue;
+            }
+            let dependency = match registry.module(&namespace, None) {
+                Some(dependency) => dependency,
+                None => continue,
+            };
+            if resolving.contains(&namespace) {
+                return Err(Error::Instantiate(format!(
+                    "registered modules import each other: {} -> {}",
+                    resolving.join(" -> "),
+                    namespace
+                )));
+            }
+            resolving.push(namespace.clone());
+            self.resolve(&dependency, resolving)?;
+            let instance = dependency.instantiate_with(&self.imports).map_err(|err| {
+                Error::Instantiate(format!("registered module `{}`: {}", namespace, err))
+            })?;
+            self.link(&namespace, instance);
+            resolving.pop();
+        }
+        Ok(())
+    }
+
+    fn link(&mut self, name: &str, instance: Instance) -> &Instance {
+        let exports = instance
+            .as_wasmer()
+            .exports()
+            .map(|(export, item)| (name.to_string(), export, item));
+        self.imports.extend(exports);
+        self.instances.push((name.to_string(), instance));
+        &self.instances.last().unwrap().1
+    }
+
+    /// Unlinks every instance after the first `linked`, and their exports.
+    fn unlink(&mut self, linked: usize) {
+        if self.instances.len() == linked {
+            return;
+        }
+        let unlinked: Vec<_> = self.instances[linked..]
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect();
+        // Import objects can't remove a namespace, so the rest are copied
+        // into a new one before the instances they point into are dropped.
+        let kept: Vec<_> = self
+            .imports
+            .clone_ref()
+            .into_iter()
+            .filter(|(namespace, _, _)| !unlinked.contains(namespace))
+            .collect();
+        self.imports = ImportObject::new();
+        self.imports.extend(kept);
+        self.instances.truncate(linked);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::error::{Error, Result};
+use crate::instance::Instance;
+use crate::value::ValueType;
+use std::fmt;
+use wasmer_runtime::types::{GlobalDescriptor, MemoryDescriptor, TableDescriptor};
+use wasmer_runtime::ImportObject;
+use wasmer_runtime_core::module::{ExportIndex, ImportName, ModuleInfo};
+use wasmer_runtime_core::structures::TypedIndex;
+use wasmer_runtime_core::types::{FuncIndex, LocalOrImport};
+
+/// The type of an imported or exported item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExternType {
+    Function {
+        params: Vec<ValueType>,
+        results: Vec<ValueType>,
+    },
+    /// A memory with limits in 64 KiB pages.
+    Memory {
+        minimum: u32,
+        maximum: Option<u32>,
+    },
+    /// A table with limits in elements.
+    Table {
+        minimum: u32,
+        maximum: Option<u32>,
+    },
+    Global {
+        ty: ValueType,
+        mutable: bool,
+    },
+}
+
+/// An item a module imports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportType {
+    pub module: String,
+    pub name: String,
+    pub ty: ExternType,
+}
+
+/// An item a module exports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportType {
+    pub name: String,
+    pub ty: ExternType,
+}
+
+/// A compiled module, created by [`Engine::compile`](crate::Engine::compile).
+///
+/// Modules are cheap to clone and can be instantiated any number of times.
+#[derive(Clone)]
+pub struct Module {
+    inner: wasmer_runtime::Module,
+}
+
+impl Module {
+    pub(crate) fn from_wasmer(inner: wasmer_runtime::Module) -> Module {
+        Module { inner }
+    }
+
+    /// Instantiates the module with no imports.
+    pub fn instantiate(&self) -> Result<Instance> {
+        self.instantiate_with(&wasmer_runtime::imports! {})
+    }
+
+    /// Instantiates the module, resolving its imports from `imports`.
+    pub fn instantiate_with(&self, imports: &ImportObject) -> Result<Instance> {
+        self.inner
+            .instantiate(imports)
+            .map(Instance::from_wasmer)
+            .map_err(|err| Error::Instantiate(err.to_string()))
+    }
+
+    /// Lists the module's imports in the order they are declared.
+    pub fn imports(&self) -> Vec<ImportType> {
+        let info = self.inner.info();
+        let mut imports = Vec::new();
+        for (index, name) in info.imported_functions.iter() {
+            let ty = function_type(info, FuncIndex::new(index.index()));
+            imports.push(import_type(info, name, ty));
+        }
+        for (_, (name, memory)) in info.imported_memories.iter() {
+            imports.push(import_type(info, name, memory_type(memory)));
+        }
+        for (_, (name, table)) in info.imported_tables.iter() {
+            imports.push(import_type(info, name, table_type(table)));
+        }
+        for (_, (name, global)) in info.imported_globals.iter() {
+            imports.push(import_type(info, name, global_type(global)));
+        }
+        imports
+    }
+
+    /// Lists the module's exports in the order they are declared.
+    pub fn exports(&self) -> Vec<ExportType> {
+        export_types(self.inner.info())
+    }
+
+    /// Returns the type of the export `name`, if there is one.
+    pub fn export(&self, name: &str) -> Option<ExternType> {
+        let info = self.inner.info();
+        info.exports.get(name).map(|index| export_type(info, index))
+    }
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Module")
+            .field("imports", &self.imports())
+            .field("exports", &self.exports())
+            .finish()
+    }
+}
+
+impl fmt::Display for ExternType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let limits = |f: &mut fmt::Formatter, minimum: u32, maximum: Option<u32>| match maximum {
+            Some(maximum) => write!(f, "{}..{}", minimum, maximum),
+            None => write!(f, "{}..", minimum),
+        };
+        match self {
+            ExternType::Function { params, results } => {
+                write!(
+                    f,
+                    "func ({}) -> ({})",
+                    type_list(params),
+                    type_list(results)
+                )
+            }
+            ExternType::Memory { minimum, maximum } => {
+                f.write_str("memory ")?;
+                limits(f, *minimum, *maximum)
+            }
+            ExternType::Table { minimum, maximum } => {
+                f.write_str("table ")?;
+                limits(f, *minimum, *maximum)
+            }
+            ExternType::Global { ty, mutable: true } => write!(f, "global mut {}", ty),
+            ExternType::Global { ty, mutable: false } => write!(f, "global {}", ty),
+        }
+    }
+}
+
+/// Formats `types` as a comma-separated list, e.g. `i32, f64`.
+pub fn type_list(types: &[ValueType]) -> String {
+    types
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn import_type(info: &ModuleInfo, name: &ImportName, ty: ExternType) -> ImportType {
+    ImportType {
+        module: info.namespace_table.get(name.namespace_index).to_string(),
+        name: info.name_table.get(name.name_index).to_string(),
+        ty,
+    }
+}
+
+pub(crate) fn export_types(info: &ModuleInfo) -> Vec<ExportType> {
+    info.exports
+        .iter()
+        .map(|(name, index)| ExportType {
+            name: name.clone(),
+            ty: export_type(info, index),
+        })
+        .collect()
+}
+
+pub(crate) fn export_type(info: &ModuleInfo, index: &ExportIndex) -> ExternType {
+    match *index {
+        ExportIndex::Func(func) => function_type(info, func),
+        ExportIndex::Memory(memory) => match memory.local_or_import(info) {
+            LocalOrImport::Local(local) => memory_type(&info.memories[local]),
+            LocalOrImport::Import(import) => memory_type(&info.imported_memories[import].1),
+        },
+        ExportIndex::Table(table) => match table.local_or_import(info) {
+            LocalOrImport::Local(local) => table_type(&info.tables[local]),
+            LocalOrImport::Import(import) => table_type(&info.imported_tables[import].1),
+        },
+        ExportIndex::Global(global) => match global.local_or_import(info) {
+            LocalOrImport::Local(local) => global_type(&info.globals[local].desc),
+            LocalOrImport::Import(import) => global_type(&info.imported_globals[import].1),
+        },
+    }
+}
+
+fn function_type(info: &ModuleInfo, func: FuncIndex) -> ExternType {
+    let signature = &info.signatures[info.func_assoc[func]];
+    ExternType::Function {
+        params: signature
+            .params()
+            .iter()
+            .map(|ty| ValueType::from_wasmer(*ty))
+            .collect(),
+        results: signature
+            .returns()
+            .iter()
+            .map(|ty| ValueType::from_wasmer(*ty))
+            .collect(),
+    }
+}
+
+fn memory_type(memory: &MemoryDescriptor) -> ExternType {
+    ExternType::Memory {
+        minimum: memory.minimum.0,
+        maximum: memory.maximum.map(|pages| pages.0),
+    }
+}
+
+fn table_type(table: &TableDescriptor) -> ExternType {
+    ExternType::Table {
+        minimum: table.minimum,
+        maximum: table.maximum,
+    }
+}
+
+fn global_type(global: &GlobalDescriptor) -> ExternType {
+    ExternType::Global {
+        ty: ValueType::from_wasmer(global.ty),
+        mutable: global.mutable,
+    }
+}
@@ -0,0 +1,238 @@
+//! Runs an export over a large input split across the instances of a
+//! [`Pool`], one worker thread per instance.
+//!
+//! The input is cut into chunks, each chunk is copied into an instance's
+//! memory and passed to the export, and the chunks it produces are joined
+//! back together in input order. The module must export:
+//!
+//! - `alloc(len: i32) -> i32`, returning the address of a buffer of `len`
+//!   bytes. Each worker calls it once, for the largest chunk, and reuses the
+//!   buffer for every chunk it processes.
+//! - the export itself, taking `(ptr: i32, len: i32)`. If it returns
+//!   nothing, its output is the chunk as it left it in the buffer. If it
+//!   returns an `i32`, that is the length of its output, starting at `ptr`.
+//!
+//! If the module also exports `dealloc(ptr: i32, len: i32)`, each worker
+//! frees its buffer with it when it is done.
+//!
+//! ```
+//! use adder::pool::Pool;
+//! use adder::Engine;
+//!
+//! let module = Engine::new().compile(br#"(module
+//!     (memory (export "memory") 1)
+//!     (func (export "alloc") (param i32) (result i32) i32.const 1024)
+//!     (func (export "invert") (param $ptr i32) (param $len i32)
+//!         (loop $next
+//!             (if (local.get $len) (then
+//!                 (i32.store8 (local.get $ptr)
+//!                     (i32.xor (i32.load8_u (local.get $ptr)) (i32.const 255)))
+//!                 (local.set $ptr (i32.add (local.get $ptr) (i32.const 1)))
+//!                 (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+//!                 (br $next))))))"#)?;
+//! let pool = Pool::builder(module).max(4).build()?;
+//! let pixels = vec![0x10; 10_000];
+//! let inverted = pool.map("invert").workers(4).align(4).run(&pixels)?;
+//! assert_eq!(inverted, vec![0xef; 10_000]);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::pool::{Pool, PoolError};
+use crate::{Error, Instance, Value};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+/// A parallel map of an export over an input; created by [`Pool::map`].
+pub struct Map<'a> {
+    pool: &'a Pool,
+    export: String,
+    alloc: String,
+    workers: usize,
+    chunk_len: Option<usize>,
+    align: usize,
+}
+
+/// The output of one chunk, with its position in the input.
+type Output = (usize, Vec<u8>);
+
+impl<'a> Map<'a> {
+    pub(crate) fn new(pool: &'a Pool, export: &str) -> Map<'a> {
+        Map {
+            pool,
+            export: export.to_string(),
+            alloc: "alloc".to_string(),
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            chunk_len: None,
+            align: 1,
+        }
+    }
+
+    /// Runs on up to this many threads, each with its own instance. Defaults
+    /// to the number of CPUs. Workers that find the pool exhausted when they
+    /// start sit the run out.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Passes the export chunks of this many bytes, the last one possibly
+    /// shorter. Defaults to an equal share of the input for each worker.
+    /// Smaller chunks balance uneven work better at the cost of more calls.
+    pub fn chunk_len(mut self, len: usize) -> Self {
+        self.chunk_len = Some(len);
+        self
+    }
+
+    /// Splits the input only at multiples of this many bytes, such as the
+    /// size of a pixel, rounding the chunk length up. Defaults to 1.
+    pub fn align(mut self, align: usize) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Allocates each worker's buffer with this export instead of `alloc`.
+    pub fn alloc(mut self, export: &str) -> Self {
+        self.alloc = export.to_string();
+        self
+    }
+
+    /// Runs the export over `input` and returns its outputs in order. Stops
+    /// at the first chunk that fails.
+    ///
+    /// # Panics
+    ///
+    /// If `workers`, `chunk_len` or `align` is zero.
+    pub fn run(&self, input: &[u8]) -> Result<Vec<u8>, PoolError> {
+        assert!(
+            self.workers > 0 && self.chunk_len != Some(0) && self.align > 0,
+            "workers, chunk length and alignment must be positive"
+        );
+        if input.is_empty() {
+            return Ok(Vec::new());
+        }
+        let chunk_len = self
+            .chunk_len
+            .unwrap_or_else(|| input.len().div_ceil(self.workers));
+        let chunk_len = chunk_len.div_ceil(self.align) * self.align;
+        let chunks: Vec<&[u8]> = input.chunks(chunk_len).collect();
+
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let results: Vec<_> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.workers.min(chunks.len()))
+                .map(|_| scope.spawn(|| self.work(&chunks, &next, &failed)))
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+
+        let mut outputs = Vec::with_capacity(chunks.len());
+        for result in results {
+            if let Some(done) = result? {
+                outputs.extend(done);
+            }
+        }
+        if outputs.is_empty() {
+            return Err(PoolError::Exhausted);
+        }
+        outputs.sort_unstable_by_key(|&(index, _)| index);
+        Ok(outputs.into_iter().flat_map(|(_, output)| output).collect())
+    }
+
+    /// Processes chunks on one instance until none are left, returning
+    /// `None` if the pool had no instance to spare.
+    fn work(
+        &self,
+        chunks: &[&[u8]],
+        next: &AtomicUsize,
+        failed: &AtomicBool,
+    ) -> Result<Option<Vec<Output>>, PoolError> {
+        let instance = match self.pool.get() {
+            Ok(instance) => instance,
+            Err(PoolError::Exhausted) => return Ok(None),
+            Err(err) => {
+                failed.store(true, Ordering::SeqCst);
+                return Err(err);
+            }
+        };
+        let result = self.process(&instance, chunks, next, failed);
+        if result.is_err() {
+            failed.store(true, Ordering::SeqCst);
+        }
+        result.map(Some).map_err(PoolError::Call)
+    }
+
+    fn process(
+        &self,
+        instance: &Instance,
+        chunks: &[&[u8]],
+        next: &AtomicUsize,
+        failed: &AtomicBool,
+    ) -> crate::Result<Vec<Output>> {
+        // The first chunk is never shorter than the others.
+        let capacity = i32_len(chunks[0].len())?;
+        let buffer = match instance.call(&self.alloc, &[Value::I32(capacity)])?[..] {
+            [Value::I32(ptr)] => ptr,
+            _ => {
+                return Err(Error::SignatureMismatch(format!(
+                    "`{}` must return an i32 address",
+                    self.alloc
+                )))
+            }
+        };
+
+        let mut outputs = Vec::new();
+        while !failed.load(Ordering::SeqCst) {
+            let index = next.fetch_add(1, Ordering::SeqCst);
+            let chunk = match chunks.get(index) {
+                Some(chunk) => chunk,
+                None => break,
+            };
+            outputs.push((index, self.call(instance, buffer, chunk)?));
+        }
+
+        if instance.export("dealloc").is_some() {
+            instance.call("dealloc", &[Value::I32(buffer), Value::I32(capacity)])?;
+        }
+        Ok(outputs)
+    }
+
+    /// Runs the export over one chunk copied into the buffer at `ptr`.
+    fn call(&self, instance: &Instance, ptr: i32, chunk: &[u8]) -> crate::Result<Vec<u8>> {
+        let offset = ptr as u32 as usize;
+        instance.write_memory(offset, chunk)?;
+        let len = i32_len(chunk.len())?;
+        let output_len = match instance.call(&self.export, &[Value::I32(ptr), Value::I32(len)])?[..]
+        {
+            [] => chunk.len(),
+            [Value::I32(len)] if len >= 0 => len as usize,
+            [Value::I32(len)] => {
+                return Err(Error::Memory(format!(
+                    "`{}` returned a negative length {}",
+                    self.export, len
+                )))
+            }
+            _ => {
+                return Err(Error::SignatureMismatch(format!(
+                    "`{}` must return nothing or an i32 length",
+                    self.export
+                )))
+            }
+        };
+        let mut output = vec![0; output_len];
+        instance.read_memory(offset, &mut output)?;
+        Ok(output)
+    }
+}
+
+fn i32_len(len: usize) -> crate::Result<i32> {
+    i32::try_from(len)
+        .map_err(|_| Error::Memory(format!("a chunk of {} bytes does not fit in memory", len)))
+}
@@ -0,0 +1,332 @@
+//! A pool of instances of one module that can be shared between threads.
+//!
+//! An [`Instance`] can be moved to another thread but not used from two at
+//! once, so callers that run concurrently each check one out of a [`Pool`]
+//! for the duration of their call.
+//!
+//! ```
+//! use adder::pool::{Exhaustion, Pool, Reset};
+//! use adder::{Engine, Value};
+//! use std::thread;
+//!
+//! let module = Engine::new().compile(include_bytes!("add.wasm"))?;
+//! let pool = Pool::builder(module)
+//!     .min(1)
+//!     .max(4)
+//!     .reset(Reset::Snapshot)
+//!     .on_exhaustion(Exhaustion::Block)
+//!     .build()?;
+//! thread::scope(|scope| {
+//!     for x in 0..8 {
+//!         let pool = &pool;
+//!         scope.spawn(move || {
+//!             assert_eq!(pool.call("add_one", &[Value::I32(x)]).unwrap(), [Value::I32(x + 1)]);
+//!         });
+//!     }
+//! });
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::parallel::Map;
+use crate::snapshot::Snapshot;
+use crate::{Error, ImportObject, Instance, Module, Value};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread;
+
+/// What happens to an instance's state between checkouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Reset {
+    /// Nothing: the next caller sees the state the previous one left.
+    #[default]
+    Keep,
+    /// Restore the memories, mutable globals and table elements captured
+    /// right after the instance was created. An instance whose memory has
+    /// grown since cannot be restored and is replaced instead.
+    Snapshot,
+    /// Replace the instance with a new one.
+    Reinstantiate,
+}
+
+/// What [`Pool::get`] does when every instance is checked out and the pool
+/// is at its maximum size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Exhaustion {
+    /// Wait until an instance is returned.
+    #[default]
+    Block,
+    /// Return [`PoolError::Exhausted`].
+    Fail,
+    /// Create an instance beyond the maximum. Instances over the maximum are
+    /// dropped when they are returned.
+    Grow,
+}
+
+/// Errors produced by a [`Pool`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum PoolError {
+    /// Every instance is checked out and the pool may not grow.
+    Exhausted,
+    /// A new instance could not be created.
+    Instantiate(Error),
+    /// The call made through [`Pool::call`] failed.
+    Call(Error),
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PoolError::Exhausted => f.write_str("every instance in the pool is in use"),
+            PoolError::Instantiate(err) | PoolError::Call(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+type ImportsFn = dyn Fn() -> ImportObject + Send + Sync;
+
+/// Configures a [`Pool`]; created by [`Pool::builder`].
+pub struct PoolBuilder {
+    module: Module,
+    imports: Option<Box<ImportsFn>>,
+    min: usize,
+    max: usize,
+    reset: Reset,
+    exhaustion: Exhaustion,
+}
+
+impl PoolBuilder {
+    /// Creates this many instances up front. Defaults to 1.
+    pub fn min(mut self, min: usize) -> Self {
+        self.min = min;
+        self
+    }
+
+    /// Caps the number of instances, checked out or idle. Defaults to the
+    /// number of CPUs.
+    pub fn max(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// What to reset between checkouts. Defaults to [`Reset::Keep`].
+    pub fn reset(mut self, reset: Reset) -> Self {
+        self.reset = reset;
+        self
+    }
+
+    /// What to do when every instance is in use. Defaults to
+    /// [`Exhaustion::Block`].
+    pub fn on_exhaustion(mut self, exhaustion: Exhaustion) -> Self {
+        self.exhaustion = exhaustion;
+        self
+    }
+
+    /// Instantiates with the import objects `imports` returns, called once
+    /// per instance. Without this, instances get no imports.
+    pub fn imports(mut self, imports: impl Fn() -> ImportObject + Send + Sync + 'static) -> Self {
+        self.imports = Some(Box::new(imports));
+        self
+    }
+
+    /// Creates the pool and its first `min` instances.
+    ///
+    /// # Panics
+    ///
+    /// If `max` is zero or less than `min`.
+    pub fn build(self) -> Result<Pool, PoolError> {
+        assert!(
+            self.max > 0 && self.min <= self.max,
+            "pool sizes must satisfy 0 <= min <= max and max > 0"
+        );
+        let pool = Pool {
+            module: self.module,
+            imports: self.imports,
+            max: self.max,
+            reset: self.reset,
+            exhaustion: self.exhaustion,
+            state: Mutex::new(State {
+                idle: Vec::with_capacity(self.max),
+                size: 0,
+            }),
+            returned: Condvar::new(),
+        };
+        for _ in 0..self.min {
+            let slot = pool.create()?;
+            let mut state = pool.lock();
+            state.idle.push(slot);
+            state.size += 1;
+        }
+        Ok(pool)
+    }
+}
+
+/// Instances of one module, checked out by one caller at a time.
+pub struct Pool {
+    module: Module,
+    imports: Option<Box<ImportsFn>>,
+    max: usize,
+    reset: Reset,
+    exhaustion: Exhaustion,
+    state: Mutex<State>,
+    /// Signalled whenever an instance is returned or a slot frees up.
+    returned: Condvar,
+}
+
+struct State {
+    idle: Vec<Slot>,
+    /// Instances that exist, idle or checked out, including ones being
+    /// created.
+    size: usize,
+}
+
+struct Slot {
+    instance: Instance,
+    /// The instance's state right after it was created, for
+    /// [`Reset::Snapshot`].
+    initial: Option<Snapshot>,
+}
+
+impl Pool {
+    /// Starts configuring a pool of instances of `module`.
+    pub fn builder(module: Module) -> PoolBuilder {
+        PoolBuilder {
+            module,
+            imports: None,
+            min: 1,
+            max: thread::available_parallelism().map_or(1, |n| n.get()),
+            reset: Reset::default(),
+            exhaustion: Exhaustion::default(),
+        }
+    }
+
+    /// Checks out an instance, which is returned to the pool when the guard
+    /// is dropped.
+    pub fn get(&self) -> Result<Pooled<'_>, PoolError> {
+        let mut state = self.lock();
+        loop {
+            if let Some(slot) = state.idle.pop() {
+                return Ok(Pooled {
+                    pool: self,
+                    slot: Some(slot),
+                });
+            }
+            if state.size < self.max || self.exhaustion == Exhaustion::Grow {
+                state.size += 1;
+                drop(state);
+                return match self.create() {
+                    Ok(slot) => Ok(Pooled {
+                        pool: self,
+                        slot: Some(slot),
+                    }),
+                    Err(err) => {
+                        self.release();
+                        Err(err)
+                    }
+                };
+            }
+            match self.exhaustion {
+                Exhaustion::Fail => return Err(PoolError::Exhausted),
+                _ => {
+                    state = self
+                        .returned
+                        .wait(state)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                }
+            }
+        }
+    }
+
+    /// Checks out an instance, calls its export `name` and returns it.
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Vec<Value>, PoolError> {
+        self.get()?.call(name, args).map_err(PoolError::Call)
+    }
+
+    /// Prepares a run of the export `name` over an input split across
+    /// instances on worker threads; see [`parallel`](crate::parallel).
+    pub fn map(&self, name: &str) -> Map<'_> {
+        Map::new(self, name)
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// The number of instances, idle or checked out.
+    pub fn size(&self) -> usize {
+        self.lock().size
+    }
+
+    /// The number of instances waiting to be checked out.
+    pub fn idle(&self) -> usize {
+        self.lock().idle.len()
+    }
+
+    fn create(&self) -> Result<Slot, PoolError> {
+        let instance = match &self.imports {
+            Some(imports) => self.module.instantiate_with(&imports()),
+            None => self.module.instantiate(),
+        }
+        .map_err(PoolError::Instantiate)?;
+        let initial = match self.reset {
+            Reset::Snapshot => Some(Snapshot::capture(&instance)),
+            _ => None,
+        };
+        Ok(Slot { instance, initial })
+    }
+
+    fn check_in(&self, mut slot: Slot) {
+        if self.lock().size > self.max {
+            return self.release();
+        }
+        let restored = match (self.reset, &slot.initial) {
+            (Reset::Keep, _) => true,
+            (Reset::Snapshot, Some(initial)) => initial.restore(&mut slot.instance).is_ok(),
+            _ => false,
+        };
+        if !restored {
+            slot = match self.create() {
+                Ok(slot) => slot,
+                Err(_) => return self.release(),
+            };
+        }
+        self.lock().idle.push(slot);
+        self.returned.notify_one();
+    }
+
+    /// Forgets an instance that was checked out or being created.
+    fn release(&self) {
+        self.lock().size -= 1;
+        self.returned.notify_one();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// An instance checked out of a [`Pool`], returned to it on drop.
+pub struct Pooled<'a> {
+    pool: &'a Pool,
+    slot: Option<Slot>,
+}
+
+impl Deref for Pooled<'_> {
+    type Target = Instance;
+
+    fn deref(&self) -> &Instance {
+        &self.slot.as_ref().unwrap().instance
+    }
+}
+
+impl Drop for Pooled<'_> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            self.pool.check_in(slot);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// Code generated by wasmer's cranelift backend calls `__rust_probestack` to
+// touch every page of large stack frames. Newer Rust toolchains no longer
+// export that symbol from `compiler_builtins`, which leaves it undefined in
+// anything linking wasmer 0.13, so this provides the same routine.
+//
+// The frame size to probe is passed in `rax`, and every register other
+// than `r11` must be preserved. The body is the one `compiler_builtins`
+// used to export. tests/probestack.rs runs functions with multi-page
+// frames through it.
+
+#[cfg(target_arch = "x86_64")]
+macro_rules! probestack_body {
+    () => {
+        "
+        push rbp
+        mov rbp, rsp
+        mov r11, rax
+        cmp r11, 0x1000
+        jna 3f
+    2:
+        sub rsp, 0x1000
+        test qword ptr [rsp + 8], rsp
+        sub r11, 0x1000
+        cmp r11, 0x1000
+        ja 2b
+    3:
+        sub rsp, r11
+        test qword ptr [rsp + 8], rsp
+        add rsp, rax
+        leave
+        ret
+        "
+    };
+}
+
+#[cfg(all(target_arch = "x86_64", not(target_os = "macos")))]
+std::arch::global_asm!(
+    ".globl __rust_probestack",
+    ".type __rust_probestack, @function",
+    ".hidden __rust_probestack",
+    "__rust_probestack:",
+    probestack_body!(),
+    ".size __rust_probestack, . - __rust_probestack",
+);
+
+#[cfg(all(target_arch = "x86_64", target_os = "macos"))]
+std::arch::global_asm!(
+    ".globl ___rust_probestack",
+    ".private_extern ___rust_probestack",
+    "___rust_probestack:",
+    probestack_body!(),
+);
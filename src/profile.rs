@@ -0,0 +1,230 @@
+use crate::{Instance, Result, Value};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+/// Whether a profiled function is exported by the module or imported from the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FunctionKind {
+    Export,
+    Import,
+}
+
+impl FunctionKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            FunctionKind::Export => "export",
+            FunctionKind::Import => "import",
+        }
+    }
+}
+
+/// Call count and wall time recorded for a single function.
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionProfile {
+    pub name: String,
+    pub kind: FunctionKind,
+    pub calls: u64,
+    /// Time spent inside the function, including nested calls.
+    pub total_ns: u64,
+    /// Time spent inside the function, excluding nested calls.
+    pub self_ns: u64,
+}
+
+/// Self time recorded for one distinct call stack, outermost frame first.
+#[derive(Debug, Clone, Serialize)]
+pub struct StackProfile {
+    pub frames: Vec<String>,
+    pub self_ns: u64,
+}
+
+/// A point-in-time copy of everything a `Profiler` has recorded.
+#[derive(Debug, Clone, Serialize)]
+pub struct Profile {
+    pub functions: Vec<FunctionProfile>,
+    pub stacks: Vec<StackProfile>,
+}
+
+impl Profile {
+    /// Renders the profile as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("profile is always serializable")
+    }
+
+    /// Renders the profile in the collapsed-stack format understood by
+    /// `flamegraph.pl` and `inferno`, weighted by self time in microseconds.
+    pub fn to_collapsed(&self) -> String {
+        let mut out = String::new();
+        for stack in &self.stacks {
+            let micros = stack.self_ns / 1_000;
+            if micros == 0 {
+                continue;
+            }
+            writeln!(out, "{} {}", stack.frames.join(";"), micros).unwrap();
+        }
+        out
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    calls: u64,
+    total: Duration,
+    self_time: Duration,
+}
+
+struct Frame {
+    key: (FunctionKind, String),
+    start: Instant,
+    children: Duration,
+    recursive: bool,
+}
+
+#[derive(Default)]
+struct State {
+    /// The frames entered and not yet exited on each thread.
+    stacks_by_thread: HashMap<ThreadId, Vec<Frame>>,
+    functions: BTreeMap<(FunctionKind, String), Stats>,
+    stacks: BTreeMap<Vec<String>, Duration>,
+}
+
+/// Records call counts and wall time for exported functions and host imports.
+///
+/// Profiling is opt-in: exports are only timed when called through
+/// [`Profiler::call`], and host imports only when their body holds the guard
+/// returned by [`Profiler::enter_import`]. The profiler is cheap to clone and
+/// every clone records into the same profile, so it can be moved into
+/// `func!` closures. Each thread has its own call stack, so calls made on
+/// several threads at once don't nest inside each other.
+#[derive(Clone, Default)]
+pub struct Profiler {
+    state: Arc<Mutex<State>>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls the export `name` on `instance`, recording it in the profile.
+    pub fn call(&self, instance: &Instance, name: &str, params: &[Value]) -> Result<Vec<Value>> {
+        let _scope = self.enter(FunctionKind::Export, name);
+        instance.call(name, params)
+    }
+
+    /// Starts timing the host import `name`. Timing stops when the returned
+    /// guard is dropped, so bind it at the top of the import's body.
+    pub fn enter_import(&self, name: &str) -> Scope<'_> {
+        self.enter(FunctionKind::Import, name)
+    }
+
+    /// Returns everything recorded so far.
+    pub fn profile(&self) -> Profile {
+        let state = self.lock();
+        let functions = state
+            .functions
+            .iter()
+            .map(|((kind, name), stats)| FunctionProfile {
+                name: name.clone(),
+                kind: *kind,
+                calls: stats.calls,
+                total_ns: stats.total.as_nanos() as u64,
+                self_ns: stats.self_time.as_nanos() as u64,
+            })
+            .collect();
+        let stacks = state
+            .stacks
+            .iter()
+            .map(|(frames, self_time)| StackProfile {
+                frames: frames.clone(),
+                self_ns: self_time.as_nanos() as u64,
+            })
+            .collect();
+        Profile { functions, stacks }
+    }
+
+    /// Discards everything recorded so far.
+    pub fn reset(&self) {
+        let mut state = self.lock();
+        state.functions.clear();
+        state.stacks.clear();
+    }
+
+    fn enter(&self, kind: FunctionKind, name: &str) -> Scope<'_> {
+        let key = (kind, name.to_string());
+        let thread = thread::current().id();
+        let mut state = self.lock();
+        let stack = state.stacks_by_thread.entry(thread).or_default();
+        let recursive = stack.iter().any(|frame| frame.key == key);
+        stack.push(Frame {
+            key,
+            start: Instant::now(),
+            children: Duration::default(),
+            recursive,
+        });
+        Scope {
+            profiler: self,
+            thread,
+        }
+    }
+
+    fn exit(&self, thread: ThreadId) {
+        let mut state = self.lock();
+        let stack = match state.stacks_by_thread.get_mut(&thread) {
+            Some(stack) => stack,
+            None => return,
+        };
+        let frame = match stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let elapsed = frame.start.elapsed();
+        let self_time = elapsed.checked_sub(frame.children).unwrap_or_default();
+        if let Some(parent) = stack.last_mut() {
+            parent.children += elapsed;
+        }
+
+        let mut path: Vec<String> = stack
+            .iter()
+            .map(|frame| format!("{}:{}", frame.key.0.prefix(), frame.key.1))
+            .collect();
+        path.push(format!("{}:{}", frame.key.0.prefix(), frame.key.1));
+        if stack.is_empty() {
+            state.stacks_by_thread.remove(&thread);
+        }
+        *state.stacks.entry(path).or_default() += self_time;
+
+        let stats = state.functions.entry(frame.key).or_default();
+        stats.calls += 1;
+        stats.self_time += self_time;
+        // Only the outermost frame of a recursive function counts towards its
+        // total, otherwise the nested time would be counted twice.
+        if !frame.recursive {
+            stats.total += elapsed;
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Guard returned by [`Profiler::enter_import`]; stops timing when dropped.
+pub struct Scope<'a> {
+    profiler: &'a Profiler,
+    /// The thread whose stack the scope was entered on, even if the guard
+    /// is dropped on another.
+    thread: ThreadId,
+}
+
+impl Drop for Scope<'_> {
+    fn drop(&mut self) {
+        self.profiler.exit(self.thread);
+    }
+}
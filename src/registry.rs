@@ -0,0 +1,246 @@
+//! A registry of modules and instances by name and version, shared between
+//! threads.
+//!
+//! Callers register what they load once and look it up by name from then
+//! on, instead of passing handles around. Without a version, lookups return
+//! the highest version registered under the name.
+//!
+//! ```
+//! use adder::registry::{Registry, Version};
+//! use adder::{Engine, Value};
+//!
+//! let registry = Registry::new();
+//! let module = Engine::new().compile(include_bytes!("add.wasm"))?;
+//! registry.register_module("adder", "1.0.0".parse()?, module.clone())?;
+//! registry.register_module("adder", "1.2.0".parse()?, module)?;
+//!
+//! let latest = registry.module("adder", None).unwrap();
+//! registry.register_instance("adder", Version::new(1, 2, 0), latest.instantiate()?)?;
+//! let instance = registry.instance("adder", None).unwrap();
+//! assert_eq!(
+//!     instance.lock().unwrap().call("add_one", &[Value::I32(1)])?,
+//!     [Value::I32(2)]
+//! );
+//! assert_eq!(registry.modules().len(), 2);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! A [`Linker`](crate::Linker) given a registry instantiates registered
+//! modules to satisfy imports from namespaces it has nothing linked under.
+
+use crate::{Instance, Module};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A `major.minor.patch` version, ordered numerically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for Version {
+    type Err = RegistryError;
+
+    fn from_str(text: &str) -> Result<Version, RegistryError> {
+        let invalid = || RegistryError::InvalidVersion(text.to_string());
+        let mut parts = text.split('.').map(|part| {
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            part.parse().map_err(|_| invalid())
+        });
+        let version = Version::new(
+            parts.next().ok_or_else(invalid)??,
+            parts.next().ok_or_else(invalid)??,
+            parts.next().ok_or_else(invalid)??,
+        );
+        match parts.next() {
+            Some(_) => Err(invalid()),
+            None => Ok(version),
+        }
+    }
+}
+
+/// Errors produced by a [`Registry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    /// Something is already registered under this name and version.
+    AlreadyRegistered { name: String, version: Version },
+    /// Nothing is registered under this name and version.
+    NotFound { name: String, version: Version },
+    /// The text is not a `major.minor.patch` version.
+    InvalidVersion(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegistryError::AlreadyRegistered { name, version } => {
+                write!(f, "`{}` {} is already registered", name, version)
+            }
+            RegistryError::NotFound { name, version } => {
+                write!(f, "`{}` {} is not registered", name, version)
+            }
+            RegistryError::InvalidVersion(text) => write!(f, "invalid version `{}`", text),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Items of one kind, by name and then version.
+type Entries<T> = BTreeMap<String, BTreeMap<Version, T>>;
+
+/// Modules and instances by name and version. Clones share the same
+/// registry.
+#[derive(Clone, Default)]
+pub struct Registry {
+    inner: Arc<RwLock<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    modules: Entries<Module>,
+    instances: Entries<Arc<Mutex<Instance>>>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    pub fn register_module(
+        &self,
+        name: &str,
+        version: Version,
+        module: Module,
+    ) -> Result<(), RegistryError> {
+        insert(&mut self.write().modules, name, version, module)
+    }
+
+    /// Returns the module registered as `name` at `version`, or at its
+    /// highest version if `version` is `None`.
+    pub fn module(&self, name: &str, version: Option<Version>) -> Option<Module> {
+        get(&self.read().modules, name, version).cloned()
+    }
+
+    /// Lists the registered modules' names and versions in order.
+    pub fn modules(&self) -> Vec<(String, Version)> {
+        list(&self.read().modules)
+    }
+
+    /// Removes a module from the registry. Instances of it are unaffected.
+    pub fn unload_module(&self, name: &str, version: Version) -> Result<Module, RegistryError> {
+        remove(&mut self.write().modules, name, version)
+    }
+
+    /// Registers an instance, which can then be used from any thread holding
+    /// the registry, one at a time.
+    pub fn register_instance(
+        &self,
+        name: &str,
+        version: Version,
+        instance: Instance,
+    ) -> Result<(), RegistryError> {
+        let instance = Arc::new(Mutex::new(instance));
+        insert(&mut self.write().instances, name, version, instance)
+    }
+
+    /// Returns the instance registered as `name` at `version`, or at its
+    /// highest version if `version` is `None`.
+    pub fn instance(&self, name: &str, version: Option<Version>) -> Option<Arc<Mutex<Instance>>> {
+        get(&self.read().instances, name, version).cloned()
+    }
+
+    /// Lists the registered instances' names and versions in order.
+    pub fn instances(&self) -> Vec<(String, Version)> {
+        list(&self.read().instances)
+    }
+
+    /// Removes an instance from the registry. Callers that looked it up
+    /// before keep it alive until they drop it.
+    pub fn unload_instance(
+        &self,
+        name: &str,
+        version: Version,
+    ) -> Result<Arc<Mutex<Instance>>, RegistryError> {
+        remove(&mut self.write().instances, name, version)
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Inner> {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Inner> {
+        self.inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn insert<T>(
+    entries: &mut Entries<T>,
+    name: &str,
+    version: Version,
+    item: T,
+) -> Result<(), RegistryError> {
+    let versions = entries.entry(name.to_string()).or_default();
+    if versions.contains_key(&version) {
+        return Err(RegistryError::AlreadyRegistered {
+            name: name.to_string(),
+            version,
+        });
+    }
+    versions.insert(version, item);
+    Ok(())
+}
+
+fn get<'a, T>(entries: &'a Entries<T>, name: &str, version: Option<Version>) -> Option<&'a T> {
+    let versions = entries.get(name)?;
+    match version {
+        Some(version) => versions.get(&version),
+        None => versions.values().next_back(),
+    }
+}
+
+fn list<T>(entries: &Entries<T>) -> Vec<(String, Version)> {
+    entries
+        .iter()
+        .flat_map(|(name, versions)| versions.keys().map(move |&version| (name.clone(), version)))
+        .collect()
+}
+
+fn remove<T>(entries: &mut Entries<T>, name: &str, version: Version) -> Result<T, RegistryError> {
+    let not_found = || RegistryError::NotFound {
+        name: name.to_string(),
+        version,
+    };
+    let versions = entries.get_mut(name).ok_or_else(not_found)?;
+    let item = versions.remove(&version).ok_or_else(not_found)?;
+    if versions.is_empty() {
+        entries.remove(name);
+    }
+    Ok(item)
+}
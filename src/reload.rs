@@ -0,0 +1,227 @@
+//! Replaces an instance with one of a rebuilt module, keeping its state when
+//! the new module can take it over.
+//!
+//! ```
+//! use adder::{reload, Engine, ImportObject, Value};
+//!
+//! let counter = |step: i32| format!(r#"(module
+//!     (global $count (mut i32) (i32.const 0))
+//!     (func (export "bump") (result i32)
+//!         (global.set $count (i32.add (global.get $count) (i32.const {})))
+//!         (global.get $count)))"#, step);
+//!
+//! let engine = Engine::new();
+//! let mut instance = engine.compile(counter(1).as_bytes())?.instantiate()?;
+//! instance.call("bump", &[])?;
+//!
+//! let report = reload::reload(&mut instance, &engine, counter(10).as_bytes(), &ImportObject::new())?;
+//! assert!(report.carried());
+//! assert_eq!(instance.call("bump", &[])?, [Value::I32(11)]);
+//! # Ok::<(), adder::Error>(())
+//! ```
+//!
+//! State is carried over when nothing callers rely on has changed:
+//!
+//! - no export was removed or changed type, other than the limits of an
+//!   exported memory or table;
+//! - the module has the same memories, and their current sizes fit the new
+//!   limits;
+//! - it has the same data segments, so static data sits where the old code
+//!   left it;
+//! - it declares globals of the same types and mutability in the same order.
+//!
+//! Otherwise the new instance starts from its initial state. Tables are
+//! never carried over: they hold the new module's functions.
+
+use crate::snapshot::Snapshot;
+use crate::{Engine, ExportType, ExternType, ImportObject, Instance, Result};
+use std::fmt;
+use wasmer_runtime_core::module::ModuleInfo;
+use wasmer_runtime_core::structures::TypedIndex;
+
+/// An export whose type differs between the old and new module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedExport {
+    pub name: String,
+    pub old: ExternType,
+    pub new: ExternType,
+}
+
+/// What a [`reload`] did.
+///
+/// Displays as one line per added (`+`), removed (`-`) or changed (`~`)
+/// export, then whether state was carried over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Exports only the new module has.
+    pub added: Vec<ExportType>,
+    /// Exports only the old module had.
+    pub removed: Vec<ExportType>,
+    /// Exports both modules have, with different types.
+    pub changed: Vec<ChangedExport>,
+    /// Why the old instance's state was not carried over. Empty if it was.
+    pub incompatible: Vec<String>,
+}
+
+impl Report {
+    /// Whether the new instance kept the old one's memories and globals.
+    pub fn carried(&self) -> bool {
+        self.incompatible.is_empty()
+    }
+
+    /// Whether any export was added, removed or changed type.
+    pub fn exports_changed(&self) -> bool {
+        !(self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for export in &self.added {
+            writeln!(f, "+ {}: {}", export.name, export.ty)?;
+        }
+        for export in &self.removed {
+            writeln!(f, "- {}: {}", export.name, export.ty)?;
+        }
+        for export in &self.changed {
+            writeln!(f, "~ {}: {} -> {}", export.name, export.old, export.new)?;
+        }
+        if self.carried() {
+            write!(f, "state carried over")
+        } else {
+            write!(f, "state reset: {}", self.incompatible.join("; "))
+        }
+    }
+}
+
+/// Compiles `wasm`, instantiates it with `imports` and replaces `instance`
+/// with the result, carrying over its state if the modules are compatible.
+///
+/// If the new module fails to compile or instantiate, `instance` is left as
+/// it was.
+pub fn reload(
+    instance: &mut Instance,
+    engine: &Engine,
+    wasm: &[u8],
+    imports: &ImportObject,
+) -> Result<Report> {
+    let mut new = engine.compile(wasm)?.instantiate_with(imports)?;
+    let mut report = compare_exports(&instance.exports(), &new.exports());
+    check_state(instance, &new, &mut report.incompatible);
+
+    if report.carried() {
+        let mut state = Snapshot::capture(instance);
+        state.tables = Snapshot::capture(&new).tables;
+        if let Err(err) = state.restore(&mut new) {
+            // The checks above should have caught this; start afresh.
+            new = engine.compile(wasm)?.instantiate_with(imports)?;
+            report.incompatible.push(err.to_string());
+        }
+    }
+    *instance = new;
+    Ok(report)
+}
+
+fn compare_exports(old: &[ExportType], new: &[ExportType]) -> Report {
+    let find = |exports: &[ExportType], name: &str| {
+        exports
+            .iter()
+            .find(|export| export.name == name)
+            .map(|export| export.ty.clone())
+    };
+    let mut report = Report {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        incompatible: Vec::new(),
+    };
+    for export in new {
+        if find(old, &export.name).is_none() {
+            report.added.push(export.clone());
+        }
+    }
+    for export in old {
+        match find(new, &export.name) {
+            None => {
+                report.removed.push(export.clone());
+                report
+                    .incompatible
+                    .push(format!("export `{}` was removed", export.name));
+            }
+            Some(ty) if ty != export.ty => {
+                // Memory and table limits are checked against the state itself.
+                if !same_kind(&ty, &export.ty) {
+                    report
+                        .incompatible
+                        .push(format!("export `{}` changed type", export.name));
+                }
+                report.changed.push(ChangedExport {
+                    name: export.name.clone(),
+                    old: export.ty.clone(),
+                    new: ty,
+                });
+            }
+            Some(_) => {}
+        }
+    }
+    report
+}
+
+fn same_kind(a: &ExternType, b: &ExternType) -> bool {
+    matches!(
+        (a, b),
+        (ExternType::Memory { .. }, ExternType::Memory { .. })
+            | (ExternType::Table { .. }, ExternType::Table { .. })
+    )
+}
+
+/// Records why `old`'s memories and globals can't be moved into `new`.
+fn check_state(old: &Instance, new: &Instance, incompatible: &mut Vec<String>) {
+    let old_info = &old.as_wasmer().module.info;
+    let new_info = &new.as_wasmer().module.info;
+
+    if old_info.imported_memories.len() != new_info.imported_memories.len()
+        || old_info.memories.len() != new_info.memories.len()
+    {
+        incompatible.push("the modules have different memories".to_string());
+    } else {
+        let ctx = old.as_wasmer().context();
+        for (local, desc) in new_info.memories.iter() {
+            let index = old_info.imported_memories.len() + local.index();
+            let pages = ctx.memory(index as u32).size();
+            if pages < desc.minimum || desc.maximum.is_some_and(|max| pages > max) {
+                incompatible.push(format!(
+                    "memory {} has {} pages, outside the new limits",
+                    index, pages.0
+                ));
+            }
+        }
+    }
+
+    if !same_data(old_info, new_info) {
+        incompatible.push("the data segments changed".to_string());
+    }
+
+    let globals = |info: &ModuleInfo| {
+        info.globals
+            .iter()
+            .map(|(_, global)| (global.desc.ty, global.desc.mutable))
+            .collect::<Vec<_>>()
+    };
+    if old_info.imported_globals.len() != new_info.imported_globals.len()
+        || globals(old_info) != globals(new_info)
+    {
+        incompatible.push("the globals changed".to_string());
+    }
+}
+
+fn same_data(old: &ModuleInfo, new: &ModuleInfo) -> bool {
+    old.data_initializers.len() == new.data_initializers.len()
+        && old
+            .data_initializers
+            .iter()
+            .zip(&new.data_initializers)
+            .all(|(old, new)| {
+                old.memory_index == new.memory_index && old.base == new.base && old.data == new.data
+            })
+}
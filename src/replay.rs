@@ -0,0 +1,1239 @@
+//! Records a module's interactions with its host so that a misbehaving run
+//! can be reproduced later without the host.
+//!
+//! A [`Recording`] runs a module against the real imports and logs every
+//! exported call made through it, every host import the module calls, and
+//! what those imports returned and wrote to memory. [`replay`] then runs the
+//! same module against the [`Log`] alone: imports are answered from the log,
+//! and every exported call is made again and checked against the recorded
+//! result.
+//!
+//! ```
+//! use adder::replay::{self, Recording};
+//! use adder::{func, imports, Engine, Value};
+//!
+//! let wat = r#"(module
+//!     (import "env" "now" (func $now (result i64)))
+//!     (func (export "elapsed") (param i64) (result i64)
+//!         call $now
+//!         local.get 0
+//!         i64.sub))"#;
+//! let engine = Engine::new();
+//! let host = imports! { "env" => { "now" => func!(|| -> i64 { 1_700_000_000 }), }, };
+//! let recording = Recording::start(&engine, wat.as_bytes(), host)?;
+//! recording.call("elapsed", &[Value::I64(1_600_000_000)])?;
+//!
+//! let log = recording.log();
+//! replay::replay(&engine, wat.as_bytes(), &log)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Modules are instrumented to make this possible: each function import is
+//! called through a generated shim that reports its arguments and result to
+//! the recorder. The recorder compares the default memory before and after
+//! every import call to find what the host wrote, so recording is slow for
+//! modules with large memories. Only function imports are replaced during a
+//! replay; modules that import memories, tables or globals cannot be
+//! replayed.
+
+use crate::instance::ctx_memory;
+use crate::state_file::{module_hash, ModuleHash};
+use crate::{func, source, Engine, Error, ImportObject, Instance, Result, Value, ValueType};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::convert::{Infallible, TryInto};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use wasm_encoder::reencode::{self, Reencode};
+use wasm_encoder::{
+    CodeSection, EntityType, ExportSection, Function, FunctionSection, GlobalSection,
+    ImportSection, Instruction, RawSection, StartSection, TypeSection,
+};
+use wasmer_runtime::{Ctx, Memory};
+use wasmer_runtime_core::import::Namespace;
+use wasmer_runtime_core::units::Pages;
+use wasmparser::{BinaryReader, CodeSectionReader, FuncType, Parser, Payload, TypeRef};
+
+const MAGIC: &[u8; 4] = b"WRPL";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 4 + 2 + 32;
+
+/// The most bytes of events [`Log::from_bytes`] and [`Log::load`] inflate a
+/// log to. Logs hold host call results and memory writes, so even long
+/// recordings stay well under this. Use [`Log::from_bytes_with_limit`] for a
+/// different bound.
+pub const MAX_LOG_LEN: u64 = 1 << 30;
+
+/// The namespace the instrumented module imports its hooks from.
+const HOOKS: &str = "adder:replay";
+
+/// The hooks, in the order they are imported. `arg_*` and `ret_*` are
+/// indexed by [`type_offset`].
+const HOOK_NAMES: [&str; 10] = [
+    "enter", "arg_i32", "arg_i64", "arg_f32", "arg_f64", "ret", "ret_i32", "ret_i64", "ret_f32",
+    "ret_f64",
+];
+const ENTER: u32 = 0;
+const ARG: u32 = 1;
+const RET: u32 = 5;
+const RET_VALUE: u32 = 6;
+
+/// Unchanged runs shorter than this are kept inside a logged memory write
+/// rather than splitting it.
+const MIN_UNCHANGED_GAP: usize = 8;
+
+/// Errors produced while loading a log or replaying it.
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// The file is not a replay log, or is truncated.
+    Malformed(&'static str),
+    /// The file was written by a newer, unknown format version.
+    UnsupportedVersion(u16),
+    /// The events inflate to more than `limit` bytes.
+    TooLarge {
+        limit: u64,
+    },
+    /// The log was recorded with a different module than the one replayed.
+    ModuleMismatch,
+    /// The module could not be instrumented, compiled or instantiated.
+    Setup(Error),
+    /// The module did something other than what was recorded at `event`.
+    Diverged {
+        event: usize,
+        what: String,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "replay log I/O error: {}", err),
+            ReplayError::Malformed(what) => write!(f, "malformed replay log: {}", what),
+            ReplayError::UnsupportedVersion(version) => {
+                write!(f, "unsupported replay log version {}", version)
+            }
+            ReplayError::TooLarge { limit } => write!(
+                f,
+                "replay log events are larger than the limit of {} bytes",
+                limit
+            ),
+            ReplayError::ModuleMismatch => {
+                f.write_str("replay log was recorded with a different module")
+            }
+            ReplayError::Setup(err) => err.fmt(f),
+            ReplayError::Diverged { event, what } => {
+                write!(f, "replay diverged at event {}: {}", event, what)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReplayError::Io(err) => Some(err),
+            ReplayError::Setup(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl From<Error> for ReplayError {
+    fn from(err: Error) -> Self {
+        ReplayError::Setup(err)
+    }
+}
+
+/// Something that happened while recording.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// An exported function was called through [`Recording::call`].
+    Call { export: String, args: Vec<Value> },
+    /// The preceding call returned.
+    Return(Vec<Value>),
+    /// The preceding call trapped.
+    Trap,
+    /// The module called a host import, during the preceding call or while
+    /// it was being instantiated.
+    Import(ImportCall),
+}
+
+/// A call from the module to a host import.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportCall {
+    /// The import's index among the module's function imports.
+    pub index: u32,
+    pub args: Vec<Value>,
+    /// What the import returned, unless it has no result or trapped.
+    pub result: Option<Value>,
+    /// Whether the import trapped instead of returning.
+    pub trapped: bool,
+    /// By how many pages the import grew the default memory.
+    pub grew: u32,
+    /// What the import wrote to the default memory, in address order.
+    pub writes: Vec<MemoryWrite>,
+}
+
+/// Bytes written to the default memory by a host import.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryWrite {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// Everything recorded for one run of a module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Log {
+    /// Hash of the module the log was recorded with; see
+    /// [`module_hash`](crate::state_file::module_hash).
+    pub module: ModuleHash,
+    pub events: Vec<Event>,
+}
+
+impl Log {
+    /// Encodes the log as a fixed header (magic, format version and module
+    /// hash) followed by the deflated events.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut events = Vec::new();
+        write_len(&mut events, self.events.len());
+        for event in &self.events {
+            write_event(&mut events, event);
+        }
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&events).unwrap();
+        let payload = encoder.finish().unwrap();
+
+        let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.module);
+        out.extend_from_slice(&payload);
+        out
+    }
+
+    /// Decodes a log produced by [`Log::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> std::result::Result<Log, ReplayError> {
+        Log::from_bytes_with_limit(bytes, MAX_LOG_LEN)
+    }
+
+    /// Like [`Log::from_bytes`], but stops inflating the events once they
+    /// pass `limit` bytes.
+    pub fn from_bytes_with_limit(
+        bytes: &[u8],
+        limit: u64,
+    ) -> std::result::Result<Log, ReplayError> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(ReplayError::Malformed("missing replay log header"));
+        }
+        let version = u16::from_le_bytes(bytes[4..6].try_into().unwrap());
+        if version != VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let module = bytes[6..38].try_into().unwrap();
+        // One byte past the limit tells a log at the limit from a larger one.
+        let mut events = Vec::new();
+        DeflateDecoder::new(&bytes[HEADER_LEN..])
+            .take(limit.saturating_add(1))
+            .read_to_end(&mut events)
+            .map_err(|_| ReplayError::Malformed("corrupt compressed events"))?;
+        if events.len() as u64 > limit {
+            return Err(ReplayError::TooLarge { limit });
+        }
+
+        let mut reader = Reader { bytes: &events };
+        let events = (0..reader.len()?)
+            .map(|_| reader.event())
+            .collect::<std::result::Result<_, _>>()?;
+        if !reader.bytes.is_empty() {
+            return Err(ReplayError::Malformed("trailing bytes"));
+        }
+        Ok(Log { module, events })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::result::Result<Log, ReplayError> {
+        Log::from_bytes(&fs::read(path)?)
+    }
+}
+
+/// An instance whose host interactions are being recorded.
+pub struct Recording {
+    instance: Instance,
+    module: ModuleHash,
+    recorder: Arc<Mutex<Recorder>>,
+}
+
+impl Recording {
+    /// Compiles `input` with `engine` and instantiates it with `imports`,
+    /// recording every call to them from then on, including calls made by
+    /// the start function.
+    pub fn start(engine: &Engine, input: &[u8], mut imports: ImportObject) -> Result<Recording> {
+        let wasm = source::wasm_bytes(input).map_err(Error::Parse)?;
+        let instrumented = instrument(&wasm, Mode::Record)?;
+        let recorder = Arc::new(Mutex::new(Recorder::default()));
+        imports.register(HOOKS, hooks(recorder.clone()));
+        let instance = engine
+            .compile(&instrumented.wasm)?
+            .instantiate_with(&imports)?;
+        Ok(Recording {
+            instance,
+            module: module_hash(&wasm),
+            recorder,
+        })
+    }
+
+    /// Calls the export `name`, recording the call and its outcome.
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Vec<Value>> {
+        lock(&self.recorder).events.push(Event::Call {
+            export: name.to_string(),
+            args: args.to_vec(),
+        });
+        let outcome = self.instance.call(name, args);
+        let mut recorder = lock(&self.recorder);
+        match &outcome {
+            Ok(results) => recorder.events.push(Event::Return(results.clone())),
+            Err(Error::Trap(_)) => {
+                // An import that trapped never reached its `ret` hook.
+                let memory = memory_bytes(&self.instance);
+                while let Some(pending) = recorder.pending.pop() {
+                    let call = pending.finish(None, true, memory.as_deref());
+                    recorder.events.push(Event::Import(call));
+                }
+                recorder.events.push(Event::Trap);
+            }
+            // Rejected before running, so there is nothing to replay.
+            Err(_) => {
+                recorder.events.pop();
+            }
+        }
+        outcome
+    }
+
+    /// The recorded instance, e.g. to inspect its memory. Calls made
+    /// directly on it are not recorded.
+    pub fn instance(&self) -> &Instance {
+        &self.instance
+    }
+
+    /// Returns everything recorded so far.
+    pub fn log(&self) -> Log {
+        Log {
+            module: self.module,
+            events: lock(&self.recorder).events.clone(),
+        }
+    }
+}
+
+/// Runs `input` against `log`, which must have been recorded with the same
+/// module, without any host imports.
+///
+/// Every recorded exported call is made again in order. Imports return what
+/// they returned while recording and make the same memory writes. Returns
+/// the instance after the last call, or where the module first did
+/// something different from the recording.
+pub fn replay(
+    engine: &Engine,
+    input: &[u8],
+    log: &Log,
+) -> std::result::Result<Instance, ReplayError> {
+    let wasm = source::wasm_bytes(input).map_err(Error::Parse)?;
+    if module_hash(&wasm) != log.module {
+        return Err(ReplayError::ModuleMismatch);
+    }
+    let instrumented = instrument(&wasm, Mode::Replay)?;
+    let replayer = Arc::new(Mutex::new(Replayer {
+        events: log.events.clone(),
+        imports: instrumented.imports,
+        cursor: 0,
+        pending: Vec::new(),
+        divergence: None,
+    }));
+    let mut imports = ImportObject::new();
+    imports.register(HOOKS, hooks(replayer.clone()));
+    let instance = engine
+        .compile(&instrumented.wasm)?
+        .instantiate_with(&imports);
+    if let Some(err) = lock(&replayer).take_divergence() {
+        return Err(err);
+    }
+    let instance = instance?;
+
+    loop {
+        let (export, args) = {
+            let mut replayer = lock(&replayer);
+            match replayer.events.get(replayer.cursor) {
+                None => return Ok(instance),
+                Some(Event::Call { export, args }) => {
+                    let call = (export.clone(), args.clone());
+                    replayer.cursor += 1;
+                    call
+                }
+                Some(Event::Import(call)) => {
+                    let what = format!("expected a call to {}", replayer.describe_import(call));
+                    return Err(replayer.diverged(what));
+                }
+                Some(_) => return Err(ReplayError::Malformed("result without a call")),
+            }
+        };
+        let outcome = instance.call(&export, &args);
+        let mut replayer = lock(&replayer);
+        if let Some(err) = replayer.take_divergence() {
+            return Err(err);
+        }
+        let same = match (replayer.events.get(replayer.cursor), &outcome) {
+            (Some(Event::Return(expected)), Ok(results)) => same_values(expected, results),
+            (Some(Event::Trap), Err(Error::Trap(_))) => true,
+            _ => false,
+        };
+        if !same {
+            let expected = match replayer.events.get(replayer.cursor) {
+                Some(Event::Return(values)) => format!("returned {:?}", values),
+                Some(Event::Trap) => "trapped".to_string(),
+                Some(Event::Import(call)) => format!("called {}", replayer.describe_import(call)),
+                _ => "ended".to_string(),
+            };
+            let actual = match &outcome {
+                Ok(results) => format!("returned {:?}", results),
+                Err(err) => err.to_string(),
+            };
+            let what = format!("`{}` {}, but {} when recorded", export, actual, expected);
+            return Err(replayer.diverged(what));
+        }
+        replayer.cursor += 1;
+    }
+}
+
+/// What the hooks do when the instrumented module calls them.
+trait Hooks: Send + 'static {
+    fn enter(&mut self, ctx: &mut Ctx, index: u32);
+    fn arg(&mut self, value: Value);
+    /// Finishes the innermost import call, whose real result (or, when
+    /// replaying, a zero of the right type) is `result`, and returns the
+    /// result to hand to the module.
+    fn ret(
+        &mut self,
+        ctx: &mut Ctx,
+        result: Option<Value>,
+    ) -> std::result::Result<Option<Value>, String>;
+}
+
+#[derive(Default)]
+struct Recorder {
+    events: Vec<Event>,
+    pending: Vec<Pending>,
+}
+
+/// An import call that has been entered but has not returned yet.
+struct Pending {
+    index: u32,
+    args: Vec<Value>,
+    before: Option<Vec<u8>>,
+}
+
+impl Pending {
+    fn finish(self, result: Option<Value>, trapped: bool, after: Option<&[u8]>) -> ImportCall {
+        let before = self.before.unwrap_or_default();
+        let after = after.unwrap_or_default();
+        let grew = (after.len().saturating_sub(before.len()) / Pages(1).bytes().0) as u32;
+        ImportCall {
+            index: self.index,
+            args: self.args,
+            result,
+            trapped,
+            grew,
+            writes: diff(&before, after),
+        }
+    }
+}
+
+impl Hooks for Recorder {
+    fn enter(&mut self, ctx: &mut Ctx, index: u32) {
+        self.pending.push(Pending {
+            index,
+            args: Vec::new(),
+            before: ctx_memory(ctx).map(read_memory),
+        });
+    }
+
+    fn arg(&mut self, value: Value) {
+        if let Some(pending) = self.pending.last_mut() {
+            pending.args.push(value);
+        }
+    }
+
+    fn ret(
+        &mut self,
+        ctx: &mut Ctx,
+        result: Option<Value>,
+    ) -> std::result::Result<Option<Value>, String> {
+        let pending = self
+            .pending
+            .pop()
+            .ok_or("import returned without being entered")?;
+        let after = ctx_memory(ctx).map(read_memory);
+        let call = pending.finish(result, false, after.as_deref());
+        self.events.push(Event::Import(call));
+        Ok(result)
+    }
+}
+
+struct Replayer {
+    events: Vec<Event>,
+    /// `module.name` of each function import, for messages.
+    imports: Vec<String>,
+    cursor: usize,
+    pending: Vec<(u32, Vec<Value>)>,
+    divergence: Option<String>,
+}
+
+impl Replayer {
+    fn describe_import(&self, call: &ImportCall) -> String {
+        let name = self
+            .imports
+            .get(call.index as usize)
+            .map_or("an unknown import", String::as_str);
+        format!("`{}` with {:?}", name, call.args)
+    }
+
+    fn diverged(&self, what: String) -> ReplayError {
+        ReplayError::Diverged {
+            event: self.cursor,
+            what,
+        }
+    }
+
+    fn take_divergence(&mut self) -> Option<ReplayError> {
+        let what = self.divergence.take()?;
+        Some(self.diverged(what))
+    }
+
+    /// Consumes the recorded call matching the one the module just made, or
+    /// describes how they differ.
+    fn next_import(
+        &mut self,
+        index: u32,
+        args: Vec<Value>,
+        result: Option<Value>,
+    ) -> std::result::Result<ImportCall, String> {
+        let actual = ImportCall {
+            index,
+            args,
+            result: None,
+            trapped: false,
+            grew: 0,
+            writes: Vec::new(),
+        };
+        let call = match self.events.get(self.cursor) {
+            Some(Event::Import(call))
+                if call.index == index && same_values(&call.args, &actual.args) =>
+            {
+                call.clone()
+            }
+            Some(Event::Import(call)) => {
+                return Err(format!(
+                    "called {}, but {} when recorded",
+                    self.describe_import(&actual),
+                    self.describe_import(call)
+                ))
+            }
+            _ => {
+                return Err(format!(
+                    "called {}, which was not called when recorded",
+                    self.describe_import(&actual)
+                ))
+            }
+        };
+        if call.result.map(|value| value.ty()) != result.map(|value| value.ty()) && !call.trapped {
+            return Err(format!(
+                "{} returned {:?}, which does not match its type",
+                self.describe_import(&call),
+                call.result
+            ));
+        }
+        self.cursor += 1;
+        Ok(call)
+    }
+}
+
+/// Makes the memory changes `call` made when it was recorded.
+fn apply_writes(ctx: &mut Ctx, call: &ImportCall) -> std::result::Result<(), String> {
+    let memory = match ctx_memory(ctx) {
+        Some(memory) => memory,
+        None => return Ok(()),
+    };
+    if call.grew > 0 {
+        memory
+            .grow(Pages(call.grew))
+            .map_err(|err| format!("could not grow memory: {:?}", err))?;
+    }
+    let view = memory.view::<u8>();
+    for write in &call.writes {
+        let start = write.offset as usize;
+        let cells = view
+            .get(start..start + write.bytes.len())
+            .ok_or("recorded memory write is out of bounds")?;
+        for (cell, byte) in cells.iter().zip(&write.bytes) {
+            cell.set(*byte);
+        }
+    }
+    Ok(())
+}
+
+impl Hooks for Replayer {
+    fn enter(&mut self, _ctx: &mut Ctx, index: u32) {
+        self.pending.push((index, Vec::new()));
+    }
+
+    fn arg(&mut self, value: Value) {
+        if let Some((_, args)) = self.pending.last_mut() {
+            args.push(value);
+        }
+    }
+
+    fn ret(
+        &mut self,
+        ctx: &mut Ctx,
+        result: Option<Value>,
+    ) -> std::result::Result<Option<Value>, String> {
+        let (index, args) = self
+            .pending
+            .pop()
+            .ok_or("import returned without being entered")?;
+        let call = self
+            .next_import(index, args, result)
+            .and_then(|call| apply_writes(ctx, &call).map(|()| call))
+            .map_err(|what| self.divergence.get_or_insert(what).clone())?;
+        if call.trapped {
+            // Not a divergence: the host trapped when recorded, too.
+            return Err("host import trapped".to_string());
+        }
+        Ok(call.result)
+    }
+}
+
+fn hooks<H: Hooks>(state: Arc<Mutex<H>>) -> Namespace {
+    let mut namespace = Namespace::new();
+    let hooks = state.clone();
+    namespace.insert(
+        HOOK_NAMES[ENTER as usize],
+        func!(move |ctx: &mut Ctx, index: i32| lock(&hooks).enter(ctx, index as u32)),
+    );
+    let hooks = state.clone();
+    namespace.insert(
+        "arg_i32",
+        func!(move |x: i32| lock(&hooks).arg(Value::I32(x))),
+    );
+    let hooks = state.clone();
+    namespace.insert(
+        "arg_i64",
+        func!(move |x: i64| lock(&hooks).arg(Value::I64(x))),
+    );
+    let hooks = state.clone();
+    namespace.insert(
+        "arg_f32",
+        func!(move |x: f32| lock(&hooks).arg(Value::F32(x))),
+    );
+    let hooks = state.clone();
+    namespace.insert(
+        "arg_f64",
+        func!(move |x: f64| lock(&hooks).arg(Value::F64(x))),
+    );
+    let hooks = state.clone();
+    namespace.insert(
+        HOOK_NAMES[RET as usize],
+        func!(move |ctx: &mut Ctx| -> std::result::Result<(), String> {
+            match lock(&hooks).ret(ctx, None)? {
+                None => Ok(()),
+                Some(value) => Err(format!("unexpected result {:?}", value)),
+            }
+        }),
+    );
+    macro_rules! ret {
+        ($name:literal, $ty:ty, $variant:ident) => {
+            let hooks = state.clone();
+            namespace.insert(
+                $name,
+                func!(
+                    move |ctx: &mut Ctx, x: $ty| -> std::result::Result<$ty, String> {
+                        match lock(&hooks).ret(ctx, Some(Value::$variant(x)))? {
+                            Some(Value::$variant(x)) => Ok(x),
+                            other => Err(format!("unexpected result {:?}", other)),
+                        }
+                    }
+                ),
+            );
+        };
+    }
+    ret!("ret_i32", i32, I32);
+    ret!("ret_i64", i64, I64);
+    ret!("ret_f32", f32, F32);
+    ret!("ret_f64", f64, F64);
+    namespace
+}
+
+fn lock<T>(state: &Mutex<T>) -> MutexGuard<'_, T> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn read_memory(memory: &Memory) -> Vec<u8> {
+    memory.view::<u8>().iter().map(|cell| cell.get()).collect()
+}
+
+fn memory_bytes(instance: &Instance) -> Option<Vec<u8>> {
+    let mut bytes = vec![0; instance.memory_len().ok()?];
+    instance.read_memory(0, &mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// Returns the writes that turn `before` into `after`, which may be longer.
+fn diff(before: &[u8], after: &[u8]) -> Vec<MemoryWrite> {
+    let changed = |i: usize| before.get(i).copied().unwrap_or(0) != after[i];
+    let mut writes = Vec::new();
+    let mut i = 0;
+    while i < after.len() {
+        if !changed(i) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let mut end = i + 1;
+        i = end;
+        while i < after.len() && i - end < MIN_UNCHANGED_GAP {
+            if changed(i) {
+                end = i + 1;
+            }
+            i += 1;
+        }
+        writes.push(MemoryWrite {
+            offset: start as u32,
+            bytes: after[start..end].to_vec(),
+        });
+    }
+    writes
+}
+
+fn same_values(a: &[Value], b: &[Value]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| a.ty() == b.ty() && a.to_bits() == b.to_bits())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    /// Keep the function imports and log calls to them.
+    Record,
+    /// Drop the function imports and answer calls from the log.
+    Replay,
+}
+
+struct Instrumented {
+    wasm: Vec<u8>,
+    /// `module.name` of each function import.
+    imports: Vec<String>,
+}
+
+/// Rewrites `wasm` so that every function import is called through a shim
+/// that reports to the hooks.
+///
+/// In the rewritten module the hooks are imported after the original
+/// function imports, which are dropped when replaying, and the shims are
+/// defined after the original functions, taking the imports' place in every
+/// reference.
+fn instrument(wasm: &[u8], mode: Mode) -> Result<Instrumented> {
+    let mut types = Vec::new();
+    let mut imports = Vec::new();
+    let mut defined = 0;
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload.map_err(parse_error)? {
+            Payload::TypeSection(reader) => {
+                for ty in reader.into_iter_err_on_gc_types() {
+                    types.push(ty.map_err(parse_error)?);
+                }
+            }
+            Payload::ImportSection(reader) => {
+                for import in reader.into_imports() {
+                    let import = import.map_err(parse_error)?;
+                    if let TypeRef::Func(ty) = import.ty {
+                        imports.push((format!("{}.{}", import.module, import.name), ty));
+                    }
+                }
+            }
+            Payload::FunctionSection(reader) => defined = reader.count(),
+            _ => {}
+        }
+    }
+    if imports.is_empty() {
+        return Ok(Instrumented {
+            wasm: wasm.to_vec(),
+            imports: Vec::new(),
+        });
+    }
+    let mut shims = Instrument {
+        mode,
+        imports: imports.iter().map(|(_, ty)| *ty).collect(),
+        types: types.len() as u32,
+        defined,
+    };
+    let mut bodies = Vec::new();
+    for (index, (name, ty)) in imports.iter().enumerate() {
+        let ty = types
+            .get(*ty as usize)
+            .ok_or_else(|| Error::Compile(format!("import `{}` has no type", name)))?;
+        bodies.push(shims.shim(index as u32, name, ty)?);
+    }
+
+    let mut module = wasm_encoder::Module::new();
+    let mut wrote_functions = false;
+    let mut wrote_code = false;
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload.map_err(parse_error)?;
+        let needs_functions = !matches!(
+            payload,
+            Payload::Version { .. }
+                | Payload::TypeSection(_)
+                | Payload::ImportSection(_)
+                | Payload::FunctionSection(_)
+                | Payload::CustomSection(_)
+        );
+        if needs_functions && !wrote_functions {
+            module.section(&shims.function_section(None)?);
+            wrote_functions = true;
+        }
+        if matches!(payload, Payload::DataSection(_) | Payload::End(_)) && !wrote_code {
+            module.section(&shims.code_section(None, &bodies)?);
+            wrote_code = true;
+        }
+
+        match payload {
+            Payload::TypeSection(reader) => {
+                let mut section = TypeSection::new();
+                shims
+                    .parse_type_section(&mut section, reader)
+                    .map_err(reencode_error)?;
+                for hook in 0..HOOK_NAMES.len() {
+                    let (params, results) = hook_type(hook as u32);
+                    section.ty().function(params, results);
+                }
+                module.section(&section);
+            }
+            Payload::ImportSection(reader) => {
+                let mut section = ImportSection::new();
+                for import in reader.into_imports() {
+                    let import = import.map_err(parse_error)?;
+                    if mode == Mode::Replay && matches!(import.ty, TypeRef::Func(_)) {
+                        continue;
+                    }
+                    let ty = shims.entity_type(import.ty).map_err(reencode_error)?;
+                    section.import(import.module, import.name, ty);
+                }
+                for (hook, name) in HOOK_NAMES.iter().enumerate() {
+                    section.import(HOOKS, name, EntityType::Function(shims.types + hook as u32));
+                }
+                module.section(&section);
+            }
+            Payload::FunctionSection(reader) => {
+                module.section(&shims.function_section(Some(reader))?);
+                wrote_functions = true;
+            }
+            Payload::GlobalSection(reader) => {
+                let mut section = GlobalSection::new();
+                shims
+                    .parse_global_section(&mut section, reader)
+                    .map_err(reencode_error)?;
+                module.section(&section);
+            }
+            Payload::ExportSection(reader) => {
+                let mut section = ExportSection::new();
+                shims
+                    .parse_export_section(&mut section, reader)
+                    .map_err(reencode_error)?;
+                module.section(&section);
+            }
+            Payload::StartSection { func, .. } => {
+                module.section(&StartSection {
+                    function_index: shims.remap(func),
+                });
+            }
+            Payload::ElementSection(reader) => {
+                let mut section = wasm_encoder::ElementSection::new();
+                shims
+                    .parse_element_section(&mut section, reader)
+                    .map_err(reencode_error)?;
+                module.section(&section);
+            }
+            Payload::CodeSectionStart { range, .. } => {
+                let reader =
+                    CodeSectionReader::new(BinaryReader::new(&wasm[range.clone()], range.start))
+                        .map_err(parse_error)?;
+                module.section(&shims.code_section(Some(reader), &bodies)?);
+                wrote_code = true;
+            }
+            Payload::CodeSectionEntry(_) => {}
+            // Function indices in the name section no longer match.
+            Payload::CustomSection(reader) if reader.name() == "name" => {}
+            payload => {
+                if let Some((id, range)) = payload.as_section() {
+                    module.section(&RawSection {
+                        id,
+                        data: &wasm[range],
+                    });
+                }
+            }
+        }
+    }
+    Ok(Instrumented {
+        wasm: module.finish(),
+        imports: imports.into_iter().map(|(name, _)| name).collect(),
+    })
+}
+
+struct Instrument {
+    mode: Mode,
+    /// Type index of each function import.
+    imports: Vec<u32>,
+    /// Number of types in the original module.
+    types: u32,
+    /// Number of functions defined in the original module.
+    defined: u32,
+}
+
+impl Instrument {
+    fn hook(&self, hook: u32) -> u32 {
+        match self.mode {
+            Mode::Record => self.imports.len() as u32 + hook,
+            Mode::Replay => hook,
+        }
+    }
+
+    /// Maps a function index in the original module to the rewritten one.
+    fn remap(&self, func: u32) -> u32 {
+        let imports = self.imports.len() as u32;
+        let defined = self.hook(HOOK_NAMES.len() as u32);
+        if func < imports {
+            defined + self.defined + func
+        } else {
+            defined + func - imports
+        }
+    }
+
+    fn shim(&self, index: u32, name: &str, ty: &FuncType) -> Result<Function> {
+        let unsupported = || {
+            Error::Compile(format!(
+                "import `{}` has a signature that cannot be recorded",
+                name
+            ))
+        };
+        let mut shim = Function::new([]);
+        shim.instruction(&Instruction::I32Const(index as i32));
+        shim.instruction(&Instruction::Call(self.hook(ENTER)));
+        for (local, &param) in ty.params().iter().enumerate() {
+            let offset = type_offset(param).ok_or_else(unsupported)?;
+            shim.instruction(&Instruction::LocalGet(local as u32));
+            shim.instruction(&Instruction::Call(self.hook(ARG + offset)));
+        }
+        match self.mode {
+            Mode::Record => {
+                for local in 0..ty.params().len() {
+                    shim.instruction(&Instruction::LocalGet(local as u32));
+                }
+                shim.instruction(&Instruction::Call(index));
+            }
+            Mode::Replay => {
+                // A placeholder for the recorded result.
+                for &result in ty.results() {
+                    shim.instruction(&match result {
+                        wasmparser::ValType::I32 => Instruction::I32Const(0),
+                        wasmparser::ValType::I64 => Instruction::I64Const(0),
+                        wasmparser::ValType::F32 => Instruction::F32Const(0.0.into()),
+                        wasmparser::ValType::F64 => Instruction::F64Const(0.0.into()),
+                        _ => return Err(unsupported()),
+                    });
+                }
+            }
+        }
+        let ret = match ty.results() {
+            [] => RET,
+            [result] => RET_VALUE + type_offset(*result).ok_or_else(unsupported)?,
+            _ => return Err(unsupported()),
+        };
+        shim.instruction(&Instruction::Call(self.hook(ret)));
+        shim.instruction(&Instruction::End);
+        Ok(shim)
+    }
+
+    fn function_section(
+        &mut self,
+        reader: Option<wasmparser::FunctionSectionReader>,
+    ) -> Result<FunctionSection> {
+        let mut section = FunctionSection::new();
+        if let Some(reader) = reader {
+            self.parse_function_section(&mut section, reader)
+                .map_err(reencode_error)?;
+        }
+        for &ty in &self.imports {
+            section.function(ty);
+        }
+        Ok(section)
+    }
+
+    fn code_section(
+        &mut self,
+        reader: Option<CodeSectionReader>,
+        shims: &[Function],
+    ) -> Result<CodeSection> {
+        let mut section = CodeSection::new();
+        if let Some(reader) = reader {
+            self.parse_code_section(&mut section, reader)
+                .map_err(reencode_error)?;
+        }
+        for shim in shims {
+            section.function(shim);
+        }
+        Ok(section)
+    }
+}
+
+impl Reencode for Instrument {
+    type Error = Infallible;
+
+    fn function_index(&mut self, func: u32) -> std::result::Result<u32, reencode::Error> {
+        Ok(self.remap(func))
+    }
+}
+
+fn hook_type(hook: u32) -> (Vec<wasm_encoder::ValType>, Vec<wasm_encoder::ValType>) {
+    use wasm_encoder::ValType::*;
+    const TYPES: [wasm_encoder::ValType; 4] = [I32, I64, F32, F64];
+    match hook {
+        ENTER => (vec![I32], vec![]),
+        RET => (vec![], vec![]),
+        hook if hook < RET => (vec![TYPES[(hook - ARG) as usize]], vec![]),
+        hook => {
+            let ty = TYPES[(hook - RET_VALUE) as usize];
+            (vec![ty], vec![ty])
+        }
+    }
+}
+
+fn type_offset(ty: wasmparser::ValType) -> Option<u32> {
+    match ty {
+        wasmparser::ValType::I32 => Some(0),
+        wasmparser::ValType::I64 => Some(1),
+        wasmparser::ValType::F32 => Some(2),
+        wasmparser::ValType::F64 => Some(3),
+        _ => None,
+    }
+}
+
+fn parse_error(err: wasmparser::BinaryReaderError) -> Error {
+    Error::Compile(err.to_string())
+}
+
+fn reencode_error(err: reencode::Error) -> Error {
+    Error::Compile(err.to_string())
+}
+
+const EVENT_CALL: u8 = 0;
+const EVENT_RETURN: u8 = 1;
+const EVENT_TRAP: u8 = 2;
+const EVENT_IMPORT: u8 = 3;
+
+fn write_event(out: &mut Vec<u8>, event: &Event) {
+    match event {
+        Event::Call { export, args } => {
+            out.push(EVENT_CALL);
+            write_len(out, export.len());
+            out.extend_from_slice(export.as_bytes());
+            write_values(out, args);
+        }
+        Event::Return(values) => {
+            out.push(EVENT_RETURN);
+            write_values(out, values);
+        }
+        Event::Trap => out.push(EVENT_TRAP),
+        Event::Import(call) => {
+            out.push(EVENT_IMPORT);
+            write_leb(out, call.index.into());
+            write_values(out, &call.args);
+            out.push(match (call.trapped, call.result) {
+                (true, _) => 2,
+                (false, None) => 0,
+                (false, Some(_)) => 1,
+            });
+            if let (false, Some(result)) = (call.trapped, &call.result) {
+                write_value(out, result);
+            }
+            write_leb(out, call.grew.into());
+            write_len(out, call.writes.len());
+            for write in &call.writes {
+                write_leb(out, write.offset.into());
+                write_len(out, write.bytes.len());
+                out.extend_from_slice(&write.bytes);
+            }
+        }
+    }
+}
+
+fn write_values(out: &mut Vec<u8>, values: &[Value]) {
+    write_len(out, values.len());
+    for value in values {
+        write_value(out, value);
+    }
+}
+
+/// Writes a type tag followed by the value's bits, using only as many bytes
+/// as its type needs.
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    let (tag, width) = match value.ty() {
+        ValueType::I32 => (0, 4),
+        ValueType::I64 => (1, 8),
+        ValueType::F32 => (2, 4),
+        ValueType::F64 => (3, 8),
+        ValueType::V128 => (4, 16),
+    };
+    out.push(tag);
+    out.extend_from_slice(&value.to_bits().to_le_bytes()[..width]);
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    write_leb(out, len as u64);
+}
+
+fn write_leb(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> std::result::Result<&'a [u8], ReplayError> {
+        if self.bytes.len() < len {
+            return Err(ReplayError::Malformed("unexpected end of events"));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> std::result::Result<u8, ReplayError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn leb(&mut self) -> std::result::Result<u64, ReplayError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ReplayError::Malformed("integer too long"))
+    }
+
+    fn u32(&mut self) -> std::result::Result<u32, ReplayError> {
+        self.leb()?
+            .try_into()
+            .map_err(|_| ReplayError::Malformed("integer out of range"))
+    }
+
+    fn len(&mut self) -> std::result::Result<usize, ReplayError> {
+        let len = self.leb()? as usize;
+        // Every counted item takes at least a byte, so a longer count is
+        // corrupt and must not be used to preallocate.
+        if len > self.bytes.len() {
+            return Err(ReplayError::Malformed("count exceeds remaining bytes"));
+        }
+        Ok(len)
+    }
+
+    fn value(&mut self) -> std::result::Result<Value, ReplayError> {
+        let (ty, width) = match self.byte()? {
+            0 => (ValueType::I32, 4),
+            1 => (ValueType::I64, 8),
+            2 => (ValueType::F32, 4),
+            3 => (ValueType::F64, 8),
+            4 => (ValueType::V128, 16),
+            _ => return Err(ReplayError::Malformed("unknown value type")),
+        };
+        let mut bits = [0; 16];
+        bits[..width].copy_from_slice(self.take(width)?);
+        Ok(Value::from_bits(ty, u128::from_le_bytes(bits)))
+    }
+
+    fn values(&mut self) -> std::result::Result<Vec<Value>, ReplayError> {
+        (0..self.len()?).map(|_| self.value()).collect()
+    }
+
+    fn event(&mut self) -> std::result::Result<Event, ReplayError> {
+        Ok(match self.byte()? {
+            EVENT_CALL => {
+                let len = self.len()?;
+                let export = String::from_utf8(self.take(len)?.to_vec())
+                    .map_err(|_| ReplayError::Malformed("export name is not UTF-8"))?;
+                Event::Call {
+                    export,
+                    args: self.values()?,
+                }
+            }
+            EVENT_RETURN => Event::Return(self.values()?),
+            EVENT_TRAP => Event::Trap,
+            EVENT_IMPORT => {
+                let index = self.u32()?;
+                let args = self.values()?;
+                let (result, trapped) = match self.byte()? {
+                    0 => (None, false),
+                    1 => (Some(self.value()?), false),
+                    2 => (None, true),
+                    _ => return Err(ReplayError::Malformed("unknown import outcome")),
+                };
+                let grew = self.u32()?;
+                let writes = (0..self.len()?)
+                    .map(|_| {
+                        let offset = self.u32()?;
+                        let len = self.len()?;
+                        Ok(MemoryWrite {
+                            offset,
+                            bytes: self.take(len)?.to_vec(),
+                        })
+                    })
+                    .collect::<std::result::Result<_, ReplayError>>()?;
+                Event::Import(ImportCall {
+                    index,
+                    args,
+                    result,
+                    trapped,
+                    grew,
+                    writes,
+                })
+            }
+            _ => return Err(ReplayError::Malformed("unknown event")),
+        })
+    }
+}
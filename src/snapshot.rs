@@ -0,0 +1,471 @@
+use crate::{Instance, Value, ValueType};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::ptr;
+use wasmer_runtime::types::{FuncIndex, LocalFuncIndex, LocalGlobalIndex, LocalMemoryIndex};
+use wasmer_runtime::units::{Bytes, Pages};
+use wasmer_runtime::Memory;
+use wasmer_runtime_core::structures::TypedIndex;
+use wasmer_runtime_core::vm::{self, Anyfunc, Ctx, FuncCtx, ImportedFunc};
+
+const MAGIC: &[u8; 4] = b"WSNP";
+const VERSION: u8 = 2;
+
+/// Errors produced while encoding, decoding or restoring a [`Snapshot`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotError {
+    /// The blob is not a snapshot, or is truncated.
+    Malformed(&'static str),
+    /// The blob was written by a newer, unknown snapshot format.
+    UnsupportedVersion(u8),
+    /// The snapshot was taken from an instance of a different module.
+    Mismatch(String),
+    /// The target instance's memory could not be grown to the snapshot's size.
+    MemoryGrow(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Malformed(what) => write!(f, "malformed snapshot: {}", what),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::Mismatch(what) => {
+                write!(f, "snapshot does not match instance: {}", what)
+            }
+            SnapshotError::MemoryGrow(err) => write!(f, "could not grow memory: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// The state of an instance's locally defined memories, globals and tables.
+///
+/// Imported items are owned by another instance and are not captured.
+/// Table elements are recorded as function indices, so that restoring them
+/// points them at the target instance's own functions and imports. Tables
+/// are not resized on restore.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// Contents of each local memory, by local memory index.
+    pub memories: Vec<Vec<u8>>,
+    /// Value of each mutable local global, with its local global index.
+    pub globals: Vec<(u32, Value)>,
+    /// Elements of each local table, by local table index.
+    pub tables: Vec<Vec<TableElement>>,
+}
+
+/// An element of a captured table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableElement {
+    Null,
+    /// The function at this index of the instance's function index space,
+    /// imports first.
+    Function(u32),
+    /// A function of another instance, stored by an element segment of a
+    /// module importing the table. It cannot be restored.
+    Foreign,
+}
+
+impl Snapshot {
+    /// Captures the current state of `instance`.
+    pub fn capture(instance: &Instance) -> Snapshot {
+        let instance = instance.as_wasmer();
+        let info = &instance.module.info;
+        let ctx = instance.context();
+        let imported_memories = info.imported_memories.len();
+
+        let memories = (0..info.memories.len())
+            .map(|local| {
+                let memory = ctx.memory((imported_memories + local) as u32);
+                memory.view::<u8>().iter().map(|cell| cell.get()).collect()
+            })
+            .collect();
+
+        let globals = info
+            .globals
+            .iter()
+            .filter(|(_, global)| global.desc.mutable)
+            .map(|(index, global)| {
+                // Safety: `globals` holds a pointer to each of the
+                // module's local globals, and `index` is one of them.
+                let data = unsafe { (**ctx.internal.globals.add(index.index())).data };
+                (
+                    index.index() as u32,
+                    Value::from_bits(ValueType::from_wasmer(global.desc.ty), data),
+                )
+            })
+            .collect();
+
+        let mut functions = None;
+        let tables = (0..info.tables.len())
+            .map(|local| {
+                // Safety: `tables` holds a pointer to each of the module's
+                // local tables, whose `base` points to `count` elements.
+                let elements = unsafe {
+                    let table = &**ctx.internal.tables.add(local);
+                    std::slice::from_raw_parts(table.base as *const Anyfunc, table.count)
+                };
+                let functions = functions.get_or_insert_with(|| function_indices(instance));
+                elements
+                    .iter()
+                    .map(|element| {
+                        if element.func.is_null() {
+                            return TableElement::Null;
+                        }
+                        functions
+                            .get(&(element.func, element.ctx as *const Ctx))
+                            .map_or(TableElement::Foreign, |&index| {
+                                TableElement::Function(index)
+                            })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Snapshot {
+            memories,
+            globals,
+            tables,
+        }
+    }
+
+    /// Writes this snapshot back into `instance`, which must be an instance
+    /// of the module the snapshot was captured from.
+    ///
+    /// Memories smaller than the snapshot are grown to match it; a memory
+    /// that has already grown past the snapshot's size cannot be shrunk and
+    /// is rejected.
+    pub fn restore(&self, instance: &mut Instance) -> Result<(), SnapshotError> {
+        self.check_layout(instance)?;
+
+        let instance = instance.as_wasmer_mut();
+        let imported_memories = instance.module.info.imported_memories.len();
+        let ctx = instance.context_mut();
+        for (local, bytes) in self.memories.iter().enumerate() {
+            let memory = ctx.memory((imported_memories + local) as u32);
+            resize_memory(memory, bytes.len())?;
+            let view = memory.view::<u8>();
+            // Safety: `resize_memory` made the view exactly `bytes.len()` long,
+            // and `Cell<u8>` has the same layout as `u8`.
+            unsafe {
+                ptr::copy_nonoverlapping(bytes.as_ptr(), view.as_ptr() as *mut u8, bytes.len());
+            }
+        }
+
+        for (index, value) in &self.globals {
+            // Safety: `check_layout` made sure the instance has a local
+            // global at `index`.
+            unsafe {
+                (**ctx.internal.globals.add(*index as usize)).data = value.to_bits();
+            }
+        }
+
+        for (local, elements) in self.tables.iter().enumerate() {
+            for (i, element) in elements.iter().enumerate() {
+                let element = match *element {
+                    TableElement::Function(index) => function_ref(instance, index),
+                    _ => Anyfunc::null(),
+                };
+                // Safety: `check_layout` made sure the table has as many
+                // elements as the snapshot, and that every function index
+                // is in range.
+                unsafe {
+                    let table = &**instance.context().internal.tables.add(local);
+                    *(table.base as *mut Anyfunc).add(i) = element;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes the snapshot into a self-describing byte blob.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(
+            16 + self.memories.iter().map(Vec::len).sum::<usize>() + self.globals.len() * 21,
+        );
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+
+        write_u32(&mut out, self.memories.len() as u32);
+        for memory in &self.memories {
+            write_u32(&mut out, memory.len() as u32);
+            out.extend_from_slice(memory);
+        }
+
+        write_u32(&mut out, self.globals.len() as u32);
+        for (index, value) in &self.globals {
+            write_u32(&mut out, *index);
+            out.push(type_tag(value));
+            out.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+
+        write_u32(&mut out, self.tables.len() as u32);
+        for elements in &self.tables {
+            write_u32(&mut out, elements.len() as u32);
+            for element in elements {
+                match element {
+                    TableElement::Null => out.push(0),
+                    TableElement::Function(index) => {
+                        out.push(1);
+                        write_u32(&mut out, *index);
+                    }
+                    TableElement::Foreign => out.push(2),
+                }
+            }
+        }
+        out
+    }
+
+    /// Decodes a blob produced by [`Snapshot::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::Malformed("missing snapshot header"));
+        }
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let memories = (0..reader.u32()?)
+            .map(|_| {
+                let len = reader.u32()? as usize;
+                Ok(reader.take(len)?.to_vec())
+            })
+            .collect::<Result<_, SnapshotError>>()?;
+
+        let globals = (0..reader.u32()?)
+            .map(|_| {
+                let index = reader.u32()?;
+                let tag = reader.take(1)?[0];
+                let data = u128::from_le_bytes(reader.take(16)?.try_into().unwrap());
+                Ok((index, value_from_tag(tag, data)?))
+            })
+            .collect::<Result<_, SnapshotError>>()?;
+
+        let tables = (0..reader.u32()?)
+            .map(|_| {
+                (0..reader.u32()?)
+                    .map(|_| match reader.take(1)?[0] {
+                        0 => Ok(TableElement::Null),
+                        1 => Ok(TableElement::Function(reader.u32()?)),
+                        2 => Ok(TableElement::Foreign),
+                        _ => Err(SnapshotError::Malformed("unknown table element")),
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, SnapshotError>>()?;
+
+        if !reader.bytes.is_empty() {
+            return Err(SnapshotError::Malformed("trailing bytes"));
+        }
+        Ok(Snapshot {
+            memories,
+            globals,
+            tables,
+        })
+    }
+
+    fn check_layout(&self, instance: &Instance) -> Result<(), SnapshotError> {
+        let instance = instance.as_wasmer();
+        let info = &instance.module.info;
+        if self.memories.len() != info.memories.len() {
+            return Err(SnapshotError::Mismatch(format!(
+                "snapshot has {} memories, instance has {}",
+                self.memories.len(),
+                info.memories.len()
+            )));
+        }
+        for (local, bytes) in self.memories.iter().enumerate() {
+            let desc = &info.memories[LocalMemoryIndex::new(local)];
+            let pages: Pages = Bytes(bytes.len()).into();
+            if bytes.len() % Pages(1).bytes().0 != 0
+                || pages < desc.minimum
+                || desc.maximum.is_some_and(|max| pages > max)
+            {
+                return Err(SnapshotError::Mismatch(format!(
+                    "memory {} size {} does not fit its limits",
+                    local,
+                    bytes.len()
+                )));
+            }
+        }
+
+        for (index, value) in &self.globals {
+            let global = info
+                .globals
+                .get(LocalGlobalIndex::new(*index as usize))
+                .ok_or_else(|| SnapshotError::Mismatch(format!("no global {}", index)))?;
+            if !global.desc.mutable || ValueType::from_wasmer(global.desc.ty) != value.ty() {
+                return Err(SnapshotError::Mismatch(format!(
+                    "global {} has a different type",
+                    index
+                )));
+            }
+        }
+
+        let ctx = instance.context();
+        if self.tables.len() != info.tables.len() {
+            return Err(SnapshotError::Mismatch(format!(
+                "snapshot has {} tables, instance has {}",
+                self.tables.len(),
+                info.tables.len()
+            )));
+        }
+        let functions = info.func_assoc.len();
+        for (local, elements) in self.tables.iter().enumerate() {
+            // Safety: the instance has as many local tables as the
+            // snapshot, and `tables` holds a pointer to each.
+            let current = unsafe { (**ctx.internal.tables.add(local)).count };
+            if current != elements.len() {
+                return Err(SnapshotError::Mismatch(format!(
+                    "table {} has {} elements, snapshot has {}",
+                    local,
+                    current,
+                    elements.len()
+                )));
+            }
+            for (i, element) in elements.iter().enumerate() {
+                match *element {
+                    TableElement::Function(index) if index as usize >= functions => {
+                        return Err(SnapshotError::Mismatch(format!(
+                            "table {} element {} refers to function {} of {}",
+                            local, i, index, functions
+                        )));
+                    }
+                    TableElement::Foreign => {
+                        return Err(SnapshotError::Mismatch(format!(
+                            "table {} element {} is a function of another instance",
+                            local, i
+                        )));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Maps the code and context pointers of each of the instance's functions,
+/// as table elements hold them, to its function index.
+fn function_indices(
+    instance: &wasmer_runtime::Instance,
+) -> HashMap<(*const vm::Func, *const Ctx), u32> {
+    (0..instance.module.info.func_assoc.len() as u32)
+        .map(|index| {
+            let element = function_ref(instance, index);
+            ((element.func, element.ctx as *const Ctx), index)
+        })
+        .collect()
+}
+
+/// Builds the table element for function `index` of `instance`, the way
+/// wasmer does for element segments.
+fn function_ref(instance: &wasmer_runtime::Instance, index: u32) -> Anyfunc {
+    let info = &instance.module.info;
+    let ctx = instance.context();
+    let index = FuncIndex::new(index as usize);
+    let imported = info.imported_functions.len();
+    let (func, vmctx) = if index.index() < imported {
+        // Safety: `imported_funcs` points to an `ImportedFunc` for each
+        // imported function. Their fields are private, so they are read at
+        // the offsets wasmer publishes for generated code, as is the
+        // `vmctx` of the `FuncCtx` they point to.
+        unsafe {
+            let import = (ctx.internal.imported_funcs.add(index.index())) as *const u8;
+            let func =
+                *(import.add(ImportedFunc::offset_func() as usize) as *const *const vm::Func);
+            let func_ctx =
+                *(import.add(ImportedFunc::offset_func_ctx() as usize) as *const *const u8);
+            let vmctx = *(func_ctx.add(FuncCtx::offset_vmctx() as usize) as *const *mut Ctx);
+            (func, vmctx)
+        }
+    } else {
+        let local = LocalFuncIndex::new(index.index() - imported);
+        let func = instance
+            .module
+            .runnable_module
+            .get_func(info, local)
+            .map_or(ptr::null(), |func| func.as_ptr() as *const vm::Func);
+        (func, ctx as *const Ctx as *mut Ctx)
+    };
+    // Safety: `dynamic_sigindices` holds the runtime id of each of the
+    // module's signatures.
+    let sig_id = unsafe {
+        *ctx.internal
+            .dynamic_sigindices
+            .add(info.func_assoc[index].index())
+    };
+    Anyfunc {
+        func,
+        ctx: vmctx,
+        sig_id,
+    }
+}
+
+fn resize_memory(memory: &Memory, len: usize) -> Result<(), SnapshotError> {
+    let current = memory.size().bytes().0;
+    if current > len {
+        return Err(SnapshotError::Mismatch(format!(
+            "memory is {} bytes, larger than the snapshot's {}",
+            current, len
+        )));
+    }
+    let delta = Pages::from(Bytes(len - current));
+    if delta.0 > 0 {
+        memory
+            .grow(delta)
+            .map_err(|err| SnapshotError::MemoryGrow(format!("{:?}", err)))?;
+    }
+    Ok(())
+}
+
+fn type_tag(value: &Value) -> u8 {
+    match value {
+        Value::I32(_) => 0,
+        Value::I64(_) => 1,
+        Value::F32(_) => 2,
+        Value::F64(_) => 3,
+        Value::V128(_) => 4,
+    }
+}
+
+fn value_from_tag(tag: u8, data: u128) -> Result<Value, SnapshotError> {
+    let ty = match tag {
+        0 => ValueType::I32,
+        1 => ValueType::I64,
+        2 => ValueType::F32,
+        3 => ValueType::F64,
+        4 => ValueType::V128,
+        _ => return Err(SnapshotError::Malformed("unknown global type")),
+    };
+    Ok(Value::from_bits(ty, data))
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < len {
+            return Err(SnapshotError::Malformed("unexpected end of snapshot"));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
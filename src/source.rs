@@ -0,0 +1,65 @@
+use std::borrow::Cow;
+use std::fmt;
+use wast::parser::{self, ParseBuffer};
+use wast::Wat;
+
+const WASM_MAGIC: &[u8; 4] = b"\0asm";
+
+/// A WebAssembly text format error, with a 1-based line and column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Returns true if `input` is a WebAssembly binary rather than text.
+pub fn is_binary(input: &[u8]) -> bool {
+    input.starts_with(WASM_MAGIC)
+}
+
+/// Returns the WebAssembly binary for `input`, which may be either a binary
+/// module (returned as is) or a module in the text format.
+pub fn wasm_bytes(input: &[u8]) -> Result<Cow<'_, [u8]>, ParseError> {
+    if is_binary(input) {
+        return Ok(Cow::Borrowed(input));
+    }
+    let text = std::str::from_utf8(input).map_err(|err| {
+        let prefix = &input[..err.valid_up_to()];
+        let line = prefix.iter().filter(|&&byte| byte == b'\n').count() + 1;
+        let column = prefix
+            .iter()
+            .rev()
+            .take_while(|&&byte| byte != b'\n')
+            .count()
+            + 1;
+        ParseError {
+            message: "input is neither a wasm binary nor UTF-8 text".to_string(),
+            line,
+            column,
+        }
+    })?;
+    parse_text(text).map(Cow::Owned)
+}
+
+fn parse_text(text: &str) -> Result<Vec<u8>, ParseError> {
+    let to_parse_error = |err: wast::Error| {
+        let (line, column) = err.span().linecol_in(text);
+        ParseError {
+            message: err.message(),
+            line: line + 1,
+            column: column + 1,
+        }
+    };
+    let buffer = ParseBuffer::new(text).map_err(to_parse_error)?;
+    let mut wat = parser::parse::<Wat>(&buffer).map_err(to_parse_error)?;
+    wat.encode().map_err(to_parse_error)
+}
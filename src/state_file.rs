@@ -0,0 +1,232 @@
+use crate::snapshot::{Snapshot, SnapshotError};
+use crate::Instance;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"WSTF";
+const VERSION: u16 = 2;
+const FLAG_COMPRESSED: u8 = 1;
+const HEADER_LEN: usize = 4 + 2 + 1 + 32 + 8 + 8;
+
+/// The largest snapshot [`decode`] and [`load`] accept: a memory of the
+/// largest size a snapshot can record, plus 64 MiB for globals and table
+/// elements. Use [`decode_with_limit`] or [`load_with_limit`] for a tighter
+/// bound on files from untrusted sources.
+pub const MAX_SNAPSHOT_LEN: u64 = u32::MAX as u64 + (64 << 20);
+
+/// SHA-256 of a module's binary, used to tie saved state to the module it
+/// was captured from.
+pub type ModuleHash = [u8; 32];
+
+/// Errors produced while saving or loading a state file.
+#[derive(Debug)]
+pub enum StateFileError {
+    Io(io::Error),
+    /// The file is not a state file, or is truncated.
+    Malformed(&'static str),
+    /// The file was written by a newer, unknown format version.
+    UnsupportedVersion(u16),
+    /// The file declares a snapshot of `len` bytes, more than the `limit`
+    /// it was loaded with.
+    TooLarge {
+        len: u64,
+        limit: u64,
+    },
+    /// The file was saved from a different module than the one being loaded.
+    ModuleMismatch {
+        expected: ModuleHash,
+        found: ModuleHash,
+    },
+    Snapshot(SnapshotError),
+}
+
+impl fmt::Display for StateFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateFileError::Io(err) => write!(f, "state file I/O error: {}", err),
+            StateFileError::Malformed(what) => write!(f, "malformed state file: {}", what),
+            StateFileError::UnsupportedVersion(version) => {
+                write!(f, "unsupported state file version {}", version)
+            }
+            StateFileError::TooLarge { len, limit } => write!(
+                f,
+                "state file holds a {}-byte snapshot, more than the limit of {} bytes",
+                len, limit
+            ),
+            StateFileError::ModuleMismatch { expected, found } => write!(
+                f,
+                "state file belongs to module {}, expected {}",
+                hex(found),
+                hex(expected)
+            ),
+            StateFileError::Snapshot(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for StateFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StateFileError::Io(err) => Some(err),
+            StateFileError::Snapshot(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StateFileError {
+    fn from(err: io::Error) -> Self {
+        StateFileError::Io(err)
+    }
+}
+
+impl From<SnapshotError> for StateFileError {
+    fn from(err: SnapshotError) -> Self {
+        StateFileError::Snapshot(err)
+    }
+}
+
+/// Returns the hash stored in state files saved for the module `wasm`.
+pub fn module_hash(wasm: &[u8]) -> ModuleHash {
+    Sha256::digest(wasm).into()
+}
+
+/// Encodes `snapshot` into the versioned state file format.
+///
+/// The layout is a fixed header (magic, format version, flags, module hash,
+/// payload length and snapshot length) followed by the snapshot blob,
+/// deflated when `compress` is set.
+pub fn encode(module: &ModuleHash, snapshot: &Snapshot, compress: bool) -> Vec<u8> {
+    let blob = snapshot.to_bytes();
+    let blob_len = blob.len() as u64;
+    let payload = if compress {
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&blob).unwrap();
+        encoder.finish().unwrap()
+    } else {
+        blob
+    };
+
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.push(if compress { FLAG_COMPRESSED } else { 0 });
+    out.extend_from_slice(module);
+    out.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    out.extend_from_slice(&blob_len.to_le_bytes());
+    out.extend_from_slice(&payload);
+    out
+}
+
+/// Decodes a state file, rejecting it unless it was saved for `module`.
+pub fn decode(module: &ModuleHash, bytes: &[u8]) -> Result<Snapshot, StateFileError> {
+    decode_with_limit(module, bytes, MAX_SNAPSHOT_LEN)
+}
+
+/// Like [`decode`], but rejects files holding a snapshot of more than
+/// `limit` bytes before inflating them.
+pub fn decode_with_limit(
+    module: &ModuleHash,
+    bytes: &[u8],
+    limit: u64,
+) -> Result<Snapshot, StateFileError> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+        return Err(StateFileError::Malformed("missing state file header"));
+    }
+    let version = u16::from_le_bytes(bytes[4..6].try_into().unwrap());
+    if version != VERSION {
+        return Err(StateFileError::UnsupportedVersion(version));
+    }
+    let flags = bytes[6];
+    let found: ModuleHash = bytes[7..39].try_into().unwrap();
+    if &found != module {
+        return Err(StateFileError::ModuleMismatch {
+            expected: *module,
+            found,
+        });
+    }
+    let len = u64::from_le_bytes(bytes[39..47].try_into().unwrap());
+    let blob_len = u64::from_le_bytes(bytes[47..55].try_into().unwrap());
+    let payload = &bytes[HEADER_LEN..];
+    if payload.len() as u64 != len {
+        return Err(StateFileError::Malformed("payload length does not match"));
+    }
+    if blob_len > limit {
+        return Err(StateFileError::TooLarge {
+            len: blob_len,
+            limit,
+        });
+    }
+
+    if flags & FLAG_COMPRESSED != 0 {
+        // The declared length is within the limit; inflating at most one byte
+        // past it keeps a payload that expands further from going on.
+        let mut blob = Vec::new();
+        DeflateDecoder::new(payload)
+            .take(blob_len.saturating_add(1))
+            .read_to_end(&mut blob)
+            .map_err(|_| StateFileError::Malformed("corrupt compressed payload"))?;
+        if blob.len() as u64 != blob_len {
+            return Err(StateFileError::Malformed("snapshot length does not match"));
+        }
+        Ok(Snapshot::from_bytes(&blob)?)
+    } else {
+        if len != blob_len {
+            return Err(StateFileError::Malformed("snapshot length does not match"));
+        }
+        Ok(Snapshot::from_bytes(payload)?)
+    }
+}
+
+/// Captures `instance` and saves it to `path` for the module `wasm`.
+///
+/// The file is written next to `path` first and renamed into place, so an
+/// interrupted save never leaves a half-written state file behind.
+pub fn save(
+    path: impl AsRef<Path>,
+    wasm: &[u8],
+    instance: &Instance,
+    compress: bool,
+) -> Result<(), StateFileError> {
+    let path = path.as_ref();
+    let bytes = encode(&module_hash(wasm), &Snapshot::capture(instance), compress);
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Loads the state saved at `path` into `instance`, a fresh instance of the
+/// module `wasm`.
+pub fn load(
+    path: impl AsRef<Path>,
+    wasm: &[u8],
+    instance: &mut Instance,
+) -> Result<(), StateFileError> {
+    load_with_limit(path, wasm, instance, MAX_SNAPSHOT_LEN)
+}
+
+/// Like [`load`], but rejects files holding a snapshot of more than `limit`
+/// bytes before inflating them.
+pub fn load_with_limit(
+    path: impl AsRef<Path>,
+    wasm: &[u8],
+    instance: &mut Instance,
+    limit: u64,
+) -> Result<(), StateFileError> {
+    let bytes = fs::read(path)?;
+    let snapshot = decode_with_limit(&module_hash(wasm), &bytes, limit)?;
+    snapshot.restore(instance)?;
+    Ok(())
+}
+
+fn hex(hash: &ModuleHash) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
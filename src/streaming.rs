@@ -0,0 +1,127 @@
+//! Compiles a binary module as it arrives in chunks, from an asset bundle, a
+//! file or the network.
+//!
+//! Each chunk is appended to the one buffer the module is finally compiled
+//! from, and the sections and function bodies it completes are validated
+//! straight away. A corrupt module fails on the chunk that shows it, and the
+//! caller never needs a second full copy of the input.
+//!
+//! ```
+//! use adder::{Engine, Value};
+//!
+//! let wasm = include_bytes!("add.wasm");
+//! let mut stream = Engine::new().begin_streaming();
+//! stream.reserve(wasm.len());
+//! for chunk in wasm.chunks(16) {
+//!     stream.push(chunk)?;
+//! }
+//! let instance = stream.finish()?.instantiate()?;
+//! assert_eq!(instance.call("add_one", &[Value::I32(1)])?, [Value::I32(2)]);
+//! # Ok::<(), adder::Error>(())
+//! ```
+//!
+//! Only binary modules can be streamed; text has to be compiled whole with
+//! [`Engine::compile`].
+
+use crate::{Engine, Error, Module, Result};
+use std::mem;
+use wasmparser::{
+    BinaryReaderError, Chunk, FuncValidatorAllocations, Parser, ValidPayload, Validator,
+    WasmFeatures,
+};
+
+/// A module being compiled from chunks; created by
+/// [`Engine::begin_streaming`].
+pub struct StreamingCompile {
+    engine: Engine,
+    parser: Parser,
+    validator: Validator,
+    allocations: FuncValidatorAllocations,
+    wasm: Vec<u8>,
+    /// How much of `wasm` has been validated.
+    validated: usize,
+    ended: bool,
+    failed: Option<Error>,
+}
+
+impl StreamingCompile {
+    pub(crate) fn new(engine: &Engine) -> StreamingCompile {
+        StreamingCompile {
+            engine: engine.clone(),
+            parser: Parser::new(0),
+            // Everything the runtime might accept. Features it doesn't
+            // support are rejected when the module is compiled.
+            validator: Validator::new_with_features(WasmFeatures::WASM2),
+            allocations: FuncValidatorAllocations::default(),
+            wasm: Vec::new(),
+            validated: 0,
+            ended: false,
+            failed: None,
+        }
+    }
+
+    /// Makes room for `additional` more bytes. When the module's length is
+    /// known up front, reserving it avoids copying the buffer as it grows.
+    pub fn reserve(&mut self, additional: usize) {
+        self.wasm.reserve_exact(additional);
+    }
+
+    /// The number of bytes pushed so far.
+    pub fn received(&self) -> usize {
+        self.wasm.len()
+    }
+
+    /// Appends the next chunk of the module and validates what it completes.
+    ///
+    /// Once a push fails, the input received so far is dropped and every
+    /// later push, and [`finish`](Self::finish), returns the same error.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<()> {
+        if let Some(err) = &self.failed {
+            return Err(err.clone());
+        }
+        self.wasm.extend_from_slice(chunk);
+        self.validate(false).map_err(|err| self.fail(err))
+    }
+
+    /// Checks that the module is complete and compiles it.
+    pub fn finish(mut self) -> Result<Module> {
+        if let Some(err) = self.failed {
+            return Err(err);
+        }
+        self.validate(true)?;
+        self.engine.compile(&self.wasm)
+    }
+
+    /// Validates every payload completed since the last call. The module
+    /// only ends at `eof`, where an incomplete payload is an error.
+    fn validate(&mut self, eof: bool) -> Result<()> {
+        while !self.ended {
+            let data = &self.wasm[self.validated..];
+            let (payload, consumed) = match self.parser.parse(data, eof).map_err(invalid)? {
+                Chunk::NeedMoreData(_) => return Ok(()),
+                Chunk::Parsed { payload, consumed } => (payload, consumed),
+            };
+            match self.validator.payload(&payload).map_err(invalid)? {
+                ValidPayload::Func(func, body) => {
+                    let mut func = func.into_validator(mem::take(&mut self.allocations));
+                    func.validate(&body).map_err(invalid)?;
+                    self.allocations = func.into_allocations();
+                }
+                ValidPayload::End(_) => self.ended = true,
+                _ => {}
+            }
+            self.validated += consumed;
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, err: Error) -> Error {
+        self.wasm = Vec::new();
+        self.failed = Some(err.clone());
+        err
+    }
+}
+
+fn invalid(err: BinaryReaderError) -> Error {
+    Error::Compile(err.to_string())
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+use wasmer_runtime::types::Type;
+
+/// The type of a WebAssembly value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+}
+
+/// A WebAssembly value passed to or returned from an exported function, or
+/// held in a global.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    V128(u128),
+}
+
+impl ValueType {
+    pub(crate) fn from_wasmer(ty: Type) -> ValueType {
+        match ty {
+            Type::I32 => ValueType::I32,
+            Type::I64 => ValueType::I64,
+            Type::F32 => ValueType::F32,
+            Type::F64 => ValueType::F64,
+            Type::V128 => ValueType::V128,
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ValueType::I32 => "i32",
+            ValueType::I64 => "i64",
+            ValueType::F32 => "f32",
+            ValueType::F64 => "f64",
+            ValueType::V128 => "v128",
+        })
+    }
+}
+
+impl Value {
+    pub fn ty(&self) -> ValueType {
+        match self {
+            Value::I32(_) => ValueType::I32,
+            Value::I64(_) => ValueType::I64,
+            Value::F32(_) => ValueType::F32,
+            Value::F64(_) => ValueType::F64,
+            Value::V128(_) => ValueType::V128,
+        }
+    }
+
+    /// Returns the value's bit pattern, zero-extended to 128 bits.
+    pub fn to_bits(&self) -> u128 {
+        match *self {
+            Value::I32(x) => x as u32 as u128,
+            Value::I64(x) => x as u64 as u128,
+            Value::F32(x) => x.to_bits() as u128,
+            Value::F64(x) => x.to_bits() as u128,
+            Value::V128(x) => x,
+        }
+    }
+
+    /// Reads a value of type `ty` from the low bits of `bits`.
+    pub fn from_bits(ty: ValueType, bits: u128) -> Value {
+        match ty {
+            ValueType::I32 => Value::I32(bits as u32 as i32),
+            ValueType::I64 => Value::I64(bits as u64 as i64),
+            ValueType::F32 => Value::F32(f32::from_bits(bits as u32)),
+            ValueType::F64 => Value::F64(f64::from_bits(bits as u64)),
+            ValueType::V128 => Value::V128(bits),
+        }
+    }
+
+    pub(crate) fn from_wasmer(value: &wasmer_runtime::Value) -> Value {
+        match *value {
+            wasmer_runtime::Value::I32(x) => Value::I32(x),
+            wasmer_runtime::Value::I64(x) => Value::I64(x),
+            wasmer_runtime::Value::F32(x) => Value::F32(x),
+            wasmer_runtime::Value::F64(x) => Value::F64(x),
+            wasmer_runtime::Value::V128(x) => Value::V128(x),
+        }
+    }
+
+    pub(crate) fn to_wasmer(self) -> wasmer_runtime::Value {
+        match self {
+            Value::I32(x) => wasmer_runtime::Value::I32(x),
+            Value::I64(x) => wasmer_runtime::Value::I64(x),
+            Value::F32(x) => wasmer_runtime::Value::F32(x),
+            Value::F64(x) => wasmer_runtime::Value::F64(x),
+            Value::V128(x) => wasmer_runtime::Value::V128(x),
+        }
+    }
+}
+
+impl From<i32> for Value {
+    fn from(x: i32) -> Self {
+        Value::I32(x)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(x: u32) -> Self {
+        Value::I32(x as i32)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(x: i64) -> Self {
+        Value::I64(x)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(x: u64) -> Self {
+        Value::I64(x as i64)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(x: f32) -> Self {
+        Value::F32(x)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Value::F64(x)
+    }
+}
@@ -0,0 +1,268 @@
+use crate::snapshot::Snapshot;
+use crate::source::{self, ParseError};
+use crate::{Engine, Error, ImportObject, Value};
+use std::fmt;
+use wasm_encoder::reencode::{Reencode, RoundtripReencoder};
+use wasm_encoder::{
+    ConstExpr, DataSection, ExportSection, GlobalSection, Ieee32, Ieee64, MemorySection, RawSection,
+};
+use wasmparser::{DataKind, Parser, Payload};
+
+/// Zero runs shorter than this are kept inside a data segment rather than
+/// splitting it, since every extra segment costs a few bytes of header.
+const MIN_ZERO_GAP: usize = 16;
+
+/// Errors produced while pre-initializing a module.
+#[derive(Debug)]
+pub enum WizenError {
+    /// The module could not be compiled or instantiated.
+    Instantiate(Error),
+    /// The init function trapped or could not be called.
+    Init(Error),
+    Parse(wasmparser::BinaryReaderError),
+    Text(ParseError),
+    /// The module uses a feature whose state cannot be baked in.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for WizenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WizenError::Instantiate(err) => write!(f, "{}", err),
+            WizenError::Init(err) => write!(f, "init function failed: {}", err),
+            WizenError::Parse(err) => write!(f, "could not parse module: {}", err),
+            WizenError::Text(err) => write!(f, "invalid WebAssembly text: {}", err),
+            WizenError::Unsupported(what) => write!(f, "cannot pre-initialize {}", what),
+        }
+    }
+}
+
+impl std::error::Error for WizenError {}
+
+impl From<wasmparser::BinaryReaderError> for WizenError {
+    fn from(err: wasmparser::BinaryReaderError) -> Self {
+        WizenError::Parse(err)
+    }
+}
+
+/// Pre-initializes ("wizens") a module.
+///
+/// The module is instantiated, its init export is run once, and the
+/// resulting memory and mutable globals are written back into a new binary
+/// as data segments and global initializers. The start section is dropped,
+/// since its effects are part of the captured state, and by default so is
+/// the init export, so it cannot accidentally run twice.
+///
+/// Imported memories belong to the host and are not captured: their data
+/// segments are kept to be written again on instantiation, but what the init
+/// function writes to them is lost.
+pub struct Wizer {
+    init_func: String,
+    keep_init_export: bool,
+}
+
+impl Wizer {
+    pub fn new(init_func: &str) -> Self {
+        Wizer {
+            init_func: init_func.to_string(),
+            keep_init_export: false,
+        }
+    }
+
+    /// Keeps the init function exported in the output module.
+    pub fn keep_init_export(mut self, keep: bool) -> Self {
+        self.keep_init_export = keep;
+        self
+    }
+
+    /// Runs the init function of `input`, a binary or text module, and returns
+    /// the pre-initialized binary.
+    pub fn run(&self, input: &[u8], import_object: &ImportObject) -> Result<Vec<u8>, WizenError> {
+        let wasm = source::wasm_bytes(input).map_err(WizenError::Text)?;
+        let instance = Engine::new()
+            .compile(&wasm)
+            .and_then(|module| module.instantiate_with(import_object))
+            .map_err(WizenError::Instantiate)?;
+        instance
+            .call(&self.init_func, &[])
+            .map_err(WizenError::Init)?;
+        let snapshot = Snapshot::capture(&instance);
+        let imported_memories = instance.as_wasmer().module.info.imported_memories.len() as u32;
+        self.rewrite(&wasm, &snapshot, imported_memories)
+    }
+
+    fn rewrite(
+        &self,
+        wasm: &[u8],
+        snapshot: &Snapshot,
+        imported_memories: u32,
+    ) -> Result<Vec<u8>, WizenError> {
+        let mut module = wasm_encoder::Module::new();
+        let mut reencoder = RoundtripReencoder;
+        let mut wrote_data = false;
+
+        // The data section is the last of the known sections. A module that
+        // has none gets one where it would have been, before any custom
+        // sections that follow, such as `name`, which tools expect there.
+        let mut known_end = 0;
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload?.as_section() {
+                Some((id, range)) if id != 0 => known_end = range.end,
+                _ => {}
+            }
+        }
+
+        for payload in Parser::new(0).parse_all(wasm) {
+            let payload = payload?;
+            let trailing = match (&payload, payload.as_section()) {
+                (Payload::CustomSection(_), Some((_, range))) => range.start >= known_end,
+                _ => false,
+            };
+            if trailing && !wrote_data && !snapshot.memories.is_empty() {
+                module.section(&data_section(&[], snapshot, imported_memories));
+                wrote_data = true;
+            }
+            match payload {
+                Payload::MemorySection(reader) => {
+                    let mut section = MemorySection::new();
+                    for (local, memory) in reader.into_iter().enumerate() {
+                        let mut memory = reencoder.memory_type(memory?).unwrap();
+                        if let Some(bytes) = snapshot.memories.get(local) {
+                            let pages = (bytes.len() / 65536) as u64;
+                            memory.minimum = memory.minimum.max(pages);
+                        }
+                        section.memory(memory);
+                    }
+                    module.section(&section);
+                }
+                Payload::GlobalSection(reader) => {
+                    let mut section = GlobalSection::new();
+                    for (local, global) in reader.into_iter().enumerate() {
+                        let global = global?;
+                        let captured = snapshot
+                            .globals
+                            .iter()
+                            .find(|(index, _)| *index as usize == local);
+                        let init = match captured {
+                            Some((_, value)) => const_expr(value),
+                            None => reencoder.const_expr(global.init_expr).unwrap(),
+                        };
+                        section.global(reencoder.global_type(global.ty).unwrap(), &init);
+                    }
+                    module.section(&section);
+                }
+                Payload::ExportSection(reader) => {
+                    let mut section = ExportSection::new();
+                    for export in reader {
+                        let export = export?;
+                        if export.name == self.init_func && !self.keep_init_export {
+                            continue;
+                        }
+                        section.export(
+                            export.name,
+                            reencoder.export_kind(export.kind).unwrap(),
+                            export.index,
+                        );
+                    }
+                    module.section(&section);
+                }
+                Payload::StartSection { .. } => {}
+                Payload::DataCountSection { .. } => {
+                    return Err(WizenError::Unsupported("modules using bulk memory"));
+                }
+                Payload::DataSection(reader) => {
+                    let mut kept = Vec::new();
+                    for data in reader {
+                        let data = data?;
+                        match data.kind {
+                            DataKind::Passive => {
+                                return Err(WizenError::Unsupported("passive data segments"));
+                            }
+                            DataKind::Active {
+                                memory_index,
+                                offset_expr,
+                            } if memory_index < imported_memories => {
+                                let offset = reencoder.const_expr(offset_expr).unwrap();
+                                kept.push((memory_index, offset, data.data));
+                            }
+                            // Local memories are written from the snapshot.
+                            DataKind::Active { .. } => {}
+                        }
+                    }
+                    module.section(&data_section(&kept, snapshot, imported_memories));
+                    wrote_data = true;
+                }
+                _ => copy_section(&mut module, wasm, &payload),
+            }
+        }
+
+        if !wrote_data && !snapshot.memories.is_empty() {
+            module.section(&data_section(&[], snapshot, imported_memories));
+        }
+        let out = module.finish();
+        wasmparser::validate(&out)?;
+        Ok(out)
+    }
+}
+
+fn copy_section(module: &mut wasm_encoder::Module, wasm: &[u8], payload: &Payload) {
+    if let Some((id, range)) = payload.as_section() {
+        module.section(&RawSection {
+            id,
+            data: &wasm[range],
+        });
+    }
+}
+
+/// Builds a data section from the segments `kept` for imported memories and
+/// the captured contents of the local ones.
+fn data_section(
+    kept: &[(u32, ConstExpr, &[u8])],
+    snapshot: &Snapshot,
+    imported_memories: u32,
+) -> DataSection {
+    let mut section = DataSection::new();
+    for (memory_index, offset, bytes) in kept {
+        section.active(*memory_index, offset, bytes.iter().copied());
+    }
+    for (local, bytes) in snapshot.memories.iter().enumerate() {
+        let memory_index = imported_memories + local as u32;
+        for (start, end) in non_zero_runs(bytes) {
+            let offset = ConstExpr::i32_const(start as i32);
+            section.active(memory_index, &offset, bytes[start..end].iter().copied());
+        }
+    }
+    section
+}
+
+/// Returns the `[start, end)` ranges of `bytes` that are not zero, merging
+/// ranges separated by fewer than `MIN_ZERO_GAP` zero bytes.
+fn non_zero_runs(bytes: &[u8]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == 0 {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && bytes[i] != 0 {
+            i += 1;
+        }
+        match runs.last_mut() {
+            Some(last) if start - last.1 < MIN_ZERO_GAP => last.1 = i,
+            _ => runs.push((start, i)),
+        }
+    }
+    runs
+}
+
+fn const_expr(value: &Value) -> ConstExpr {
+    match *value {
+        Value::I32(x) => ConstExpr::i32_const(x),
+        Value::I64(x) => ConstExpr::i64_const(x),
+        Value::F32(x) => ConstExpr::f32_const(Ieee32::new(x.to_bits())),
+        Value::F64(x) => ConstExpr::f64_const(Ieee64::new(x.to_bits())),
+        Value::V128(x) => ConstExpr::v128_const(x as i128),
+    }
+}
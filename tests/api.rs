@@ -0,0 +1,167 @@
+use adder::{Engine, Error, ExportType, ExternType, Value, ValueType};
+
+const MODULE: &str = r#"
+(module
+  (memory (export "memory") 1 4)
+  (global $counter (export "counter") (mut i64) (i64.const 0))
+  (global (export "version") i32 (i32.const 3))
+  (func (export "add") (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.add)
+  (func (export "bump") (result i64)
+    global.get $counter
+    i64.const 1
+    i64.add
+    global.set $counter
+    global.get $counter)
+  (func (export "fail")
+    unreachable))
+"#;
+
+fn instance() -> adder::Instance {
+    Engine::new()
+        .compile(MODULE.as_bytes())
+        .unwrap()
+        .instantiate()
+        .unwrap()
+}
+
+#[test]
+fn exports_are_described_in_declaration_order() {
+    let module = Engine::new().compile(MODULE.as_bytes()).unwrap();
+    let names: Vec<String> = module.exports().into_iter().map(|e| e.name).collect();
+    assert_eq!(
+        names,
+        ["memory", "counter", "version", "add", "bump", "fail"]
+    );
+    assert_eq!(
+        module.export("add"),
+        Some(ExternType::Function {
+            params: vec![ValueType::I32, ValueType::I32],
+            results: vec![ValueType::I32],
+        })
+    );
+    assert!(module.exports().contains(&ExportType {
+        name: "memory".to_string(),
+        ty: ExternType::Memory {
+            minimum: 1,
+            maximum: Some(4)
+        },
+    }));
+}
+
+#[test]
+fn calls_check_arguments_and_report_traps() {
+    let instance = instance();
+    assert_eq!(
+        instance.call("add", &[Value::I32(2), Value::I32(40)]),
+        Ok(vec![Value::I32(42)])
+    );
+    assert!(matches!(
+        instance.call("add", &[Value::I64(2), Value::I32(40)]),
+        Err(Error::SignatureMismatch(_))
+    ));
+    assert!(matches!(
+        instance.call("missing", &[]),
+        Err(Error::ExportNotFound(_))
+    ));
+    assert!(matches!(
+        instance.call("memory", &[]),
+        Err(Error::ExportNotFound(_))
+    ));
+    assert!(matches!(instance.call("fail", &[]), Err(Error::Trap(_))));
+}
+
+#[test]
+fn memory_can_be_read_written_and_grown() {
+    let instance = instance();
+    instance.write_memory(100, b"hello").unwrap();
+    let mut buf = [0; 5];
+    instance.read_memory(100, &mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+
+    let end = instance.memory_len().unwrap();
+    assert!(matches!(
+        instance.write_memory(end - 2, b"abc"),
+        Err(Error::Memory(_))
+    ));
+
+    assert_eq!(instance.grow_memory(2), Ok(1));
+    assert_eq!(instance.memory_pages(), Ok(3));
+    assert!(matches!(instance.grow_memory(2), Err(Error::Memory(_))));
+}
+
+#[test]
+fn globals_can_be_read_and_set() {
+    let instance = instance();
+    assert_eq!(instance.call("bump", &[]), Ok(vec![Value::I64(1)]));
+    assert_eq!(instance.global("counter"), Ok(Value::I64(1)));
+    instance.set_global("counter", Value::I64(10)).unwrap();
+    assert_eq!(instance.call("bump", &[]), Ok(vec![Value::I64(11)]));
+
+    assert_eq!(instance.global("version"), Ok(Value::I32(3)));
+    assert!(matches!(
+        instance.set_global("version", Value::I32(4)),
+        Err(Error::SignatureMismatch(_))
+    ));
+    assert!(matches!(
+        instance.set_global("counter", Value::I32(4)),
+        Err(Error::SignatureMismatch(_))
+    ));
+}
+
+#[test]
+fn invalid_input_is_reported_by_kind() {
+    let engine = Engine::new();
+    assert!(matches!(
+        engine.compile(b"(module (func i32.bogus))"),
+        Err(Error::Parse(_))
+    ));
+    assert!(matches!(
+        engine.compile(b"(module (func (result i32)))"),
+        Err(Error::Compile(_))
+    ));
+    assert!(engine.validate(MODULE.as_bytes()).is_ok());
+}
+
+#[test]
+fn modules_without_functions_compile() {
+    let engine = Engine::new();
+    for input in [
+        "(module)",
+        r#"(module (memory (export "memory") 1) (data (i32.const 0) "hi"))"#,
+        r#"(module (type (func (param i32))) (global (export "g") i32 (i32.const 7)))"#,
+        r#"(module (import "env" "f" (func)) (@custom "note" "x"))"#,
+        r#"(module (func $unused) (memory (export "memory") 1))"#,
+        r#"(module
+            (@custom "early" (before first) "x")
+            (type (func))
+            (memory (export "memory") 1)
+            (@custom "late" (after data) "y")
+            (data (i32.const 0) "hi"))"#,
+    ] {
+        let module = engine.compile(input.as_bytes()).unwrap();
+        assert!(module
+            .exports()
+            .iter()
+            .all(|export| !matches!(export.ty, ExternType::Function { .. })));
+    }
+
+    let instance = engine
+        .compile(br#"(module (global (export "g") i32 (i32.const 7)))"#)
+        .unwrap()
+        .instantiate()
+        .unwrap();
+    assert_eq!(instance.global("g"), Ok(Value::I32(7)));
+    assert_eq!(instance.exports().len(), 1);
+
+    let instance = engine
+        .compile(br#"(module (memory (export "memory") 1) (data (i32.const 3) "hi"))"#)
+        .unwrap()
+        .instantiate()
+        .unwrap();
+    let mut data = [0; 2];
+    instance.read_memory(3, &mut data).unwrap();
+    assert_eq!(&data, b"hi");
+}
@@ -0,0 +1,82 @@
+/* Links against libadder.a and drives the C API end to end.
+ * Built and run by tests/staticlib.rs; exits non-zero on the first failure. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "adder.h"
+
+#define CHECK(cond)                                                        \
+  do {                                                                     \
+    if (!(cond)) {                                                         \
+      const char *message = wasm_last_error_message();                     \
+      fprintf(stderr, "%s:%d: check failed: %s (%s)\n", __FILE__, __LINE__, \
+              #cond, message ? message : "no error");                      \
+      return 1;                                                            \
+    }                                                                      \
+  } while (0)
+
+static const char MODULE[] =
+    "(module\n"
+    "  (func (export \"add_one\") (param i32) (result i32)\n"
+    "    local.get 0\n"
+    "    i32.const 1\n"
+    "    i32.add)\n"
+    "  (func (export \"fail\") unreachable))";
+
+int main(void) {
+  CHECK(load_wasm() == 0);
+
+  WasmModule *module = NULL;
+  CHECK(wasm_module_new((const uint8_t *)MODULE, strlen(MODULE), &module) ==
+        WASM_STATUS_OK);
+
+  WasmInstance *instance = NULL;
+  CHECK(wasm_instance_new(module, &instance) == WASM_STATUS_OK);
+
+  WasmPool *pool = NULL;
+  CHECK(wasm_pool_new(module, 1, 2, WASM_POOL_RESET_KEEP, WASM_POOL_BLOCK,
+                      &pool) == WASM_STATUS_OK);
+  wasm_module_delete(module);
+
+  WasmValue arg = {.kind = WASM_I32, .of = {.i32 = 41}};
+  WasmValue result = {0};
+  CHECK(wasm_instance_call(instance, "add_one", &arg, 1, &result, 1) ==
+        WASM_STATUS_OK);
+  CHECK(result.kind == WASM_I32 && result.of.i32 == 42);
+
+  arg.of.i32 = 1;
+  CHECK(wasm_pool_call(pool, "add_one", &arg, 1, &result, 1) ==
+        WASM_STATUS_OK);
+  CHECK(result.kind == WASM_I32 && result.of.i32 == 2);
+  wasm_pool_delete(pool);
+
+  CHECK(wasm_instance_call(instance, "fail", NULL, 0, NULL, 0) ==
+        WASM_STATUS_TRAP);
+  CHECK(wasm_instance_call(instance, "missing", NULL, 0, NULL, 0) ==
+        WASM_STATUS_EXPORT_NOT_FOUND);
+  CHECK(strstr(wasm_last_error_message(), "missing") != NULL);
+
+  const uint8_t garbage[] = "(module (func i32.bogus))";
+  WasmModule *invalid = NULL;
+  CHECK(wasm_module_new(garbage, sizeof garbage - 1, &invalid) ==
+        WASM_STATUS_PARSE_ERROR);
+  CHECK(invalid == NULL);
+
+  bool carried = false;
+  char *report = NULL;
+  CHECK(wasm_instance_reload(instance, (const uint8_t *)MODULE, strlen(MODULE),
+                             &carried, &report) == WASM_STATUS_OK);
+  CHECK(carried && strcmp(report, "state carried over") == 0);
+  wasm_string_delete(report);
+
+  WasmStream *stream = NULL;
+  CHECK(wasm_stream_new(0, &stream) == WASM_STATUS_OK);
+  CHECK(wasm_stream_push(stream, garbage, 4) == WASM_STATUS_COMPILE_ERROR);
+  CHECK(wasm_stream_finish(stream, &invalid) == WASM_STATUS_COMPILE_ERROR);
+  CHECK(invalid == NULL);
+
+  wasm_instance_delete(instance);
+  printf("c api ok\n");
+  return 0;
+}
@@ -0,0 +1,98 @@
+use adder::{dartgen, source};
+use std::process::Command;
+
+const MODULE: &str = r#"
+(module
+  (func (export "add_one") (param i32) (result i32)
+    local.get 0
+    i32.const 1
+    i32.add)
+  (func (export "scale") (param f64 f32) (result f64)
+    local.get 0)
+  (func (export "reset"))
+  (func (export "pair") (result i32 i32)
+    i32.const 1
+    i32.const 2)
+  (memory (export "memory") 1))
+"#;
+
+#[test]
+fn generates_one_typed_method_per_function_export() {
+    let wasm = source::wasm_bytes(MODULE.as_bytes()).unwrap();
+    let dart = dartgen::generate(&wasm, "Math").unwrap();
+
+    assert!(dart.contains("class Math {"));
+    assert!(dart.contains("factory Math.load(ffi.DynamicLibrary lib, Uint8List wasm)"));
+    assert!(dart.contains("  int addOne(int arg0) {"));
+    assert!(dart.contains("  double scale(double arg0, double arg1) {"));
+    assert!(dart.contains("  void reset() {"));
+    assert!(dart.contains("// Skipped `pair`: unsupported signature."));
+    assert!(!dart.contains("memory("));
+    assert!(dart.contains("double _f32FromBits(int bits)"));
+    assert!(dart.contains("double _f64FromBits(int bits)"));
+}
+
+#[test]
+fn class_names_are_upper_camel_case() {
+    assert_eq!(dartgen::class_name("add"), "Add");
+    assert_eq!(dartgen::class_name("image-filters_v2"), "ImageFiltersV2");
+    assert_eq!(dartgen::class_name("3d"), "Wasm3d");
+}
+
+#[test]
+fn export_names_cannot_break_out_of_comments() {
+    let wat = r#"(module
+        (func (export "evil\nvoid injected() {}\n") (param i32))
+        (func (export "skipped\r\nimport 'x';") (result i32 i32)
+            i32.const 1
+            i32.const 2))"#;
+    let wasm = source::wasm_bytes(wat.as_bytes()).unwrap();
+    let dart = dartgen::generate(&wasm, "Evil").unwrap();
+
+    assert!(dart.contains("  /// Calls `evil\\u{a}void injected() {}\\u{a}`: (i32) -> ()."));
+    assert!(dart.contains("  // Skipped `skipped\\u{d}\\u{a}import 'x';`: unsupported signature."));
+    assert!(!dart.lines().any(|line| line.starts_with("void injected")));
+    assert!(!dart.lines().any(|line| line.starts_with("import 'x'")));
+    // The string literal naming the export is escaped too.
+    assert!(dart.contains("'evil\\u{a}void injected() {}\\u{a}'"));
+}
+
+#[test]
+fn class_names_must_be_dart_identifiers() {
+    for name in ["Math", "_Private", "V2", "lower_case"] {
+        assert!(dartgen::is_class_name(name), "{}", name);
+    }
+    for name in ["", "my class", "2d", "a-b", "a$b", "class", "Größe"] {
+        assert!(!dartgen::is_class_name(name), "{}", name);
+    }
+    // Derived names always pass.
+    for stem in ["add", "image-filters_v2", "3d", "class", "", "größe"] {
+        let name = dartgen::class_name(stem);
+        assert!(dartgen::is_class_name(&name), "{} -> {}", stem, name);
+    }
+}
+
+#[test]
+fn the_binary_rejects_invalid_class_names() {
+    let output = Command::new(env!("CARGO_BIN_EXE_wasmer-dartgen"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["src/add.wasm", "--class", "my class"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: `my class` is not a valid Dart class name\n"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_wasmer-dartgen"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["src/add.wasm", "--class", "Adder"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("class Adder {"));
+}
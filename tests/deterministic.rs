@@ -0,0 +1,266 @@
+use adder::deterministic::{self, Host, CANONICAL_NAN_F32, CANONICAL_NAN_F64};
+use adder::{Engine, Instance, Value};
+use std::time::Duration;
+
+const FLOATS: &str = r#"(module
+    (memory (export "memory") 1)
+    (func (export "add_f32") (param f32 f32) (result f32)
+        local.get 0
+        local.get 1
+        f32.add)
+    (func (export "sqrt_f64") (param f64) (result f64)
+        local.get 0
+        f64.sqrt)
+    (func (export "neg_f32") (param f32) (result f32)
+        local.get 0
+        f32.neg)
+    (func (export "store_div") (param f64 f64)
+        (local i32)
+        i32.const 8
+        local.get 0
+        local.get 1
+        f64.div
+        f64.store))"#;
+
+const WASI: &str = r#"(module
+    (import "wasi_snapshot_preview1" "clock_time_get"
+        (func $clock_time_get (param i32 i64 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "clock_res_get"
+        (func $clock_res_get (param i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "random_get"
+        (func $random_get (param i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (func (export "now") (param i32) (result i64)
+        (drop (call $clock_time_get (local.get 0) (i64.const 0) (i32.const 0)))
+        (i64.load (i32.const 0)))
+    (func (export "resolution") (result i64)
+        (drop (call $clock_res_get (i32.const 1) (i32.const 0)))
+        (i64.load (i32.const 0)))
+    (func (export "clock_errno") (param i32 i32) (result i32)
+        (call $clock_time_get (local.get 0) (i64.const 0) (local.get 1)))
+    (func (export "random") (param i32 i32) (result i32)
+        (call $random_get (local.get 0) (local.get 1))))"#;
+
+fn floats() -> Instance {
+    Engine::new()
+        .canonicalize_nans(true)
+        .compile(FLOATS.as_bytes())
+        .unwrap()
+        .instantiate()
+        .unwrap()
+}
+
+fn wasi(host: &Host) -> Instance {
+    Engine::new()
+        .compile(WASI.as_bytes())
+        .unwrap()
+        .instantiate_with(&host.imports())
+        .unwrap()
+}
+
+fn memory(instance: &Instance) -> Vec<u8> {
+    let mut bytes = vec![0; instance.memory_len().unwrap()];
+    instance.read_memory(0, &mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn arithmetic_nans_are_canonicalized() {
+    let instance = floats();
+    let payload = f32::from_bits(0xffa0_0001);
+    match instance
+        .call("add_f32", &[Value::F32(payload), Value::F32(1.0)])
+        .unwrap()[..]
+    {
+        [Value::F32(x)] => assert_eq!(x.to_bits(), CANONICAL_NAN_F32),
+        ref other => panic!("unexpected results {:?}", other),
+    }
+    match instance.call("sqrt_f64", &[Value::F64(-1.0)]).unwrap()[..] {
+        [Value::F64(x)] => assert_eq!(x.to_bits(), CANONICAL_NAN_F64),
+        ref other => panic!("unexpected results {:?}", other),
+    }
+
+    instance
+        .call("store_div", &[Value::F64(0.0), Value::F64(0.0)])
+        .unwrap();
+    let mut bytes = [0; 8];
+    instance.read_memory(8, &mut bytes).unwrap();
+    assert_eq!(u64::from_le_bytes(bytes), CANONICAL_NAN_F64);
+}
+
+#[test]
+fn other_values_are_untouched() {
+    let instance = floats();
+    assert_eq!(
+        instance
+            .call("add_f32", &[Value::F32(-0.0), Value::F32(-0.0)])
+            .unwrap()[0]
+            .to_bits(),
+        (-0.0f32).to_bits() as u128
+    );
+    assert_eq!(
+        instance.call("sqrt_f64", &[Value::F64(2.25)]).unwrap(),
+        [Value::F64(1.5)]
+    );
+    // Sign changes only move bits, so they keep the payload passed in.
+    let payload = f32::from_bits(0x7fa0_0001);
+    assert_eq!(
+        instance.call("neg_f32", &[Value::F32(payload)]).unwrap()[0].to_bits(),
+        0xffa0_0001
+    );
+}
+
+#[test]
+fn canonicalization_keeps_the_module_valid() {
+    let wasm = adder::source::wasm_bytes(FLOATS.as_bytes()).unwrap();
+    let rewritten = deterministic::canonicalize_nans(&wasm).unwrap();
+    wasmparser::validate(&rewritten).unwrap();
+    assert_eq!(
+        deterministic::canonicalize_nans(include_bytes!("../src/add.wasm")).map(|_| ()),
+        Ok(())
+    );
+    assert!(matches!(
+        deterministic::canonicalize_nans(&wasm[..wasm.len() - 3]),
+        Err(adder::Error::Compile(_))
+    ));
+}
+
+#[test]
+fn virtual_clock_advances_by_its_tick() {
+    let host = Host::new(0)
+        .start_time(1_000)
+        .tick(Duration::from_micros(5));
+    let instance = wasi(&host);
+    let times: Vec<_> = (0..4)
+        .map(|clock| instance.call("now", &[Value::I32(clock)]).unwrap())
+        .collect();
+    assert_eq!(
+        times,
+        [1_000, 6_000, 11_000, 16_000].map(|t| vec![Value::I64(t)])
+    );
+    assert_eq!(
+        instance.call("resolution", &[]).unwrap(),
+        [Value::I64(5_000)]
+    );
+
+    // EINVAL for an unknown clock, EFAULT for an out-of-bounds pointer.
+    assert_eq!(
+        instance
+            .call("clock_errno", &[Value::I32(4), Value::I32(0)])
+            .unwrap(),
+        [Value::I32(28)]
+    );
+    assert_eq!(
+        instance
+            .call("clock_errno", &[Value::I32(0), Value::I32(65535)])
+            .unwrap(),
+        [Value::I32(21)]
+    );
+    // The faulted read did not use up a tick.
+    assert_eq!(
+        instance.call("now", &[Value::I32(0)]).unwrap(),
+        [Value::I64(21_000)]
+    );
+}
+
+#[test]
+fn modules_without_memory_fault() {
+    let instance = Engine::new()
+        .compile(
+            br#"(module
+                (import "wasi_snapshot_preview1" "clock_time_get"
+                    (func $clock_time_get (param i32 i64 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "clock_res_get"
+                    (func $clock_res_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "random_get"
+                    (func $random_get (param i32 i32) (result i32)))
+                (func (export "now") (result i32)
+                    (call $clock_time_get (i32.const 0) (i64.const 0) (i32.const 0)))
+                (func (export "resolution") (result i32)
+                    (call $clock_res_get (i32.const 0) (i32.const 0)))
+                (func (export "random") (result i32)
+                    (call $random_get (i32.const 0) (i32.const 8))))"#,
+        )
+        .unwrap()
+        .instantiate_with(&Host::new(0).imports())
+        .unwrap();
+    for name in ["now", "resolution", "random"] {
+        assert_eq!(
+            instance.call(name, &[]).unwrap(),
+            [Value::I32(21)],
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn random_bytes_depend_only_on_the_seed() {
+    let run = |seed| {
+        let instance = wasi(&Host::new(seed));
+        for &(offset, len) in &[(16, 13), (100, 64), (1000, 1)] {
+            assert_eq!(
+                instance
+                    .call("random", &[Value::I32(offset), Value::I32(len)])
+                    .unwrap(),
+                [Value::I32(0)]
+            );
+        }
+        memory(&instance)
+    };
+    assert_eq!(run(42), run(42));
+    assert_ne!(run(42), run(43));
+
+    let mut expected = [0; 13];
+    Host::new(42).fill(&mut expected);
+    assert_eq!(run(42)[16..29], expected);
+}
+
+#[test]
+fn random_buffers_outside_memory_fault_without_allocating() {
+    let instance = wasi(&Host::new(1));
+    for &(offset, len) in &[(0, -1), (16, 65_521), (-1, 1)] {
+        assert_eq!(
+            instance
+                .call("random", &[Value::I32(offset), Value::I32(len)])
+                .unwrap(),
+            [Value::I32(21)],
+            "{} bytes at {}",
+            len,
+            offset
+        );
+    }
+    assert!(memory(&instance).iter().all(|&byte| byte == 0));
+
+    // The whole of memory is fine.
+    assert_eq!(
+        instance
+            .call("random", &[Value::I32(0), Value::I32(65_536)])
+            .unwrap(),
+        [Value::I32(0)]
+    );
+    let mut expected = vec![0; 65_536];
+    Host::new(1).fill(&mut expected);
+    assert_eq!(memory(&instance), expected);
+}
+
+#[test]
+fn repeated_runs_are_bit_identical() {
+    let run = || {
+        let wasi = wasi(&Host::new(7));
+        let floats = floats();
+        let mut results = Vec::new();
+        for x in [0.0, -1.0, f64::INFINITY, f64::NAN, 3.0] {
+            results.extend(floats.call("sqrt_f64", &[Value::F64(x)]).unwrap());
+            floats
+                .call("store_div", &[Value::F64(x), Value::F64(x - 3.0)])
+                .unwrap();
+            results.extend(wasi.call("now", &[Value::I32(0)]).unwrap());
+            wasi.call("random", &[Value::I32(64), Value::I32(32)])
+                .unwrap();
+        }
+        let bits: Vec<u128> = results.iter().map(Value::to_bits).collect();
+        (bits, memory(&floats), memory(&wasi))
+    };
+    assert_eq!(run(), run());
+}
@@ -0,0 +1,140 @@
+use adder::differential::{self, Call, Observation, Outcome};
+use adder::{Backend, Error, Value};
+use std::fs;
+use std::path::Path;
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    fs::read(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+}
+
+fn observation() -> Observation {
+    Observation {
+        backend: Backend::Cranelift,
+        setup_error: None,
+        calls: vec![
+            Outcome::Returned(vec![Value::F32(f32::NAN)]),
+            Outcome::Trapped,
+        ],
+        globals: vec![("counter".to_string(), Value::I64(1))],
+        memory: Some(vec![0; 4]),
+    }
+}
+
+#[test]
+fn fixtures_behave_the_same_on_every_backend() {
+    let cases = [
+        (
+            "arithmetic.wat",
+            vec![
+                Call::new("add_one", &[Value::I32(i32::MAX)]),
+                Call::new("mul_f32", &[Value::F32(f32::INFINITY), Value::F32(0.0)]),
+                Call::new("div_f64", &[Value::F64(-1.0), Value::F64(0.0)]),
+                Call::new("div_u", &[Value::I32(1), Value::I32(0)]),
+            ],
+        ),
+        (
+            "memory.wat",
+            vec![
+                Call::new("store", &[Value::I32(8), Value::I32(0x0102_0304)]),
+                Call::new("grow", &[Value::I32(1)]),
+                Call::new("load", &[Value::I32(-1)]),
+            ],
+        ),
+        (
+            "globals.wat",
+            vec![Call::new("bump", &[]), Call::new("set", &[Value::I64(9)])],
+        ),
+        ("start_trap.wat", vec![]),
+    ];
+    for (name, calls) in &cases {
+        let observations = differential::run(&fixture(name), calls)
+            .unwrap_or_else(|divergence| panic!("{}: {}", name, divergence));
+        assert_eq!(observations.len(), Backend::ALL.len());
+    }
+}
+
+#[test]
+fn observations_record_calls_globals_and_memory() {
+    let calls = [
+        Call::new("store", &[Value::I32(8), Value::I32(0x0102_0304)]),
+        Call::new("load", &[Value::I32(1 << 30)]),
+        Call::new("missing", &[]),
+    ];
+    let observation = differential::observe(Backend::Cranelift, &fixture("memory.wat"), &calls);
+    assert_eq!(observation.setup_error, None);
+    assert_eq!(observation.calls[0], Outcome::Returned(vec![]));
+    assert_eq!(observation.calls[1], Outcome::Trapped);
+    assert_eq!(
+        observation.calls[2],
+        Outcome::Rejected(Error::ExportNotFound("missing".to_string()))
+    );
+    assert_eq!(observation.memory.unwrap()[8..12], [4, 3, 2, 1]);
+
+    let observation = differential::observe(Backend::Cranelift, &fixture("globals.wat"), &[]);
+    assert_eq!(
+        observation.globals,
+        [("scale".to_string(), Value::F64(2.5))]
+    );
+
+    let observation = differential::observe(Backend::Cranelift, &fixture("start_trap.wat"), &[]);
+    assert!(matches!(
+        observation.setup_error,
+        Some(Error::Instantiate(_))
+    ));
+}
+
+#[test]
+fn divergences_are_described() {
+    let calls = [Call::new("f", &[]), Call::new("g", &[])];
+    let left = observation();
+    assert_eq!(differential::compare(&left, &left.clone(), &calls), None);
+
+    let mut right = observation();
+    right.calls[0] = Outcome::Returned(vec![Value::F32(-f32::NAN)]);
+    assert_eq!(
+        differential::compare(&left, &right, &calls),
+        None,
+        "NaN payloads may differ"
+    );
+
+    right.calls[1] = Outcome::Returned(vec![]);
+    let divergence = differential::compare(&left, &right, &calls).unwrap();
+    assert_eq!(divergence.what, "call 1 (`g`): trapped, then returned []");
+
+    let mut right = observation();
+    right.globals[0].1 = Value::I64(2);
+    assert_eq!(
+        differential::compare(&left, &right, &calls).unwrap().what,
+        "global `counter`: I64(1), then I64(2)"
+    );
+
+    let mut right = observation();
+    right.memory.as_mut().unwrap()[2] = 0xff;
+    assert_eq!(
+        differential::compare(&left, &right, &calls).unwrap().what,
+        "memory at 2: 0x00, then 0xff"
+    );
+
+    let mut right = observation();
+    right.setup_error = Some(Error::Instantiate("start trapped".to_string()));
+    assert_eq!(
+        differential::compare(&left, &right, &calls)
+            .unwrap()
+            .to_string(),
+        "cranelift and cranelift diverge: setup: instantiated, then could not \
+         instantiate module: start trapped"
+    );
+}
+
+#[test]
+fn engines_report_their_backend() {
+    assert_eq!(adder::Engine::new().backend(), Backend::Cranelift);
+    for &backend in Backend::ALL {
+        let engine = adder::Engine::with_backend(backend);
+        assert_eq!(engine.backend(), backend);
+        engine.compile(&fixture("arithmetic.wat")).unwrap();
+    }
+}
@@ -0,0 +1,922 @@
+//! Drives the C ABI in `adder::ffi` against the modules in `tests/fixtures`.
+
+use adder::ffi::*;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::ptr;
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    fs::read(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+}
+
+fn last_error() -> String {
+    let message = wasm_last_error_message();
+    assert!(!message.is_null());
+    unsafe { CStr::from_ptr(message) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn compile(input: &[u8]) -> Result<*mut WasmModule, WasmStatus> {
+    let mut module = ptr::null_mut();
+    match unsafe { wasm_module_new(input.as_ptr(), input.len(), &mut module) } {
+        WasmStatus::Ok => Ok(module),
+        status => {
+            assert!(module.is_null());
+            Err(status)
+        }
+    }
+}
+
+fn instantiate(input: &[u8]) -> Result<Instance, WasmStatus> {
+    let module = compile(input)?;
+    let mut instance = ptr::null_mut();
+    let status = unsafe { wasm_instance_new(module, &mut instance) };
+    unsafe { wasm_module_delete(module) };
+    match status {
+        WasmStatus::Ok => Ok(Instance(instance)),
+        status => Err(status),
+    }
+}
+
+/// Owns an instance created through the C ABI.
+struct Instance(*mut WasmInstance);
+
+impl Instance {
+    fn load(name: &str) -> Instance {
+        instantiate(&fixture(name)).unwrap_or_else(|status| {
+            panic!("{}: {:?}: {}", name, status, last_error());
+        })
+    }
+
+    fn call_raw(
+        &self,
+        name: *const c_char,
+        args: &[WasmValue],
+        results_len: usize,
+    ) -> Result<Vec<WasmValue>, WasmStatus> {
+        let mut results = vec![i32(0); results_len];
+        let status = unsafe {
+            wasm_instance_call(
+                self.0,
+                name,
+                args.as_ptr(),
+                args.len(),
+                results.as_mut_ptr(),
+                results.len(),
+            )
+        };
+        match status {
+            WasmStatus::Ok => Ok(results),
+            status => Err(status),
+        }
+    }
+
+    fn call(
+        &self,
+        name: &str,
+        args: &[WasmValue],
+        results_len: usize,
+    ) -> Result<Vec<WasmValue>, WasmStatus> {
+        let name = CString::new(name).unwrap();
+        self.call_raw(name.as_ptr(), args, results_len)
+    }
+
+    fn call_i32(&self, name: &str, args: &[WasmValue]) -> Result<i32, WasmStatus> {
+        self.call(name, args, 1).map(|results| as_i32(&results[0]))
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        unsafe { wasm_instance_delete(self.0) };
+    }
+}
+
+fn i32(x: i32) -> WasmValue {
+    WasmValue {
+        kind: WASM_I32,
+        of: WasmValueOf { i32: x },
+    }
+}
+
+fn i64(x: i64) -> WasmValue {
+    WasmValue {
+        kind: WASM_I64,
+        of: WasmValueOf { i64: x },
+    }
+}
+
+fn f32(x: f32) -> WasmValue {
+    WasmValue {
+        kind: WASM_F32,
+        of: WasmValueOf { f32: x },
+    }
+}
+
+fn f64(x: f64) -> WasmValue {
+    WasmValue {
+        kind: WASM_F64,
+        of: WasmValueOf { f64: x },
+    }
+}
+
+fn as_i32(value: &WasmValue) -> i32 {
+    assert_eq!(value.kind, WASM_I32);
+    unsafe { value.of.i32 }
+}
+
+fn as_i64(value: &WasmValue) -> i64 {
+    assert_eq!(value.kind, WASM_I64);
+    unsafe { value.of.i64 }
+}
+
+fn as_f32(value: &WasmValue) -> f32 {
+    assert_eq!(value.kind, WASM_F32);
+    unsafe { value.of.f32 }
+}
+
+fn as_f64(value: &WasmValue) -> f64 {
+    assert_eq!(value.kind, WASM_F64);
+    unsafe { value.of.f64 }
+}
+
+#[test]
+fn load_wasm_runs_the_bundled_module() {
+    assert_eq!(load_wasm(), 0);
+}
+
+#[test]
+fn arithmetic_on_every_value_type() {
+    let instance = Instance::load("arithmetic.wat");
+    assert_eq!(instance.call_i32("add_one", &[i32(41)]), Ok(42));
+
+    let sum = instance
+        .call("add_i64", &[i64(1 << 40), i64(-1)], 1)
+        .unwrap();
+    assert_eq!(as_i64(&sum[0]), (1 << 40) - 1);
+
+    let product = instance.call("mul_f32", &[f32(1.5), f32(-4.0)], 1).unwrap();
+    assert_eq!(as_f32(&product[0]), -6.0);
+
+    let quotient = instance.call("div_f64", &[f64(1.0), f64(0.0)], 1).unwrap();
+    assert_eq!(as_f64(&quotient[0]), f64::INFINITY);
+
+    assert_eq!(instance.call("nothing", &[], 0).unwrap().len(), 0);
+}
+
+#[test]
+fn add_one_wraps_around_at_u32_max() {
+    let instance = Instance::load("arithmetic.wat");
+    assert_eq!(instance.call_i32("add_one", &[i32(u32::MAX as i32)]), Ok(0));
+    assert_eq!(instance.call_i32("add_one", &[i32(i32::MAX)]), Ok(i32::MIN));
+
+    // The bundled module behaves the same way.
+    let bundled = instantiate(include_bytes!("../src/add.wasm")).unwrap();
+    assert_eq!(bundled.call_i32("add_one", &[i32(u32::MAX as i32)]), Ok(0));
+}
+
+#[test]
+fn unsigned_arguments_round_trip_through_i32() {
+    let instance = Instance::load("arithmetic.wat");
+    let quotient = instance
+        .call_i32("div_u", &[i32(u32::MAX as i32), i32(2)])
+        .unwrap();
+    assert_eq!(quotient as u32, u32::MAX / 2);
+}
+
+#[test]
+fn mismatched_calls_are_rejected_before_running() {
+    let instance = Instance::load("arithmetic.wat");
+    assert_eq!(
+        instance.call("add_one", &[], 1).unwrap_err(),
+        WasmStatus::SignatureMismatch
+    );
+    assert_eq!(
+        instance.call("add_one", &[i64(1)], 1).unwrap_err(),
+        WasmStatus::SignatureMismatch
+    );
+    assert_eq!(
+        instance.call("add_one", &[i32(1)], 0).unwrap_err(),
+        WasmStatus::SignatureMismatch
+    );
+    assert_eq!(
+        instance.call("add_one", &[i32(1)], 2).unwrap_err(),
+        WasmStatus::SignatureMismatch
+    );
+    assert!(last_error().contains("add_one"), "{}", last_error());
+
+    let unknown_kind = WasmValue {
+        kind: 42,
+        of: WasmValueOf { i64: 0 },
+    };
+    assert_eq!(
+        instance.call("add_one", &[unknown_kind], 1).unwrap_err(),
+        WasmStatus::InvalidArgument
+    );
+}
+
+#[test]
+fn missing_exports_and_bad_names() {
+    let instance = Instance::load("arithmetic.wat");
+    assert_eq!(
+        instance.call("subtract", &[], 0).unwrap_err(),
+        WasmStatus::ExportNotFound
+    );
+    assert!(last_error().contains("subtract"), "{}", last_error());
+
+    assert_eq!(
+        instance.call_raw(ptr::null(), &[], 0).unwrap_err(),
+        WasmStatus::InvalidArgument
+    );
+    let not_utf8 = b"add_\xff\0";
+    assert_eq!(
+        instance
+            .call_raw(not_utf8.as_ptr() as *const c_char, &[], 0)
+            .unwrap_err(),
+        WasmStatus::InvalidArgument
+    );
+}
+
+#[test]
+fn traps_are_reported_and_leave_the_instance_usable() {
+    let instance = Instance::load("traps.wat");
+    let traps: &[(&str, &[WasmValue], usize)] = &[
+        ("unreachable", &[], 0),
+        ("div_by_zero", &[i32(1)], 1),
+        ("overflow", &[], 1),
+        ("bad_truncate", &[f32(f32::NAN)], 1),
+        ("bad_truncate", &[f32(3e9)], 1),
+        ("null_call", &[], 0),
+    ];
+    for (name, args, results_len) in traps {
+        assert_eq!(
+            instance.call(name, args, *results_len).unwrap_err(),
+            WasmStatus::Trap,
+            "{}",
+            name
+        );
+        assert!(last_error().starts_with("trap"), "{}", last_error());
+        assert_eq!(instance.call_i32("ok", &[]), Ok(7));
+    }
+}
+
+#[test]
+fn memory_grows_up_to_its_maximum() {
+    let instance = Instance::load("memory.wat");
+    assert_eq!(instance.call_i32("size", &[]), Ok(1));
+
+    let past_end = 65536;
+    assert_eq!(
+        instance
+            .call("store", &[i32(past_end), i32(5)], 0)
+            .unwrap_err(),
+        WasmStatus::Trap
+    );
+
+    assert_eq!(instance.call_i32("grow", &[i32(1)]), Ok(1));
+    assert_eq!(instance.call_i32("size", &[]), Ok(2));
+    instance.call("store", &[i32(past_end), i32(5)], 0).unwrap();
+    assert_eq!(instance.call_i32("load", &[i32(past_end)]), Ok(5));
+
+    // Growing past the maximum fails inside wasm without trapping.
+    assert_eq!(instance.call_i32("grow", &[i32(2)]), Ok(-1));
+    assert_eq!(instance.call_i32("grow", &[i32(1)]), Ok(2));
+    assert_eq!(instance.call_i32("size", &[]), Ok(3));
+    assert_eq!(
+        instance
+            .call_i32("load", &[i32(3 * 65536 - 2)])
+            .unwrap_err(),
+        WasmStatus::Trap
+    );
+}
+
+#[test]
+fn modules_with_imports_cannot_be_instantiated() {
+    assert_eq!(
+        instantiate(&fixture("imports.wat")).err(),
+        Some(WasmStatus::InstantiateError)
+    );
+    assert!(last_error().contains("log"), "{}", last_error());
+}
+
+#[test]
+fn a_trapping_start_function_fails_instantiation() {
+    assert_eq!(
+        instantiate(&fixture("start_trap.wat")).err(),
+        Some(WasmStatus::InstantiateError)
+    );
+}
+
+#[test]
+fn globals_keep_state_per_instance() {
+    let first = Instance::load("globals.wat");
+    let second = Instance::load("globals.wat");
+    let bump = |instance: &Instance| as_i64(&instance.call("bump", &[], 1).unwrap()[0]);
+
+    assert_eq!(bump(&first), 1);
+    assert_eq!(bump(&first), 2);
+    assert_eq!(bump(&second), 1);
+
+    first.call("set", &[i64(i64::MAX)], 0).unwrap();
+    assert_eq!(bump(&first), i64::MIN);
+
+    let scaled = first.call("scaled", &[f64(4.0)], 1).unwrap();
+    assert_eq!(as_f64(&scaled[0]), 10.0);
+
+    // Exported globals are not functions.
+    assert_eq!(
+        first.call("scale", &[], 0).unwrap_err(),
+        WasmStatus::ExportNotFound
+    );
+}
+
+#[test]
+fn invalid_modules_report_parse_and_compile_errors() {
+    assert_eq!(
+        compile(b"(module (func i32.bogus))").unwrap_err(),
+        WasmStatus::ParseError
+    );
+    assert!(last_error().contains("1:"), "{}", last_error());
+
+    assert_eq!(
+        compile(b"(module (func (result i32) i64.const 0))").unwrap_err(),
+        WasmStatus::CompileError
+    );
+    assert_eq!(
+        compile(b"\0asm\x01\0\0\0\xff").unwrap_err(),
+        WasmStatus::CompileError
+    );
+}
+
+#[test]
+fn truncated_and_corrupted_modules_fail_cleanly() {
+    let wasm = adder::source::wasm_bytes(&fixture("arithmetic.wat"))
+        .unwrap()
+        .into_owned();
+    let truncated = (0..wasm.len()).map(|len| wasm[..len].to_vec());
+    let corrupted = (8..wasm.len()).map(|i| {
+        let mut corrupted = wasm.clone();
+        corrupted[i] ^= 0xff;
+        corrupted
+    });
+    for input in truncated.chain(corrupted) {
+        // Prefixes ending on a section boundary are valid modules.
+        match compile(&input) {
+            Ok(module) => unsafe { wasm_module_delete(module) },
+            Err(status) => assert_ne!(status, WasmStatus::Panic, "{}", last_error()),
+        }
+    }
+}
+
+#[test]
+fn null_pointers_are_rejected_and_deletes_accept_null() {
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        unsafe { wasm_module_new(ptr::null(), 0, &mut module) },
+        WasmStatus::InvalidArgument
+    );
+    assert_eq!(
+        unsafe { wasm_module_new(b"(module)".as_ptr(), 8, ptr::null_mut()) },
+        WasmStatus::InvalidArgument
+    );
+    let mut instance = ptr::null_mut();
+    assert_eq!(
+        unsafe { wasm_instance_new(ptr::null(), &mut instance) },
+        WasmStatus::InvalidArgument
+    );
+    assert_eq!(
+        unsafe {
+            wasm_instance_call(
+                ptr::null_mut(),
+                b"f\0".as_ptr() as *const c_char,
+                ptr::null(),
+                0,
+                ptr::null_mut(),
+                0,
+            )
+        },
+        WasmStatus::InvalidArgument
+    );
+    unsafe {
+        wasm_module_delete(ptr::null_mut());
+        wasm_instance_delete(ptr::null_mut());
+    }
+}
+
+#[test]
+fn errors_are_tracked_per_thread() {
+    assert_eq!(
+        compile(b"(module (func i32.bogus))").unwrap_err(),
+        WasmStatus::ParseError
+    );
+    let message = last_error();
+    std::thread::spawn(|| assert!(wasm_last_error_message().is_null()))
+        .join()
+        .unwrap();
+    assert_eq!(last_error(), message);
+}
+
+#[test]
+fn pools_are_callable_from_many_threads() {
+    let module = compile(&fixture("globals.wat")).unwrap();
+    let mut pool = ptr::null_mut();
+    let status = unsafe {
+        wasm_pool_new(
+            module,
+            1,
+            2,
+            WASM_POOL_RESET_SNAPSHOT,
+            WASM_POOL_BLOCK,
+            &mut pool,
+        )
+    };
+    unsafe { wasm_module_delete(module) };
+    assert_eq!(status, WasmStatus::Ok, "{}", last_error());
+
+    // Raw pointers are not Send; the pool itself may be shared.
+    let address = pool as usize;
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            std::thread::spawn(move || {
+                let name = CString::new("bump").unwrap();
+                let mut result = i64(0);
+                for _ in 0..10 {
+                    let status = unsafe {
+                        wasm_pool_call(
+                            address as *const WasmPool,
+                            name.as_ptr(),
+                            ptr::null(),
+                            0,
+                            &mut result,
+                            1,
+                        )
+                    };
+                    assert_eq!(status, WasmStatus::Ok);
+                    // Every checkout starts from the initial state.
+                    assert_eq!(as_i64(&result), 1);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let missing = CString::new("missing").unwrap();
+    assert_eq!(
+        unsafe { wasm_pool_call(pool, missing.as_ptr(), ptr::null(), 0, ptr::null_mut(), 0) },
+        WasmStatus::ExportNotFound
+    );
+    unsafe { wasm_pool_delete(pool) };
+}
+
+#[test]
+fn pool_arguments_are_validated() {
+    let module = compile(&fixture("arithmetic.wat")).unwrap();
+    let mut pool = ptr::null_mut();
+    let new = |min, max, reset, exhaustion, pool_out| unsafe {
+        wasm_pool_new(module, min, max, reset, exhaustion, pool_out)
+    };
+    assert_eq!(
+        new(2, 1, WASM_POOL_RESET_KEEP, WASM_POOL_FAIL, &mut pool),
+        WasmStatus::InvalidArgument
+    );
+    assert_eq!(
+        new(0, 0, WASM_POOL_RESET_KEEP, WASM_POOL_FAIL, &mut pool),
+        WasmStatus::InvalidArgument
+    );
+    assert_eq!(
+        new(0, 1, 7, WASM_POOL_FAIL, &mut pool),
+        WasmStatus::InvalidArgument
+    );
+    assert_eq!(
+        new(0, 1, WASM_POOL_RESET_KEEP, 7, &mut pool),
+        WasmStatus::InvalidArgument
+    );
+    assert!(pool.is_null());
+    assert_eq!(
+        new(0, 1, WASM_POOL_RESET_KEEP, WASM_POOL_FAIL, &mut pool),
+        WasmStatus::Ok
+    );
+    unsafe {
+        wasm_pool_delete(pool);
+        wasm_pool_delete(ptr::null_mut());
+        wasm_module_delete(module);
+    }
+}
+
+#[test]
+fn pools_map_exports_over_buffers_in_place() {
+    let module = compile(&fixture("filters.wat")).unwrap();
+    let mut pool = ptr::null_mut();
+    let status = unsafe {
+        wasm_pool_new(
+            module,
+            0,
+            4,
+            WASM_POOL_RESET_KEEP,
+            WASM_POOL_BLOCK,
+            &mut pool,
+        )
+    };
+    unsafe { wasm_module_delete(module) };
+    assert_eq!(status, WasmStatus::Ok, "{}", last_error());
+
+    let map = |name: &str, data: &mut [u8], chunk_len| {
+        let name = CString::new(name).unwrap();
+        unsafe {
+            wasm_pool_map(
+                pool,
+                name.as_ptr(),
+                data.as_mut_ptr(),
+                data.len(),
+                chunk_len,
+                4,
+                0,
+            )
+        }
+    };
+    let mut pixels: Vec<u8> = (0..=255).cycle().take(40_000).collect();
+    assert_eq!(map("invert", &mut pixels, 0), WasmStatus::Ok);
+    assert!(pixels
+        .iter()
+        .zip((0..=255u8).cycle())
+        .all(|(&a, b)| a == !b));
+    assert_eq!(map("invert", &mut pixels, 999), WasmStatus::Ok);
+    assert!(pixels.iter().zip((0..=255u8).cycle()).all(|(&a, b)| a == b));
+
+    let unchanged = pixels.clone();
+    assert_eq!(map("length", &mut pixels, 0), WasmStatus::SignatureMismatch);
+    assert!(last_error().contains("length"), "{}", last_error());
+    assert_eq!(pixels, unchanged);
+
+    pixels[0] = 0xff;
+    assert_eq!(map("reject", &mut pixels, 0), WasmStatus::Trap);
+    unsafe { wasm_pool_delete(pool) };
+}
+
+#[test]
+fn linkers_resolve_imports_from_earlier_instances() {
+    let runtime = compile(
+        br#"(module
+            (memory (export "memory") 1)
+            (func (export "double") (param i32) (result i32)
+                (i32.mul (local.get 0) (i32.const 2))))"#,
+    )
+    .unwrap();
+    let feature = compile(
+        br#"(module
+            (import "runtime" "double" (func $double (param i32) (result i32)))
+            (import "runtime" "memory" (memory 1))
+            (func (export "quadruple") (param i32) (result i32)
+                (i32.store (i32.const 0) (call $double (call $double (local.get 0))))
+                (i32.load (i32.const 0))))"#,
+    )
+    .unwrap();
+
+    let mut linker = ptr::null_mut();
+    assert_eq!(unsafe { wasm_linker_new(&mut linker) }, WasmStatus::Ok);
+    let instantiate = |name: &str, module| {
+        let name = CString::new(name).unwrap();
+        unsafe { wasm_linker_instantiate(linker, name.as_ptr(), module) }
+    };
+    assert_eq!(
+        instantiate("feature", feature),
+        WasmStatus::InstantiateError
+    );
+    assert_eq!(instantiate("runtime", runtime), WasmStatus::Ok);
+    assert_eq!(instantiate("feature", feature), WasmStatus::Ok);
+    assert_eq!(
+        instantiate("feature", feature),
+        WasmStatus::InstantiateError
+    );
+    unsafe {
+        wasm_module_delete(runtime);
+        wasm_module_delete(feature);
+    }
+
+    let call = |instance: &str, name: &str, args: &[WasmValue]| {
+        let instance = CString::new(instance).unwrap();
+        let name = CString::new(name).unwrap();
+        let mut result = i32(0);
+        let status = unsafe {
+            wasm_linker_call(
+                linker,
+                instance.as_ptr(),
+                name.as_ptr(),
+                args.as_ptr(),
+                args.len(),
+                &mut result,
+                1,
+            )
+        };
+        (status, as_i32(&result))
+    };
+    assert_eq!(
+        call("feature", "quadruple", &[i32(5)]),
+        (WasmStatus::Ok, 20)
+    );
+    assert_eq!(call("runtime", "double", &[i32(5)]), (WasmStatus::Ok, 10));
+    assert_eq!(call("missing", "double", &[i32(5)]).0, WasmStatus::NotFound);
+    assert!(last_error().contains("missing"), "{}", last_error());
+    assert_eq!(
+        call("runtime", "triple", &[i32(5)]).0,
+        WasmStatus::ExportNotFound
+    );
+
+    unsafe {
+        wasm_linker_delete(linker);
+        wasm_linker_delete(ptr::null_mut());
+    }
+}
+
+#[test]
+fn registries_look_up_modules_and_instances_by_name() {
+    let mut registry = ptr::null_mut();
+    assert_eq!(unsafe { wasm_registry_new(&mut registry) }, WasmStatus::Ok);
+    let c = |text: &str| CString::new(text).unwrap();
+
+    let module = compile(&fixture("globals.wat")).unwrap();
+    let register = |version: &str| unsafe {
+        wasm_registry_register_module(registry, c("globals").as_ptr(), c(version).as_ptr(), module)
+    };
+    assert_eq!(register("1.0.0"), WasmStatus::Ok);
+    assert_eq!(register("1.2.0"), WasmStatus::Ok);
+    assert_eq!(register("1.2.0"), WasmStatus::InvalidArgument);
+    assert_eq!(register("1.2"), WasmStatus::InvalidArgument);
+    unsafe { wasm_module_delete(module) };
+
+    let mut found = ptr::null_mut();
+    assert_eq!(
+        unsafe { wasm_registry_module(registry, c("globals").as_ptr(), ptr::null(), &mut found) },
+        WasmStatus::Ok
+    );
+    assert_eq!(
+        unsafe { wasm_registry_module(registry, c("missing").as_ptr(), ptr::null(), &mut found) },
+        WasmStatus::NotFound
+    );
+
+    let mut instance = ptr::null_mut();
+    assert_eq!(
+        unsafe { wasm_instance_new(found, &mut instance) },
+        WasmStatus::Ok
+    );
+    unsafe { wasm_module_delete(found) };
+    assert_eq!(
+        unsafe {
+            wasm_registry_register_instance(
+                registry,
+                c("counter").as_ptr(),
+                c("0.1.0").as_ptr(),
+                instance,
+            )
+        },
+        WasmStatus::Ok
+    );
+    let bump = |version: *const c_char| {
+        let mut result = i64(0);
+        let status = unsafe {
+            wasm_registry_call(
+                registry,
+                c("counter").as_ptr(),
+                version,
+                c("bump").as_ptr(),
+                ptr::null(),
+                0,
+                &mut result,
+                1,
+            )
+        };
+        (status, as_i64(&result))
+    };
+    assert_eq!(bump(ptr::null()), (WasmStatus::Ok, 1));
+    assert_eq!(bump(c("0.1.0").as_ptr()), (WasmStatus::Ok, 2));
+    assert_eq!(bump(c("0.2.0").as_ptr()).0, WasmStatus::NotFound);
+
+    let list = |kind| {
+        let mut len = 0;
+        assert_eq!(
+            unsafe { wasm_registry_list(registry, kind, ptr::null_mut(), 0, &mut len) },
+            WasmStatus::InvalidArgument
+        );
+        let mut buffer = vec![0u8; len];
+        assert_eq!(
+            unsafe {
+                wasm_registry_list(
+                    registry,
+                    kind,
+                    buffer.as_mut_ptr() as *mut c_char,
+                    len,
+                    &mut len,
+                )
+            },
+            WasmStatus::Ok
+        );
+        CStr::from_bytes_with_nul(&buffer)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        list(WASM_REGISTRY_MODULES),
+        "globals 1.0.0\nglobals 1.2.0\n"
+    );
+    assert_eq!(list(WASM_REGISTRY_INSTANCES), "counter 0.1.0\n");
+
+    let unload = |name: &str, version: &str| unsafe {
+        wasm_registry_unload_instance(registry, c(name).as_ptr(), c(version).as_ptr())
+    };
+    assert_eq!(unload("counter", "0.1.0"), WasmStatus::Ok);
+    assert_eq!(unload("counter", "0.1.0"), WasmStatus::NotFound);
+    assert_eq!(
+        unsafe {
+            wasm_registry_unload_module(registry, c("globals").as_ptr(), c("1.0.0").as_ptr())
+        },
+        WasmStatus::Ok
+    );
+    assert_eq!(list(WASM_REGISTRY_MODULES), "globals 1.2.0\n");
+
+    unsafe {
+        wasm_registry_delete(registry);
+        wasm_registry_delete(ptr::null_mut());
+    }
+}
+
+#[test]
+fn linkers_can_resolve_imports_from_a_registry() {
+    let mut registry = ptr::null_mut();
+    let mut linker = ptr::null_mut();
+    let runtime = compile(&fixture("arithmetic.wat")).unwrap();
+    let feature = compile(
+        br#"(module
+            (import "arithmetic" "add_one" (func $add_one (param i32) (result i32)))
+            (func (export "add_two") (param i32) (result i32)
+                (call $add_one (call $add_one (local.get 0)))))"#,
+    )
+    .unwrap();
+    let c = |text: &str| CString::new(text).unwrap();
+    unsafe {
+        assert_eq!(wasm_registry_new(&mut registry), WasmStatus::Ok);
+        assert_eq!(
+            wasm_registry_register_module(
+                registry,
+                c("arithmetic").as_ptr(),
+                c("1.0.0").as_ptr(),
+                runtime
+            ),
+            WasmStatus::Ok
+        );
+        assert_eq!(wasm_linker_new(&mut linker), WasmStatus::Ok);
+        assert_eq!(wasm_linker_use_registry(linker, registry), WasmStatus::Ok);
+        // The linker keeps its own reference to the registry.
+        wasm_registry_delete(registry);
+        assert_eq!(
+            wasm_linker_instantiate(linker, c("feature").as_ptr(), feature),
+            WasmStatus::Ok
+        );
+        let mut result = i32(0);
+        assert_eq!(
+            wasm_linker_call(
+                linker,
+                c("feature").as_ptr(),
+                c("add_two").as_ptr(),
+                &i32(40),
+                1,
+                &mut result,
+                1
+            ),
+            WasmStatus::Ok
+        );
+        assert_eq!(as_i32(&result), 42);
+        wasm_linker_delete(linker);
+        wasm_module_delete(runtime);
+        wasm_module_delete(feature);
+    }
+}
+
+#[test]
+fn instances_reload_keeping_compatible_state() {
+    let instance = Instance::load("globals.wat");
+    let bump = |instance: &Instance| as_i64(&instance.call("bump", &[], 1).unwrap()[0]);
+    bump(&instance);
+    bump(&instance);
+
+    let rebuilt = String::from_utf8(fixture("globals.wat"))
+        .unwrap()
+        .replace("(func (export \"set\")", "(func (export \"reset\")");
+    let mut carried = false;
+    let mut report = ptr::null_mut();
+    unsafe {
+        assert_eq!(
+            wasm_instance_reload(
+                instance.0,
+                rebuilt.as_ptr(),
+                rebuilt.len(),
+                &mut carried,
+                &mut report
+            ),
+            WasmStatus::Ok
+        );
+        assert_eq!(
+            CStr::from_ptr(report).to_str().unwrap(),
+            "+ reset: func (i64) -> ()\n\
+             - set: func (i64) -> ()\n\
+             state reset: export `set` was removed"
+        );
+        wasm_string_delete(report);
+    }
+    assert!(!carried);
+    assert_eq!(bump(&instance), 1);
+
+    // The report is optional.
+    let status = unsafe {
+        wasm_instance_reload(
+            instance.0,
+            rebuilt.as_ptr(),
+            rebuilt.len(),
+            &mut carried,
+            ptr::null_mut(),
+        )
+    };
+    assert_eq!(status, WasmStatus::Ok);
+    assert!(carried);
+    assert_eq!(bump(&instance), 2);
+
+    let status = unsafe {
+        wasm_instance_reload(
+            instance.0,
+            b"\0asm".as_ptr(),
+            4,
+            &mut carried,
+            ptr::null_mut(),
+        )
+    };
+    assert_eq!(status, WasmStatus::CompileError, "{}", last_error());
+    assert_eq!(bump(&instance), 3);
+    unsafe { wasm_string_delete(ptr::null_mut()) };
+}
+
+#[test]
+fn modules_compile_from_streamed_chunks() {
+    let wasm = adder::source::wasm_bytes(&fixture("arithmetic.wat"))
+        .unwrap()
+        .into_owned();
+    let mut stream = ptr::null_mut();
+    let mut module = ptr::null_mut();
+    unsafe {
+        assert_eq!(wasm_stream_new(wasm.len(), &mut stream), WasmStatus::Ok);
+        for chunk in wasm.chunks(5) {
+            assert_eq!(
+                wasm_stream_push(stream, chunk.as_ptr(), chunk.len()),
+                WasmStatus::Ok
+            );
+        }
+        assert_eq!(wasm_stream_finish(stream, &mut module), WasmStatus::Ok);
+        let mut instance = ptr::null_mut();
+        assert_eq!(wasm_instance_new(module, &mut instance), WasmStatus::Ok);
+        wasm_module_delete(module);
+        assert_eq!(Instance(instance).call_i32("add_one", &[i32(1)]), Ok(2));
+
+        // A bad header fails the first push, and the stream stays failed.
+        assert_eq!(wasm_stream_new(0, &mut stream), WasmStatus::Ok);
+        let text = b"(module)";
+        assert_eq!(
+            wasm_stream_push(stream, text.as_ptr(), text.len()),
+            WasmStatus::CompileError
+        );
+        assert!(last_error().contains("magic header"), "{}", last_error());
+        assert_eq!(
+            wasm_stream_push(stream, wasm.as_ptr(), wasm.len()),
+            WasmStatus::CompileError
+        );
+        module = ptr::null_mut();
+        assert_eq!(
+            wasm_stream_finish(stream, &mut module),
+            WasmStatus::CompileError
+        );
+        assert!(module.is_null());
+
+        // A truncated module fails when finished.
+        assert_eq!(wasm_stream_new(0, &mut stream), WasmStatus::Ok);
+        assert_eq!(wasm_stream_push(stream, wasm.as_ptr(), 10), WasmStatus::Ok);
+        assert_eq!(
+            wasm_stream_finish(stream, &mut module),
+            WasmStatus::CompileError
+        );
+
+        assert_eq!(wasm_stream_new(0, &mut stream), WasmStatus::Ok);
+        assert_eq!(
+            wasm_stream_push(stream, ptr::null(), 0),
+            WasmStatus::InvalidArgument
+        );
+        wasm_stream_delete(stream);
+        wasm_stream_delete(ptr::null_mut());
+    }
+}
@@ -0,0 +1,22 @@
+(module
+  (func (export "add_one") (param i32) (result i32)
+    local.get 0
+    i32.const 1
+    i32.add)
+  (func (export "add_i64") (param i64 i64) (result i64)
+    local.get 0
+    local.get 1
+    i64.add)
+  (func (export "mul_f32") (param f32 f32) (result f32)
+    local.get 0
+    local.get 1
+    f32.mul)
+  (func (export "div_f64") (param f64 f64) (result f64)
+    local.get 0
+    local.get 1
+    f64.div)
+  (func (export "div_u") (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.div_u)
+  (func (export "nothing")))
@@ -0,0 +1,40 @@
+(module
+  (memory (export "memory") 2)
+  (global $next (mut i32) (i32.const 1024))
+  (global $freed (mut i32) (i32.const 0))
+  (func (export "alloc") (param $len i32) (result i32)
+    global.get $next
+    global.get $next
+    local.get $len
+    i32.add
+    global.set $next)
+  ;; Frees the most recent allocation.
+  (func (export "dealloc") (param $ptr i32) (param i32)
+    local.get $ptr
+    global.set $next
+    global.get $freed
+    i32.const 1
+    i32.add
+    global.set $freed)
+  (func (export "freed") (result i32)
+    global.get $freed)
+  ;; Inverts every byte in place.
+  (func (export "invert") (param $ptr i32) (param $len i32)
+    (loop $next
+      (if (local.get $len)
+        (then
+          (i32.store8 (local.get $ptr)
+            (i32.xor (i32.load8_u (local.get $ptr)) (i32.const 255)))
+          (local.set $ptr (i32.add (local.get $ptr) (i32.const 1)))
+          (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+          (br $next)))))
+  ;; Replaces the chunk with its length as one byte.
+  (func (export "length") (param $ptr i32) (param $len i32) (result i32)
+    (i32.store8 (local.get $ptr) (local.get $len))
+    i32.const 1)
+  ;; Traps on a chunk starting with 0xff.
+  (func (export "reject") (param $ptr i32) (param $len i32)
+    (if (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 255))
+      (then unreachable)))
+  (func (export "negative") (param i32 i32) (result i32)
+    i32.const -1))
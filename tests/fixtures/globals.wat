@@ -0,0 +1,16 @@
+(module
+  (global $counter (mut i64) (i64.const 0))
+  (global $scale (export "scale") f64 (f64.const 2.5))
+  (func (export "bump") (result i64)
+    global.get $counter
+    i64.const 1
+    i64.add
+    global.set $counter
+    global.get $counter)
+  (func (export "set") (param i64)
+    local.get 0
+    global.set $counter)
+  (func (export "scaled") (param f64) (result f64)
+    local.get 0
+    global.get $scale
+    f64.mul))
@@ -0,0 +1,5 @@
+(module
+  (import "env" "log" (func $log (param i32)))
+  (func (export "run")
+    i32.const 1
+    call $log))
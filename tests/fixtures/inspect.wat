@@ -0,0 +1,17 @@
+(module
+  (@producers
+    (language "Rust" "1.0")
+    (processed-by "wasm-bindgen" "0.2")
+    (processed-by "walrus" "0.1"))
+  (@custom "notes" "hello")
+  (type $pair (func (param i32 i64) (result f32)))
+  (import "env" "log" (func $log (param i32)))
+  (import "env" "memory" (memory 1 2))
+  (import "env" "base" (global $base i32))
+  (table (export "table") 2 10 funcref)
+  (memory (export "heap") 3)
+  (global $counter (export "counter") (mut i64) (i64.const 0))
+  (func (export "pair") (type $pair) (f32.const 0))
+  (func (export "nothing"))
+  (export "log" (func $log))
+  (export "base" (global $base)))
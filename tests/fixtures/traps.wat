@@ -0,0 +1,21 @@
+(module
+  (table 2 funcref)
+  (type $void (func))
+  (func (export "unreachable")
+    unreachable)
+  (func (export "div_by_zero") (param i32) (result i32)
+    local.get 0
+    i32.const 0
+    i32.div_s)
+  (func (export "overflow") (result i32)
+    i32.const 0x80000000
+    i32.const -1
+    i32.div_s)
+  (func (export "bad_truncate") (param f32) (result i32)
+    local.get 0
+    i32.trunc_f32_s)
+  (func (export "null_call")
+    i32.const 1
+    call_indirect (type $void))
+  (func (export "ok") (result i32)
+    i32.const 7))
@@ -0,0 +1,33 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const HEADER: &str = "include/adder.h";
+
+/// Fails when `include/adder.h` no longer matches the `extern "C"` API.
+/// Run with `UPDATE_HEADER=1` to regenerate it.
+#[test]
+fn c_header_is_up_to_date() {
+    let crate_dir = env!("CARGO_MANIFEST_DIR");
+    let config = cbindgen::Config::from_file(Path::new(crate_dir).join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_crate(crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("could not generate the C header")
+        .write(&mut generated);
+    let generated = String::from_utf8(generated).unwrap();
+
+    let path = Path::new(crate_dir).join(HEADER);
+    if env::var_os("UPDATE_HEADER").is_some() {
+        fs::write(&path, &generated).unwrap();
+        return;
+    }
+    let checked_in = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        checked_in == generated,
+        "{} is stale; regenerate it with `UPDATE_HEADER=1 cargo test --test header`",
+        HEADER
+    );
+}
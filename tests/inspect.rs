@@ -0,0 +1,195 @@
+use adder::inspect::{self, ModuleReport};
+use adder::source;
+use std::path::Path;
+
+fn report(fixture: &str) -> ModuleReport {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(fixture);
+    let input = std::fs::read(path).unwrap();
+    inspect::inspect(&source::wasm_bytes(&input).unwrap()).unwrap()
+}
+
+fn fixture() -> ModuleReport {
+    report("tests/fixtures/inspect.wat")
+}
+
+#[test]
+fn imports_and_exports_are_listed_with_their_types() {
+    let report = fixture();
+    let imports: Vec<_> = report
+        .imports
+        .iter()
+        .map(|import| {
+            (
+                import.kind,
+                import.module.as_str(),
+                import.name.as_str(),
+                import.ty.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        imports,
+        [
+            ("func", "env", "log", "(i32) -> ()"),
+            ("memory", "env", "memory", "1..2"),
+            ("global", "env", "base", "i32"),
+        ]
+    );
+
+    let exports: Vec<_> = report
+        .exports
+        .iter()
+        .map(|export| (export.kind, export.name.as_str(), export.ty.as_str()))
+        .collect();
+    assert_eq!(
+        exports,
+        [
+            ("table", "table", "2..10"),
+            ("memory", "heap", "3.."),
+            ("global", "counter", "mut i64"),
+            ("func", "pair", "(i32, i64) -> (f32)"),
+            ("func", "nothing", "() -> ()"),
+            // Re-exported imports keep their imported types.
+            ("func", "log", "(i32) -> ()"),
+            ("global", "base", "i32"),
+        ]
+    );
+}
+
+#[test]
+fn limits_count_imported_memories_first() {
+    let report = fixture();
+    let memories: Vec<_> = report
+        .memories
+        .iter()
+        .map(|memory| {
+            (
+                memory.index,
+                memory.imported,
+                memory.minimum,
+                memory.maximum,
+            )
+        })
+        .collect();
+    assert_eq!(memories, [(0, true, 1, Some(2)), (1, false, 3, None)]);
+    let tables: Vec<_> = report
+        .tables
+        .iter()
+        .map(|table| (table.index, table.imported, table.minimum, table.maximum))
+        .collect();
+    assert_eq!(tables, [(0, false, 2, Some(10))]);
+}
+
+#[test]
+fn custom_and_producers_sections_are_read() {
+    let report = fixture();
+    let custom: Vec<_> = report
+        .custom_sections
+        .iter()
+        .map(|section| (section.name.as_str(), section.size))
+        .collect();
+    assert!(custom.contains(&("notes", 5)), "{:?}", custom);
+    assert!(custom.iter().any(|(name, _)| *name == "producers"));
+
+    let producers: Vec<_> = report
+        .producers
+        .iter()
+        .map(|field| {
+            let values: Vec<_> = field
+                .values
+                .iter()
+                .map(|value| format!("{} {}", value.name, value.version))
+                .collect();
+            (field.name.as_str(), values)
+        })
+        .collect();
+    assert_eq!(
+        producers,
+        [
+            ("language", vec!["Rust 1.0".to_string()]),
+            (
+                "processed-by",
+                vec!["wasm-bindgen 0.2".to_string(), "walrus 0.1".to_string()]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn section_sizes_add_up_to_the_module_size() {
+    // A data section over 127 bytes needs a two-byte length.
+    let big = format!(
+        r#"(module (memory 1) (data (i32.const 0) "{}"))"#,
+        "x".repeat(300)
+    );
+    let big = inspect::inspect(&source::wasm_bytes(big.as_bytes()).unwrap()).unwrap();
+    let data = big.sections.iter().find(|section| section.name == "data");
+    // One byte of id, two of length, then the segment.
+    assert_eq!(data.unwrap().size, 1 + 2 + 1 + 1 + 3 + 2 + 300);
+
+    for report in [fixture(), report("src/add.wasm"), big] {
+        assert_eq!(report.sections[0].name, "header");
+        assert_eq!(report.sections[0].size, 8);
+        let total: usize = report.sections.iter().map(|section| section.size).sum();
+        assert_eq!(total, report.size);
+    }
+
+    let names: Vec<_> = fixture()
+        .sections
+        .iter()
+        .map(|section| section.name.clone())
+        .collect();
+    assert_eq!(
+        &names[..9],
+        ["header", "type", "import", "function", "table", "memory", "global", "export", "code"]
+    );
+    assert!(names.contains(&"custom \"notes\"".to_string()));
+}
+
+#[test]
+fn reports_render_as_json() {
+    let report = fixture();
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["size"], report.size);
+    assert_eq!(
+        json["imports"][1],
+        serde_json::json!({
+            "module": "env",
+            "name": "memory",
+            "kind": "memory",
+            "type": "1..2",
+        })
+    );
+    assert_eq!(
+        json["exports"][3],
+        serde_json::json!({ "name": "pair", "kind": "func", "type": "(i32, i64) -> (f32)" })
+    );
+    assert_eq!(
+        json["memories"][1],
+        serde_json::json!({
+            "index": 1,
+            "imported": false,
+            "minimum": 3,
+            "maximum": null,
+            "shared": false,
+        })
+    );
+    assert_eq!(
+        json["producers"][1]["values"][1],
+        serde_json::json!({ "name": "walrus", "version": "0.1" })
+    );
+    assert_eq!(
+        json["sections"][0],
+        serde_json::json!({ "name": "header", "size": 8 })
+    );
+}
+
+#[test]
+fn reports_render_as_text() {
+    let text = fixture().to_string();
+    assert!(text.starts_with("size: 290 bytes\n\nimports (3):\n  func env.log (i32) -> ()\n"));
+    assert!(text.contains("\nmemories (2):\n  0: 1..2 pages, imported\n  1: 3.. pages\n"));
+    assert!(text.contains("\ntables (1):\n  0: 2..10 elements\n"));
+    assert!(text.contains("\n  processed-by: wasm-bindgen 0.2, walrus 0.1\n"));
+    assert!(text.contains("\n  header                            8 bytes   2.8%\n"));
+}
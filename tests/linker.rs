@@ -0,0 +1,193 @@
+use adder::{func, imports, Engine, Error, Linker, Module, Value};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+const RUNTIME: &str = r#"(module
+    (memory (export "memory") 1)
+    (global (export "version") i32 (i32.const 3))
+    (table (export "table") 2 funcref)
+    (elem (i32.const 0) $double $negate)
+    (global $next (mut i32) (i32.const 16))
+    (func $double (param i32) (result i32)
+        (i32.mul (local.get 0) (i32.const 2)))
+    (func $negate (param i32) (result i32)
+        (i32.sub (i32.const 0) (local.get 0)))
+    (func (export "alloc") (param i32) (result i32)
+        (global.get $next)
+        (global.set $next (i32.add (global.get $next) (local.get 0))))
+    (func (export "load") (param i32) (result i32)
+        (i32.load (local.get 0))))"#;
+
+const FEATURE: &str = r#"(module
+    (import "runtime" "memory" (memory 1))
+    (import "runtime" "version" (global $version i32))
+    (import "runtime" "table" (table 2 funcref))
+    (import "runtime" "alloc" (func $alloc (param i32) (result i32)))
+    (import "env" "log" (func $log (param i32)))
+    (func (export "store") (param i32) (result i32)
+        (local $ptr i32)
+        (local.set $ptr (call $alloc (i32.const 4)))
+        (i32.store (local.get $ptr) (local.get 0))
+        (local.get $ptr))
+    (func (export "version") (result i32)
+        (global.get $version))
+    (func (export "apply") (param i32 i32) (result i32)
+        (call $log (local.get 1))
+        (call_indirect (param i32) (result i32) (local.get 0) (local.get 1))))"#;
+
+fn compile(wat: &str) -> Module {
+    Engine::new().compile(wat.as_bytes()).unwrap()
+}
+
+fn linker(logged: Arc<AtomicI32>) -> Linker {
+    let mut linker = Linker::new();
+    linker
+        .imports(imports! {
+            "env" => {
+                "log" => func!(move |x: i32| { logged.fetch_add(x + 1, Ordering::SeqCst); }),
+            },
+        })
+        .unwrap();
+    linker.instantiate("runtime", &compile(RUNTIME)).unwrap();
+    linker
+}
+
+#[test]
+fn functions_memories_globals_and_tables_are_shared() {
+    let logged = Arc::new(AtomicI32::new(0));
+    let mut linker = linker(logged.clone());
+    let feature = linker.instantiate("feature", &compile(FEATURE)).unwrap();
+
+    // The feature allocates through the runtime and writes to its memory.
+    let ptr = feature.call("store", &[Value::I32(1234)]).unwrap();
+    assert_eq!(ptr, [Value::I32(16)]);
+    assert_eq!(
+        feature.call("store", &[Value::I32(5)]).unwrap(),
+        [Value::I32(20)]
+    );
+    let runtime = linker.get("runtime").unwrap();
+    assert_eq!(runtime.call("load", &ptr).unwrap(), [Value::I32(1234)]);
+
+    let feature = linker.get("feature").unwrap();
+    assert_eq!(feature.call("version", &[]).unwrap(), [Value::I32(3)]);
+    assert_eq!(
+        feature
+            .call("apply", &[Value::I32(7), Value::I32(0)])
+            .unwrap(),
+        [Value::I32(14)]
+    );
+    assert_eq!(
+        feature
+            .call("apply", &[Value::I32(7), Value::I32(1)])
+            .unwrap(),
+        [Value::I32(-7)]
+    );
+    assert_eq!(logged.load(Ordering::SeqCst), 3, "host imports are kept");
+    assert_eq!(linker.names().collect::<Vec<_>>(), ["runtime", "feature"]);
+}
+
+#[test]
+fn linked_instances_can_be_linked_again() {
+    let mut linker = Linker::new();
+    linker
+        .instantiate(
+            "a",
+            &compile(r#"(module (func (export "one") (result i32) i32.const 1))"#),
+        )
+        .unwrap();
+    linker
+        .instantiate(
+            "b",
+            &compile(
+                r#"(module
+                    (import "a" "one" (func $one (result i32)))
+                    (func (export "two") (result i32)
+                        (i32.add (call $one) (call $one))))"#,
+            ),
+        )
+        .unwrap();
+    let c = linker
+        .instantiate(
+            "c",
+            &compile(
+                r#"(module
+                    (import "a" "one" (func $one (result i32)))
+                    (import "b" "two" (func $two (result i32)))
+                    (func (export "three") (result i32)
+                        (i32.add (call $one) (call $two))))"#,
+            ),
+        )
+        .unwrap();
+    assert_eq!(c.call("three", &[]).unwrap(), [Value::I32(3)]);
+
+    // The linker, and with it every linked instance, can move threads.
+    let three = std::thread::spawn(move || linker.get("c").unwrap().call("three", &[]))
+        .join()
+        .unwrap();
+    assert_eq!(three, Ok(vec![Value::I32(3)]));
+}
+
+#[test]
+fn instances_created_elsewhere_can_be_linked() {
+    let runtime = compile(RUNTIME).instantiate().unwrap();
+    runtime.write_memory(16, &42i32.to_le_bytes()).unwrap();
+    let mut linker = Linker::new();
+    linker.instance("runtime", runtime).unwrap();
+    let reader = linker
+        .instantiate(
+            "reader",
+            &compile(
+                r#"(module
+                    (import "runtime" "memory" (memory 1))
+                    (func (export "read") (result i32) (i32.load (i32.const 16))))"#,
+            ),
+        )
+        .unwrap();
+    assert_eq!(reader.call("read", &[]).unwrap(), [Value::I32(42)]);
+}
+
+#[test]
+fn unresolved_and_mismatched_imports_fail_instantiation() {
+    let mut linker = Linker::new();
+    linker.instantiate("runtime", &compile(RUNTIME)).unwrap();
+    // `env.log` is not defined.
+    assert!(matches!(
+        linker.instantiate("feature", &compile(FEATURE)),
+        Err(Error::Instantiate(_))
+    ));
+    let wrong_signature = r#"(module
+        (import "runtime" "alloc" (func (param i64) (result i32))))"#;
+    assert!(matches!(
+        linker.instantiate("feature", &compile(wrong_signature)),
+        Err(Error::Instantiate(_))
+    ));
+    let missing_export = r#"(module (import "runtime" "free" (func (param i32))))"#;
+    assert!(matches!(
+        linker.instantiate("feature", &compile(missing_export)),
+        Err(Error::Instantiate(_))
+    ));
+    assert!(linker.get("feature").is_none());
+}
+
+#[test]
+fn names_must_be_unique() {
+    let mut linker = linker(Arc::new(AtomicI32::new(0)));
+    let empty = compile("(module)");
+    assert_eq!(
+        linker.instantiate("runtime", &empty).err(),
+        Some(Error::Instantiate(
+            "an instance is already linked as `runtime`".to_string()
+        ))
+    );
+    assert_eq!(
+        linker.instantiate("env", &empty).err(),
+        Some(Error::Instantiate(
+            "`env` is already a namespace of host imports".to_string()
+        ))
+    );
+    assert!(matches!(
+        linker.imports(imports! { "runtime" => { "log" => func!(|| {}), }, }),
+        Err(Error::Instantiate(_))
+    ));
+    linker.instantiate("empty", &empty).unwrap();
+}
@@ -0,0 +1,133 @@
+use adder::pool::{Exhaustion, Pool, PoolError};
+use adder::{func, imports, Engine, Error, Module, Value};
+use std::sync::{Arc, Barrier};
+
+const FILTERS: &str = include_str!("fixtures/filters.wat");
+
+fn module(wat: &str) -> Module {
+    Engine::new().compile(wat.as_bytes()).unwrap()
+}
+
+fn pool(max: usize) -> Pool {
+    Pool::builder(module(FILTERS)).max(max).build().unwrap()
+}
+
+fn input(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+#[test]
+fn outputs_are_joined_in_input_order() {
+    let pool = pool(4);
+    let input = input(100_003);
+    let expected: Vec<u8> = input.iter().map(|byte| !byte).collect();
+    for &(workers, chunk_len) in &[(1, 100_003), (4, 1000), (4, 7), (8, 40_000)] {
+        let output = pool
+            .map("invert")
+            .workers(workers)
+            .chunk_len(chunk_len)
+            .run(&input)
+            .unwrap();
+        assert!(
+            output == expected,
+            "{} workers, {} bytes",
+            workers,
+            chunk_len
+        );
+    }
+    assert_eq!(pool.map("invert").run(&[]), Ok(vec![]));
+}
+
+#[test]
+fn chunks_are_split_at_aligned_offsets() {
+    let pool = pool(2);
+    let lengths = |map: adder::parallel::Map| map.run(&input(30)).unwrap();
+    assert_eq!(lengths(pool.map("length").workers(4)), [8, 8, 8, 6]);
+    assert_eq!(
+        lengths(pool.map("length").chunk_len(5).align(4)),
+        [8, 8, 8, 6]
+    );
+    assert_eq!(lengths(pool.map("length").chunk_len(12).align(5)), [15, 15]);
+}
+
+#[test]
+fn each_worker_runs_on_its_own_instance_at_the_same_time() {
+    const WAITING: &str = r#"(module
+        (import "env" "wait" (func $wait))
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32) i32.const 0)
+        (func (export "wait") (param i32 i32) call $wait))"#;
+    let workers = 4;
+    let barrier = Arc::new(Barrier::new(workers));
+    let pool = Pool::builder(module(WAITING))
+        .min(0)
+        .max(workers)
+        .imports(move || {
+            let barrier = barrier.clone();
+            imports! { "env" => { "wait" => func!(move || { barrier.wait(); }), }, }
+        })
+        .build()
+        .unwrap();
+    // Each chunk waits for the others, so this only finishes if all four
+    // run concurrently.
+    let input = input(4096);
+    assert_eq!(pool.map("wait").workers(workers).run(&input), Ok(input));
+    assert_eq!(pool.size(), workers);
+}
+
+#[test]
+fn buffers_are_freed_with_dealloc() {
+    let pool = pool(1);
+    pool.map("invert")
+        .workers(1)
+        .chunk_len(10)
+        .run(&input(100))
+        .unwrap();
+    pool.map("invert").workers(1).run(&input(100)).unwrap();
+    assert_eq!(pool.call("freed", &[]), Ok(vec![Value::I32(2)]));
+}
+
+#[test]
+fn workers_without_an_instance_sit_out() {
+    let pool = Pool::builder(module(FILTERS))
+        .max(1)
+        .on_exhaustion(Exhaustion::Fail)
+        .build()
+        .unwrap();
+    let input = input(1000);
+    let output = pool
+        .map("invert")
+        .workers(4)
+        .chunk_len(10)
+        .run(&input)
+        .unwrap();
+    assert_eq!(output.len(), input.len());
+
+    let _held = pool.get().unwrap();
+    assert_eq!(pool.map("invert").run(&input), Err(PoolError::Exhausted));
+}
+
+#[test]
+fn the_first_failure_stops_the_run() {
+    let pool = pool(4);
+    let mut input = input(10_000);
+    input[5000] = 0xff;
+    assert!(matches!(
+        pool.map("reject").chunk_len(100).run(&input),
+        Err(PoolError::Call(Error::Trap(_)))
+    ));
+    assert!(matches!(
+        pool.map("negative").run(&input),
+        Err(PoolError::Call(Error::Memory(_)))
+    ));
+    assert!(matches!(
+        pool.map("missing").run(&input),
+        Err(PoolError::Call(Error::ExportNotFound(_)))
+    ));
+    assert!(matches!(
+        pool.map("invert").alloc("freed").run(&input),
+        Err(PoolError::Call(Error::SignatureMismatch(_)))
+    ));
+    // The pool is still usable afterwards.
+    assert!(pool.map("invert").run(&input).is_ok());
+}
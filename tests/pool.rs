@@ -0,0 +1,181 @@
+use adder::pool::{Exhaustion, Pool, PoolError, Reset};
+use adder::{func, imports, Engine, Module, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+
+const COUNTER: &str = r#"(module
+    (import "env" "id" (func $id (result i32)))
+    (memory (export "memory") 1)
+    (global $count (mut i32) (i32.const 0))
+    (func (export "bump") (result i32)
+        (global.set $count (i32.add (global.get $count) (i32.const 1)))
+        (i32.store (i32.const 0) (global.get $count))
+        (global.get $count))
+    (func (export "stored") (result i32)
+        (i32.load (i32.const 0)))
+    (func (export "grow") (result i32)
+        (memory.grow (i32.const 1)))
+    (func (export "id") (result i32)
+        (call $id)))"#;
+
+fn module() -> Module {
+    Engine::new().compile(COUNTER.as_bytes()).unwrap()
+}
+
+/// A pool whose instances each get a distinct `env.id`, counting how many
+/// have been created.
+fn counting_pool(
+    min: usize,
+    max: usize,
+    reset: Reset,
+    exhaustion: Exhaustion,
+) -> (Pool, Arc<AtomicUsize>) {
+    let created = Arc::new(AtomicUsize::new(0));
+    let counter = created.clone();
+    let pool = Pool::builder(module())
+        .min(min)
+        .max(max)
+        .reset(reset)
+        .on_exhaustion(exhaustion)
+        .imports(move || {
+            let id = counter.fetch_add(1, Ordering::SeqCst) as i32;
+            imports! { "env" => { "id" => func!(move || -> i32 { id }), }, }
+        })
+        .build()
+        .unwrap();
+    (pool, created)
+}
+
+fn i32(results: Vec<Value>) -> i32 {
+    match results[..] {
+        [Value::I32(x)] => x,
+        ref other => panic!("unexpected results {:?}", other),
+    }
+}
+
+#[test]
+fn min_instances_are_created_up_front() {
+    let (pool, created) = counting_pool(3, 4, Reset::Keep, Exhaustion::Block);
+    assert_eq!(created.load(Ordering::SeqCst), 3);
+    assert_eq!((pool.size(), pool.idle()), (3, 3));
+
+    let (lazy, created) = counting_pool(0, 2, Reset::Keep, Exhaustion::Block);
+    assert_eq!(created.load(Ordering::SeqCst), 0);
+    assert_eq!(i32(lazy.call("bump", &[]).unwrap()), 1);
+    assert_eq!((lazy.size(), lazy.idle()), (1, 1));
+}
+
+#[test]
+fn keep_carries_state_between_checkouts() {
+    let (pool, _) = counting_pool(1, 1, Reset::Keep, Exhaustion::Block);
+    assert_eq!(i32(pool.call("bump", &[]).unwrap()), 1);
+    assert_eq!(i32(pool.call("bump", &[]).unwrap()), 2);
+    assert_eq!(i32(pool.call("stored", &[]).unwrap()), 2);
+}
+
+#[test]
+fn snapshot_restores_globals_and_memory() {
+    let (pool, created) = counting_pool(1, 1, Reset::Snapshot, Exhaustion::Block);
+    {
+        let instance = pool.get().unwrap();
+        assert_eq!(i32(instance.call("bump", &[]).unwrap()), 1);
+        assert_eq!(i32(instance.call("bump", &[]).unwrap()), 2);
+    }
+    assert_eq!(i32(pool.call("stored", &[]).unwrap()), 0);
+    assert_eq!(i32(pool.call("bump", &[]).unwrap()), 1);
+    assert_eq!(created.load(Ordering::SeqCst), 1, "restored in place");
+
+    // Grown memory can't be restored, so the instance is replaced.
+    assert_eq!(i32(pool.call("grow", &[]).unwrap()), 1);
+    assert_eq!(created.load(Ordering::SeqCst), 2);
+    assert_eq!(pool.get().unwrap().memory_len(), Ok(65536));
+}
+
+#[test]
+fn reinstantiate_replaces_instances_after_each_checkout() {
+    let (pool, created) = counting_pool(1, 1, Reset::Reinstantiate, Exhaustion::Block);
+    assert_eq!(i32(pool.call("id", &[]).unwrap()), 0);
+    assert_eq!(i32(pool.call("bump", &[]).unwrap()), 1);
+    assert_eq!(i32(pool.call("bump", &[]).unwrap()), 1);
+    assert_eq!(i32(pool.call("id", &[]).unwrap()), 3);
+    assert_eq!(created.load(Ordering::SeqCst), 5);
+    assert_eq!(pool.size(), 1);
+}
+
+#[test]
+fn fail_reports_exhaustion() {
+    let (pool, _) = counting_pool(0, 2, Reset::Keep, Exhaustion::Fail);
+    let first = pool.get().unwrap();
+    let second = pool.get().unwrap();
+    assert_ne!(
+        i32(first.call("id", &[]).unwrap()),
+        i32(second.call("id", &[]).unwrap())
+    );
+    assert!(matches!(pool.get(), Err(PoolError::Exhausted)));
+    assert_eq!(pool.call("bump", &[]), Err(PoolError::Exhausted));
+    drop(first);
+    pool.get().unwrap();
+}
+
+#[test]
+fn grow_goes_past_the_maximum_and_shrinks_back() {
+    let (pool, created) = counting_pool(0, 1, Reset::Keep, Exhaustion::Grow);
+    let checked_out: Vec<_> = (0..3).map(|_| pool.get().unwrap()).collect();
+    assert_eq!(pool.size(), 3);
+    drop(checked_out);
+    assert_eq!((pool.size(), pool.idle()), (1, 1));
+    assert_eq!(created.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn block_waits_for_an_instance_to_be_returned() {
+    let (pool, _) = counting_pool(1, 1, Reset::Keep, Exhaustion::Block);
+    let held = pool.get().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            let instance = pool.get().unwrap();
+            sender
+                .send(i32(instance.call("bump", &[]).unwrap()))
+                .unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+        assert_eq!(i32(held.call("bump", &[]).unwrap()), 1);
+        drop(held);
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(10)), Ok(2));
+    });
+}
+
+#[test]
+fn concurrent_callers_share_a_bounded_pool() {
+    let (pool, created) = counting_pool(0, 2, Reset::Snapshot, Exhaustion::Block);
+    let start = Barrier::new(8);
+    thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                start.wait();
+                for _ in 0..25 {
+                    let instance = pool.get().unwrap();
+                    assert!(pool.size() <= 2);
+                    assert_eq!(i32(instance.call("bump", &[]).unwrap()), 1);
+                }
+            });
+        }
+    });
+    assert!(pool.size() <= 2);
+    assert_eq!(created.load(Ordering::SeqCst), pool.size());
+}
+
+#[test]
+fn instantiation_failures_free_their_slot() {
+    let pool = Pool::builder(module()).min(0).max(1).build().unwrap();
+    assert!(matches!(pool.get(), Err(PoolError::Instantiate(_))));
+    assert!(matches!(pool.get(), Err(PoolError::Instantiate(_))));
+    assert_eq!(pool.size(), 0);
+    assert!(matches!(
+        Pool::builder(module()).min(1).build(),
+        Err(PoolError::Instantiate(_))
+    ));
+}
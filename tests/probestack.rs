@@ -0,0 +1,83 @@
+//! Cranelift probes stack frames larger than a page by calling
+//! `__rust_probestack`, which `src/probestack.rs` provides. These run
+//! functions whose frames span several pages, so a routine that clobbered a
+//! register or moved the stack pointer would crash them or corrupt their
+//! results.
+
+use adder::{Engine, Instance, Value};
+use std::fmt::Write;
+
+/// Live values kept across a call, each spilled to its own stack slot.
+const LOCALS: i64 = 2048;
+
+/// `$big` computes `LOCALS` values, calls out so they must all be spilled,
+/// then sums them: `p * (1 + LOCALS * (LOCALS + 1) / 2)`.
+fn instance() -> Instance {
+    let mut wat = String::from(
+        r#"(module
+            (func $leaf (param i64) (result i64) (local.get 0))
+            (func $big (export "big") (param $p i64) (result i64)"#,
+    );
+    for i in 0..LOCALS {
+        write!(wat, " (local $l{} i64)", i).unwrap();
+    }
+    for i in 0..LOCALS {
+        write!(
+            wat,
+            " (local.set $l{} (i64.mul (local.get $p) (i64.const {})))",
+            i,
+            i + 1
+        )
+        .unwrap();
+    }
+    wat.push_str(" (local.set $p (call $leaf (local.get $p)))");
+    for i in 0..LOCALS {
+        write!(
+            wat,
+            " (local.set $p (i64.add (local.get $p) (local.get $l{})))",
+            i
+        )
+        .unwrap();
+    }
+    wat.push_str(
+        r#" (local.get $p))
+            (func (export "deep") (param $depth i32) (result i64)
+                (if (result i64) (i32.eqz (local.get $depth))
+                    (then (call $big (i64.const 1)))
+                    (else
+                        (i64.add
+                            (call $big (i64.extend_i32_u (local.get $depth)))
+                            (call 2 (i32.sub (local.get $depth) (i32.const 1))))))))"#,
+    );
+    Engine::new()
+        .compile(wat.as_bytes())
+        .unwrap()
+        .instantiate()
+        .unwrap()
+}
+
+fn big(p: i64) -> i64 {
+    p * (1 + LOCALS * (LOCALS + 1) / 2)
+}
+
+#[test]
+fn functions_with_large_frames_run() {
+    let instance = instance();
+    for p in [0, 1, 3, -7] {
+        assert_eq!(
+            instance.call("big", &[Value::I64(p)]).unwrap(),
+            [Value::I64(big(p))]
+        );
+    }
+}
+
+#[test]
+fn large_frames_nest() {
+    let instance = instance();
+    let depth = 64;
+    let expected = big(1) + (1..=depth).map(big).sum::<i64>();
+    assert_eq!(
+        instance.call("deep", &[Value::I32(depth as i32)]).unwrap(),
+        [Value::I64(expected)]
+    );
+}
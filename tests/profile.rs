@@ -0,0 +1,205 @@
+use adder::profile::{FunctionKind, FunctionProfile, Profile, Profiler, StackProfile};
+use adder::{func, imports, Engine, Instance, Value};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+
+const WORK: Duration = Duration::from_millis(5);
+
+/// Calls the host import `work` once per unit asked for.
+fn instance(profiler: &Profiler) -> Instance {
+    let profiler = profiler.clone();
+    Engine::new()
+        .compile(
+            br#"(module
+                (import "env" "work" (func $work))
+                (func (export "run") (param i32)
+                    (block $done
+                        (loop $next
+                            (br_if $done (i32.eqz (local.get 0)))
+                            (call $work)
+                            (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                            (br $next))))
+                (func (export "idle")))"#,
+        )
+        .unwrap()
+        .instantiate_with(&imports! {
+            "env" => {
+                "work" => func!(move || {
+                    let _scope = profiler.enter_import("work");
+                    thread::sleep(WORK);
+                }),
+            },
+        })
+        .unwrap()
+}
+
+fn function<'a>(profile: &'a Profile, kind: FunctionKind, name: &str) -> &'a FunctionProfile {
+    profile
+        .functions
+        .iter()
+        .find(|function| function.kind == kind && function.name == name)
+        .unwrap_or_else(|| panic!("{} is not in the profile", name))
+}
+
+fn stacks(profile: &Profile) -> Vec<String> {
+    profile
+        .stacks
+        .iter()
+        .map(|stack| stack.frames.join(";"))
+        .collect()
+}
+
+#[test]
+fn calls_are_counted() {
+    let profiler = Profiler::new();
+    let instance = instance(&profiler);
+    profiler.call(&instance, "run", &[Value::I32(3)]).unwrap();
+    profiler.call(&instance, "idle", &[]).unwrap();
+    profiler.call(&instance, "idle", &[]).unwrap();
+    // Failed calls are recorded too.
+    assert!(profiler.call(&instance, "missing", &[]).is_err());
+    // Calls made without the profiler are not.
+    instance.call("run", &[Value::I32(1)]).unwrap();
+
+    let profile = profiler.profile();
+    let calls: Vec<_> = profile
+        .functions
+        .iter()
+        .map(|function| (function.kind, function.name.as_str(), function.calls))
+        .collect();
+    assert_eq!(
+        calls,
+        [
+            (FunctionKind::Export, "idle", 2),
+            (FunctionKind::Export, "missing", 1),
+            (FunctionKind::Export, "run", 1),
+            (FunctionKind::Import, "work", 4),
+        ]
+    );
+
+    profiler.reset();
+    assert!(profiler.profile().functions.is_empty());
+    assert!(profiler.profile().stacks.is_empty());
+}
+
+#[test]
+fn self_time_leaves_out_nested_imports() {
+    let profiler = Profiler::new();
+    let instance = instance(&profiler);
+    profiler.call(&instance, "run", &[Value::I32(2)]).unwrap();
+
+    let profile = profiler.profile();
+    let run = function(&profile, FunctionKind::Export, "run");
+    let work = function(&profile, FunctionKind::Import, "work");
+    assert!(work.total_ns >= 2 * WORK.as_nanos() as u64);
+    assert_eq!(work.self_ns, work.total_ns);
+    assert!(run.total_ns >= work.total_ns);
+    assert_eq!(run.self_ns + work.total_ns, run.total_ns);
+
+    assert_eq!(stacks(&profile), ["export:run", "export:run;import:work"]);
+    let nested = &profile.stacks[1];
+    assert_eq!(nested.self_ns, work.self_ns);
+}
+
+#[test]
+fn recursive_calls_count_towards_the_total_once() {
+    let profiler = Profiler::new();
+    {
+        let _outer = profiler.enter_import("walk");
+        thread::sleep(WORK);
+        let _inner = profiler.enter_import("walk");
+        thread::sleep(WORK);
+    }
+
+    let profile = profiler.profile();
+    let walk = function(&profile, FunctionKind::Import, "walk");
+    assert_eq!(walk.calls, 2);
+    assert!(walk.total_ns >= 2 * WORK.as_nanos() as u64);
+    assert_eq!(walk.self_ns, walk.total_ns);
+    assert_eq!(stacks(&profile), ["import:walk", "import:walk;import:walk"]);
+}
+
+#[test]
+fn threads_keep_separate_stacks() {
+    let profiler = Profiler::new();
+    let barrier = Arc::new(Barrier::new(2));
+    // The first thread enters, then the second, then the first leaves while
+    // the second is still inside.
+    let threads: Vec<_> = ["first", "second"]
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let profiler = profiler.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                if i == 1 {
+                    barrier.wait();
+                }
+                let scope = profiler.enter_import(name);
+                if i == 0 {
+                    barrier.wait();
+                }
+                barrier.wait();
+                if i == 1 {
+                    barrier.wait();
+                }
+                drop(scope);
+                if i == 0 {
+                    barrier.wait();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let profile = profiler.profile();
+    assert_eq!(stacks(&profile), ["import:first", "import:second"]);
+    for function in &profile.functions {
+        assert_eq!(function.calls, 1);
+        assert_eq!(function.self_ns, function.total_ns);
+    }
+}
+
+#[test]
+fn profiles_render_as_json() {
+    let profiler = Profiler::new();
+    let instance = instance(&profiler);
+    profiler.call(&instance, "run", &[Value::I32(1)]).unwrap();
+
+    let json: serde_json::Value = serde_json::from_str(&profiler.profile().to_json()).unwrap();
+    let functions = json["functions"].as_array().unwrap();
+    assert_eq!(functions.len(), 2);
+    assert_eq!(functions[0]["name"], "run");
+    assert_eq!(functions[0]["kind"], "export");
+    assert_eq!(functions[0]["calls"], 1);
+    assert_eq!(functions[1]["kind"], "import");
+    assert!(functions[1]["total_ns"].as_u64().unwrap() >= WORK.as_nanos() as u64);
+    assert_eq!(
+        json["stacks"][1]["frames"],
+        serde_json::json!(["export:run", "import:work"])
+    );
+}
+
+#[test]
+fn profiles_render_as_collapsed_stacks() {
+    let stack = |frames: &[&str], self_ns| StackProfile {
+        frames: frames.iter().map(|frame| frame.to_string()).collect(),
+        self_ns,
+    };
+    let profile = Profile {
+        functions: Vec::new(),
+        stacks: vec![
+            stack(&["export:run"], 2_500_999),
+            stack(&["export:run", "import:work"], 10_000),
+            // Less than a microsecond is left out.
+            stack(&["export:run", "import:log"], 999),
+        ],
+    };
+    assert_eq!(
+        profile.to_collapsed(),
+        "export:run 2500\nexport:run;import:work 10\n"
+    );
+}
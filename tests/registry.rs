@@ -0,0 +1,300 @@
+use adder::registry::{Registry, RegistryError, Version};
+use adder::{Engine, Error, Linker, Module, Value};
+use std::thread;
+
+fn compile(wat: &str) -> Module {
+    Engine::new().compile(wat.as_bytes()).unwrap()
+}
+
+/// A module whose `version` export returns `n`.
+fn versioned(n: i32) -> Module {
+    compile(&format!(
+        r#"(module (func (export "version") (result i32) i32.const {}))"#,
+        n
+    ))
+}
+
+fn version(text: &str) -> Version {
+    text.parse().unwrap()
+}
+
+fn call_version(module: &Module) -> Vec<Value> {
+    module.instantiate().unwrap().call("version", &[]).unwrap()
+}
+
+#[test]
+fn versions_parse_and_order_numerically() {
+    assert_eq!(version("1.10.0"), Version::new(1, 10, 0));
+    assert_eq!(version("1.10.0").to_string(), "1.10.0");
+    assert!(version("1.10.0") > version("1.9.9"));
+    assert!(version("2.0.0") > version("1.99.99"));
+    for invalid in &[
+        "", "1", "1.2", "1.2.3.4", "1.x.3", "1..3", "-1.2.3", "+1.2.3", " 1.2.3",
+    ] {
+        assert_eq!(
+            invalid.parse::<Version>(),
+            Err(RegistryError::InvalidVersion(invalid.to_string()))
+        );
+    }
+}
+
+#[test]
+fn modules_are_looked_up_by_name_and_version() {
+    let registry = Registry::new();
+    registry
+        .register_module("filter", version("1.9.0"), versioned(19))
+        .unwrap();
+    registry
+        .register_module("filter", version("1.10.0"), versioned(110))
+        .unwrap();
+    registry
+        .register_module("audio", version("0.1.0"), versioned(1))
+        .unwrap();
+
+    let latest = registry.module("filter", None).unwrap();
+    assert_eq!(call_version(&latest), [Value::I32(110)]);
+    let older = registry.module("filter", Some(version("1.9.0"))).unwrap();
+    assert_eq!(call_version(&older), [Value::I32(19)]);
+    assert!(registry.module("filter", Some(version("2.0.0"))).is_none());
+    assert!(registry.module("video", None).is_none());
+
+    assert_eq!(
+        registry.register_module("filter", version("1.9.0"), versioned(0)),
+        Err(RegistryError::AlreadyRegistered {
+            name: "filter".to_string(),
+            version: version("1.9.0"),
+        })
+    );
+    assert_eq!(
+        registry.modules(),
+        [
+            ("audio".to_string(), version("0.1.0")),
+            ("filter".to_string(), version("1.9.0")),
+            ("filter".to_string(), version("1.10.0")),
+        ]
+    );
+}
+
+#[test]
+fn unloading_removes_one_version() {
+    let registry = Registry::new();
+    registry
+        .register_module("filter", version("1.0.0"), versioned(1))
+        .unwrap();
+    registry
+        .register_module("filter", version("2.0.0"), versioned(2))
+        .unwrap();
+    let unloaded = registry.unload_module("filter", version("2.0.0")).unwrap();
+    assert_eq!(call_version(&unloaded), [Value::I32(2)]);
+    assert_eq!(
+        call_version(&registry.module("filter", None).unwrap()),
+        [Value::I32(1)]
+    );
+    assert_eq!(
+        registry.unload_module("filter", version("2.0.0")).err(),
+        Some(RegistryError::NotFound {
+            name: "filter".to_string(),
+            version: version("2.0.0"),
+        })
+    );
+    registry.unload_module("filter", version("1.0.0")).unwrap();
+    assert!(registry.modules().is_empty());
+}
+
+#[test]
+fn instances_are_shared_between_threads() {
+    let registry = Registry::new();
+    let counter = compile(
+        r#"(module
+            (global $count (mut i32) (i32.const 0))
+            (func (export "bump") (result i32)
+                (global.set $count (i32.add (global.get $count) (i32.const 1)))
+                (global.get $count)))"#,
+    );
+    registry
+        .register_instance("counter", version("1.0.0"), counter.instantiate().unwrap())
+        .unwrap();
+
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            let registry = registry.clone();
+            scope.spawn(move || {
+                for _ in 0..25 {
+                    let instance = registry.instance("counter", None).unwrap();
+                    instance.lock().unwrap().call("bump", &[]).unwrap();
+                }
+            });
+        }
+    });
+    let instance = registry
+        .instance("counter", Some(version("1.0.0")))
+        .unwrap();
+    assert_eq!(
+        instance.lock().unwrap().call("bump", &[]),
+        Ok(vec![Value::I32(101)])
+    );
+    assert_eq!(
+        registry.instances(),
+        [("counter".to_string(), version("1.0.0"))]
+    );
+
+    // Unloading leaves existing handles working.
+    registry
+        .unload_instance("counter", version("1.0.0"))
+        .unwrap();
+    assert!(registry.instance("counter", None).is_none());
+    assert_eq!(
+        instance.lock().unwrap().call("bump", &[]),
+        Ok(vec![Value::I32(102)])
+    );
+}
+
+#[test]
+fn linkers_instantiate_registered_modules_for_imports() {
+    let registry = Registry::new();
+    registry
+        .register_module(
+            "runtime",
+            version("1.0.0"),
+            compile(r#"(module (func (export "base") (result i32) i32.const 100))"#),
+        )
+        .unwrap();
+    registry
+        .register_module(
+            "runtime",
+            version("1.1.0"),
+            compile(r#"(module (func (export "base") (result i32) i32.const 110))"#),
+        )
+        .unwrap();
+    registry
+        .register_module(
+            "math",
+            version("1.0.0"),
+            compile(
+                r#"(module
+                    (import "runtime" "base" (func $base (result i32)))
+                    (func (export "plus") (param i32) (result i32)
+                        (i32.add (call $base) (local.get 0))))"#,
+            ),
+        )
+        .unwrap();
+    let feature = compile(
+        r#"(module
+            (import "math" "plus" (func $plus (param i32) (result i32)))
+            (import "runtime" "base" (func $base (result i32)))
+            (func (export "run") (result i32)
+                (i32.add (call $plus (i32.const 1)) (call $base))))"#,
+    );
+
+    let mut linker = Linker::new();
+    linker.registry(registry.clone());
+    let feature = linker.instantiate("feature", &feature).unwrap();
+    assert_eq!(feature.call("run", &[]).unwrap(), [Value::I32(221)]);
+    // Dependencies are linked once, before the modules that import them.
+    assert_eq!(
+        linker.names().collect::<Vec<_>>(),
+        ["runtime", "math", "feature"]
+    );
+
+    // Without the registry, the imports stay unresolved.
+    assert!(Linker::new()
+        .instantiate("feature", &registry.module("math", None).unwrap())
+        .is_err());
+}
+
+#[test]
+fn registered_modules_that_import_each_other_are_rejected() {
+    let registry = Registry::new();
+    for (name, other) in &[("a", "b"), ("b", "a")] {
+        let wat = format!(
+            r#"(module (import "{}" "f" (func)) (func (export "f")))"#,
+            other
+        );
+        registry
+            .register_module(name, version("1.0.0"), compile(&wat))
+            .unwrap();
+    }
+    let mut linker = Linker::new();
+    linker.registry(registry);
+    assert_eq!(
+        linker
+            .instantiate("main", &compile(r#"(module (import "a" "f" (func)))"#))
+            .err(),
+        Some(Error::Instantiate(
+            "registered modules import each other: main -> a -> b -> a".to_string()
+        ))
+    );
+}
+
+#[test]
+fn failed_instantiations_unlink_the_modules_linked_for_them() {
+    let registry = Registry::new();
+    registry
+        .register_module(
+            "runtime",
+            version("1.0.0"),
+            compile(r#"(module (func (export "base") (result i32) i32.const 100))"#),
+        )
+        .unwrap();
+    let math = |extra_import: &str| {
+        compile(&format!(
+            r#"(module
+                (import "runtime" "base" (func $base (result i32)))
+                {}
+                (func (export "plus") (param i32) (result i32)
+                    (i32.add (call $base) (local.get 0))))"#,
+            extra_import
+        ))
+    };
+    registry
+        .register_module(
+            "math",
+            version("1.0.0"),
+            math(r#"(import "env" "missing" (func))"#),
+        )
+        .unwrap();
+    let feature = |extra_import: &str| {
+        compile(&format!(
+            r#"(module
+                (import "math" "plus" (func $plus (param i32) (result i32)))
+                (import "base" "one" (func $one (result i32)))
+                {}
+                (func (export "run") (result i32) (call $plus (call $one))))"#,
+            extra_import
+        ))
+    };
+
+    let mut linker = Linker::new();
+    linker.registry(registry.clone());
+    linker
+        .instantiate(
+            "base",
+            &compile(r#"(module (func (export "one") (result i32) i32.const 1))"#),
+        )
+        .unwrap();
+    // `runtime` is linked for `math`, which then fails.
+    let err = linker.instantiate("feature", &feature("")).err().unwrap();
+    assert!(
+        err.to_string().contains("registered module `math`"),
+        "{}",
+        err
+    );
+    assert_eq!(linker.names().collect::<Vec<_>>(), ["base"]);
+    assert!(linker.get("runtime").is_none());
+
+    registry
+        .register_module("math", version("1.1.0"), math(""))
+        .unwrap();
+    // Both dependencies are linked, then the module itself fails.
+    let missing = feature(r#"(import "env" "missing" (func))"#);
+    assert!(linker.instantiate("feature", &missing).is_err());
+    assert_eq!(linker.names().collect::<Vec<_>>(), ["base"]);
+
+    // Nothing stale is left in the way of a retry.
+    let feature = linker.instantiate("feature", &feature("")).unwrap();
+    assert_eq!(feature.call("run", &[]).unwrap(), [Value::I32(101)]);
+    assert_eq!(
+        linker.names().collect::<Vec<_>>(),
+        ["base", "runtime", "math", "feature"]
+    );
+}
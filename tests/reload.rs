@@ -0,0 +1,221 @@
+use adder::reload::{self, ChangedExport};
+use adder::{Engine, Error, ExportType, ExternType, ImportObject, Instance, Value, ValueType};
+
+/// A plugin that counts calls in a global and records them in memory.
+const PLUGIN: &str = r#"(module
+    (memory (export "memory") 1 4)
+    (data (i32.const 0) "plugin")
+    (global $count (mut i32) (i32.const 0))
+    (func (export "bump") (result i32)
+        (global.set $count (i32.add (global.get $count) (i32.const 1)))
+        (i32.store8 (i32.add (i32.const 16) (global.get $count)) (global.get $count))
+        (global.get $count))
+    (func (export "count") (result i32)
+        (global.get $count)))"#;
+
+fn instance(wat: &str) -> Instance {
+    Engine::new()
+        .compile(wat.as_bytes())
+        .unwrap()
+        .instantiate()
+        .unwrap()
+}
+
+fn reload(instance: &mut Instance, wat: &str) -> adder::Result<reload::Report> {
+    reload::reload(
+        instance,
+        &Engine::new(),
+        wat.as_bytes(),
+        &ImportObject::new(),
+    )
+}
+
+fn bumped(times: usize) -> Instance {
+    let instance = instance(PLUGIN);
+    for _ in 0..times {
+        instance.call("bump", &[]).unwrap();
+    }
+    instance
+}
+
+fn func(params: &[ValueType], results: &[ValueType]) -> ExternType {
+    ExternType::Function {
+        params: params.to_vec(),
+        results: results.to_vec(),
+    }
+}
+
+#[test]
+fn compatible_modules_keep_memory_and_globals() {
+    let mut instance = bumped(3);
+    // Bumps by ten from now on and exports one more function.
+    let rebuilt = PLUGIN
+        .replace("(i32.const 1)))", "(i32.const 10)))")
+        .replace(
+            r#"(func (export "count")"#,
+            r#"(func (export "reset") (global.set $count (i32.const 0)))
+    (func (export "count")"#,
+        );
+
+    let report = reload(&mut instance, &rebuilt).unwrap();
+    assert!(report.carried());
+    assert_eq!(
+        report.added,
+        [ExportType {
+            name: "reset".to_string(),
+            ty: func(&[], &[]),
+        }]
+    );
+    assert!(report.removed.is_empty() && report.changed.is_empty());
+
+    assert_eq!(instance.call("count", &[]).unwrap(), [Value::I32(3)]);
+    let mut recorded = [0; 4];
+    instance.read_memory(16, &mut recorded).unwrap();
+    assert_eq!(recorded, [0, 1, 2, 3]);
+    assert_eq!(instance.call("bump", &[]).unwrap(), [Value::I32(13)]);
+    instance.call("reset", &[]).unwrap();
+    assert_eq!(instance.call("count", &[]).unwrap(), [Value::I32(0)]);
+}
+
+#[test]
+fn grown_memory_is_carried_over() {
+    let mut instance = bumped(1);
+    instance.call("bump", &[]).unwrap();
+    let grow = PLUGIN.replace(
+        r#"(func (export "count")"#,
+        r#"(func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+    (func (export "count")"#,
+    );
+    reload(&mut instance, &grow).unwrap();
+    instance.call("grow", &[Value::I32(2)]).unwrap();
+    assert_eq!(instance.memory_pages().unwrap(), 3);
+
+    assert!(reload(&mut instance, &grow).unwrap().carried());
+    assert_eq!(instance.memory_pages().unwrap(), 3);
+    assert_eq!(instance.call("count", &[]).unwrap(), [Value::I32(2)]);
+
+    // Three pages no longer fit.
+    let report = reload(&mut instance, &grow.replace("1 4", "1 2")).unwrap();
+    assert_eq!(
+        report.incompatible,
+        ["memory 0 has 3 pages, outside the new limits"]
+    );
+    assert_eq!(instance.memory_pages().unwrap(), 1);
+    assert_eq!(instance.call("count", &[]).unwrap(), [Value::I32(0)]);
+}
+
+#[test]
+fn removed_and_changed_exports_reset_state() {
+    let mut instance = bumped(2);
+    let rebuilt = PLUGIN
+        .replace(
+            r#"(func (export "count") (result i32)
+        (global.get $count))"#,
+            "",
+        )
+        .replace(
+            r#"(func (export "bump") (result i32)"#,
+            r#"(func (export "bump") (param i32) (result i32)"#,
+        );
+
+    let report = reload(&mut instance, &rebuilt).unwrap();
+    assert!(!report.carried());
+    assert!(report.exports_changed());
+    assert_eq!(
+        report.removed,
+        [ExportType {
+            name: "count".to_string(),
+            ty: func(&[], &[ValueType::I32]),
+        }]
+    );
+    assert_eq!(
+        report.changed,
+        [ChangedExport {
+            name: "bump".to_string(),
+            old: func(&[], &[ValueType::I32]),
+            new: func(&[ValueType::I32], &[ValueType::I32]),
+        }]
+    );
+    assert_eq!(
+        instance.call("bump", &[Value::I32(0)]).unwrap(),
+        [Value::I32(1)]
+    );
+}
+
+#[test]
+fn changed_data_segments_reset_state() {
+    let mut instance = bumped(2);
+    let report = reload(
+        &mut instance,
+        &PLUGIN.replace("\"plugin\"", "\"plugin v2\""),
+    )
+    .unwrap();
+    assert!(!report.exports_changed());
+    assert_eq!(report.incompatible, ["the data segments changed"]);
+    assert_eq!(instance.call("count", &[]).unwrap(), [Value::I32(0)]);
+    let mut data = [0; 9];
+    instance.read_memory(0, &mut data).unwrap();
+    assert_eq!(&data, b"plugin v2");
+}
+
+#[test]
+fn changed_globals_reset_state() {
+    let mut instance = bumped(2);
+    let rebuilt = PLUGIN.replace(
+        "(global $count",
+        "(global $limit i32 (i32.const 100))\n    (global $count",
+    );
+    let report = reload(&mut instance, &rebuilt).unwrap();
+    assert_eq!(report.incompatible, ["the globals changed"]);
+    assert_eq!(instance.call("count", &[]).unwrap(), [Value::I32(0)]);
+
+    let mut instance = bumped(2);
+    let rebuilt = PLUGIN.replace(
+        "(global $count (mut i32) (i32.const 0))",
+        "(global $count (mut i32) (i32.const 0))\n    (global $total (mut i64) (i64.const 0))",
+    );
+    let report = reload(&mut instance, &rebuilt).unwrap();
+    assert_eq!(report.incompatible, ["the globals changed"]);
+}
+
+#[test]
+fn failed_reloads_leave_the_instance_alone() {
+    let mut instance = bumped(2);
+    let err = reload(&mut instance, "(module (func (export \"bump\")").unwrap_err();
+    assert!(matches!(err, Error::Parse(_)), "{:?}", err);
+
+    let err = reload(
+        &mut instance,
+        r#"(module (import "env" "missing" (func)) (func (export "bump")))"#,
+    )
+    .unwrap_err();
+    assert!(matches!(err, Error::Instantiate(_)), "{:?}", err);
+
+    assert_eq!(instance.call("count", &[]).unwrap(), [Value::I32(2)]);
+}
+
+#[test]
+fn reports_list_export_changes_then_the_outcome() {
+    let mut instance = instance(PLUGIN);
+    let report = reload(&mut instance, PLUGIN).unwrap();
+    assert_eq!(report.to_string(), "state carried over");
+
+    // Raising the memory maximum keeps the state.
+    let report = reload(&mut instance, &PLUGIN.replace("1 4", "1 8")).unwrap();
+    assert_eq!(
+        report.to_string(),
+        "~ memory: memory 1..4 -> memory 1..8\nstate carried over"
+    );
+
+    let rebuilt = PLUGIN
+        .replace(r#"(export "count")"#, r#"(export "total")"#)
+        .replace("1 4", "1 16");
+    let report = reload(&mut instance, &rebuilt).unwrap();
+    assert_eq!(
+        report.to_string(),
+        "+ total: func () -> (i32)\n\
+         - count: func () -> (i32)\n\
+         ~ memory: memory 1..8 -> memory 1..16\n\
+         state reset: export `count` was removed"
+    );
+}
@@ -0,0 +1,68 @@
+//! Drives `wasmer-repl` through piped stdin. Built only with the `repl`
+//! feature.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Returns the REPL's output after the greeting, and its errors.
+fn repl(fixture: &str, input: &str) -> (Vec<String>, Vec<String>) {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wasmer-repl"))
+        .arg(manifest.join("tests/fixtures").join(fixture))
+        // Keep the history out of the real home directory.
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let lines = |bytes: Vec<u8>| {
+        String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    let mut stdout = lines(output.stdout);
+    stdout.remove(0);
+    (stdout, lines(output.stderr))
+}
+
+#[test]
+fn calls_and_memory_commands() {
+    let (output, errors) = repl(
+        "memory.wat",
+        "call store 16 0x41424344\nmem read 16 4\ncall load 16\nmem size\n",
+    );
+    assert_eq!(errors, Vec::<String>::new());
+    assert_eq!(
+        output,
+        [
+            "00000010  44 43 42 41                                      DCBA",
+            "1094861636",
+            "1 pages (65536 bytes)",
+        ]
+    );
+}
+
+#[test]
+fn reads_past_the_end_of_memory_are_refused() {
+    let (output, errors) = repl("memory.wat", "mem read 0 0xffffffff\nmem read 65532 8\n");
+    assert_eq!(output, Vec::<String>::new());
+    assert_eq!(
+        errors,
+        [
+            "error: memory error: 4294967295 bytes at 0 is out of bounds (65536 bytes)",
+            "error: memory error: 8 bytes at 65532 is out of bounds (65536 bytes)",
+        ]
+    );
+}
@@ -0,0 +1,258 @@
+use adder::deterministic::Host;
+use adder::replay::{self, Event, Log, Recording, ReplayError};
+use adder::{func, imports, Engine, Error, ImportObject, Instance, Value};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Length of a log's magic, version and module hash.
+const HEADER_LEN: usize = 4 + 2 + 32;
+
+const PLUGIN: &str = r#"(module
+    (import "env" "next" (func $next (param i32) (result i32)))
+    (import "env" "log" (func $log (param f64)))
+    (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+    (import "env" "fail" (func $fail))
+    (memory (export "memory") 1)
+    (global $started (mut i32) (i32.const 0))
+    (table 2 funcref)
+    (elem (i32.const 0) $next $double)
+    (export "next" (func $next))
+    (start $init)
+    (func $init
+        (global.set $started (call $next (i32.const 100))))
+    (func $double (param i32) (result i32)
+        (i32.mul (local.get 0) (i32.const 2)))
+    (func (export "step") (param i32) (result i32)
+        (call $log (f64.convert_i32_s (local.get 0)))
+        (drop (call $random_get (i32.const 16) (i32.const 8)))
+        (i32.add
+            (call_indirect (param i32) (result i32) (local.get 0) (i32.const 0))
+            (global.get $started)))
+    (func (export "indirect_double") (param i32) (result i32)
+        (call_indirect (param i32) (result i32) (local.get 0) (i32.const 1)))
+    (func (export "fail")
+        (call $fail))
+    (func (export "random") (result i64)
+        (i64.load (i32.const 16))))"#;
+
+/// Host imports that give different answers on every run.
+fn host(logged: Arc<Mutex<Vec<f64>>>) -> ImportObject {
+    let counter = Arc::new(AtomicI32::new(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos() as i32,
+    ));
+    let mut imports = imports! {
+        "env" => {
+            "next" => func!(move |x: i32| -> i32 {
+                counter.fetch_add(1, Ordering::SeqCst).wrapping_add(x)
+            }),
+            "log" => func!(move |x: f64| logged.lock().unwrap().push(x)),
+            "fail" => func!(|| -> Result<(), String> { Err("host failure".to_string()) }),
+        },
+    };
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64;
+    Host::new(seed).register(&mut imports);
+    imports
+}
+
+fn memory(instance: &Instance) -> Vec<u8> {
+    let mut bytes = vec![0; instance.memory_len().unwrap()];
+    instance.read_memory(0, &mut bytes).unwrap();
+    bytes
+}
+
+/// Records a run of `PLUGIN`, returning the log, the results of each call
+/// and the final memory.
+fn record() -> (Log, Vec<adder::Result<Vec<Value>>>, Vec<u8>) {
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let recording =
+        Recording::start(&Engine::new(), PLUGIN.as_bytes(), host(logged.clone())).unwrap();
+    let results = vec![
+        recording.call("step", &[Value::I32(5)]),
+        recording.call("indirect_double", &[Value::I32(4)]),
+        recording.call("next", &[Value::I32(1)]),
+        recording.call("fail", &[]),
+        recording.call("step", &[Value::I32(-3)]),
+        recording.call("random", &[]),
+    ];
+    assert_eq!(*logged.lock().unwrap(), [5.0, -3.0]);
+    (recording.log(), results, memory(recording.instance()))
+}
+
+#[test]
+fn replays_reproduce_recorded_runs_without_the_host() {
+    let (log, results, recorded_memory) = record();
+    assert_eq!(results[1], Ok(vec![Value::I32(8)]));
+    assert!(matches!(results[3], Err(Error::Trap(_))));
+
+    let instance = replay::replay(&Engine::new(), PLUGIN.as_bytes(), &log).unwrap();
+    assert_eq!(memory(&instance), recorded_memory);
+    assert_eq!(
+        instance.call("random", &[]),
+        results[5],
+        "host writes to memory are replayed"
+    );
+
+    // The start function's import call comes before any exported call.
+    assert!(matches!(&log.events[0], Event::Import(call) if call.index == 0));
+    assert!(matches!(&log.events[1], Event::Call { export, .. } if export == "step"));
+}
+
+#[test]
+fn imports_are_logged_with_arguments_results_and_writes() {
+    let (log, _, _) = record();
+    let calls: Vec<_> = log
+        .events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Import(call) => Some(call),
+            _ => None,
+        })
+        .collect();
+    // init, then step: log, random_get, next (through the table).
+    assert_eq!(calls[0].args, [Value::I32(100)]);
+    assert_eq!(calls[1].index, 1);
+    assert_eq!(calls[1].args, [Value::F64(5.0)]);
+    assert_eq!(calls[1].result, None);
+    assert_eq!(calls[2].args, [Value::I32(16), Value::I32(8)]);
+    assert_eq!(calls[2].result, Some(Value::I32(0)));
+    assert_eq!(calls[2].writes.len(), 1);
+    assert_eq!(calls[2].writes[0].offset, 16);
+    assert_eq!(calls[3].args, [Value::I32(5)]);
+
+    let failed = calls.iter().find(|call| call.index == 3).unwrap();
+    assert!(failed.trapped);
+    assert!(log.events.contains(&Event::Trap));
+}
+
+#[test]
+fn logs_round_trip_through_files() {
+    let (log, _, _) = record();
+    let bytes = log.to_bytes();
+    assert_eq!(Log::from_bytes(&bytes).unwrap(), log);
+
+    let path = std::env::temp_dir().join(format!("adder-replay-{}.log", std::process::id()));
+    log.save(&path).unwrap();
+    let loaded = Log::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap(), log);
+
+    assert!(matches!(
+        Log::from_bytes(b"not a log"),
+        Err(ReplayError::Malformed(_))
+    ));
+    assert!(matches!(
+        Log::from_bytes(&bytes[..bytes.len() - 4]),
+        Err(ReplayError::Malformed(_))
+    ));
+    let mut newer = bytes.clone();
+    newer[4] = 2;
+    assert!(matches!(
+        Log::from_bytes(&newer),
+        Err(ReplayError::UnsupportedVersion(2))
+    ));
+}
+
+#[test]
+fn events_are_inflated_up_to_a_limit() {
+    let (log, _, _) = record();
+    let bytes = log.to_bytes();
+    let mut events = Vec::new();
+    DeflateDecoder::new(&bytes[HEADER_LEN..])
+        .read_to_end(&mut events)
+        .unwrap();
+    let len = events.len() as u64;
+    assert_eq!(Log::from_bytes_with_limit(&bytes, len).unwrap(), log);
+    assert!(matches!(
+        Log::from_bytes_with_limit(&bytes, len - 1),
+        Err(ReplayError::TooLarge { limit }) if limit == len - 1
+    ));
+
+    // A hundred megabytes of zeros deflate to about a hundred kilobytes.
+    let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+    for _ in 0..100 {
+        encoder.write_all(&vec![0; 1 << 20]).unwrap();
+    }
+    let mut bomb = bytes[..HEADER_LEN].to_vec();
+    bomb.extend_from_slice(&encoder.finish().unwrap());
+    assert!(matches!(
+        Log::from_bytes_with_limit(&bomb, 1 << 20),
+        Err(ReplayError::TooLarge { limit: 1048576 })
+    ));
+}
+
+#[test]
+fn divergences_are_reported() {
+    let (log, _, _) = record();
+    let engine = Engine::new();
+
+    let other = PLUGIN.replace("(i32.const 2)", "(i32.const 3)");
+    assert!(matches!(
+        replay::replay(&engine, other.as_bytes(), &log),
+        Err(ReplayError::ModuleMismatch)
+    ));
+
+    let mut tampered = log.clone();
+    let position = tampered
+        .events
+        .iter()
+        .position(|event| matches!(event, Event::Return(values) if values == &[Value::I32(8)]))
+        .unwrap();
+    tampered.events[position] = Event::Return(vec![Value::I32(9)]);
+    match replay::replay(&engine, PLUGIN.as_bytes(), &tampered) {
+        Err(ReplayError::Diverged { event, what }) => {
+            assert_eq!(event, position);
+            assert_eq!(
+                what,
+                "`indirect_double` returned [I32(8)], but returned [I32(9)] when recorded"
+            );
+        }
+        other => panic!("expected a divergence, got {:?}", other.map(|_| ())),
+    }
+
+    let mut tampered = log.clone();
+    if let Event::Import(call) = &mut tampered.events[0] {
+        call.args = vec![Value::I32(101)];
+    }
+    match replay::replay(&engine, PLUGIN.as_bytes(), &tampered) {
+        Err(ReplayError::Diverged { event: 0, what }) => assert_eq!(
+            what,
+            "called `env.next` with [I32(100)], but `env.next` with [I32(101)] when recorded"
+        ),
+        other => panic!("expected a divergence, got {:?}", other.map(|_| ())),
+    }
+
+    let mut truncated = log;
+    truncated.events.truncate(3);
+    truncated.events.remove(2);
+    assert!(matches!(
+        replay::replay(&engine, PLUGIN.as_bytes(), &truncated),
+        Err(ReplayError::Diverged { event: 2, .. })
+    ));
+}
+
+#[test]
+fn modules_without_imports_are_recorded_as_is() {
+    let wasm = include_bytes!("../src/add.wasm");
+    let recording = Recording::start(&Engine::new(), wasm, imports! {}).unwrap();
+    assert_eq!(
+        recording.call("add_one", &[Value::I32(1)]),
+        Ok(vec![Value::I32(2)])
+    );
+    assert!(matches!(
+        recording.call("missing", &[]),
+        Err(Error::ExportNotFound(_))
+    ));
+    let log = recording.log();
+    assert_eq!(log.events.len(), 2, "rejected calls are not logged");
+    replay::replay(&Engine::new(), wasm, &log).unwrap();
+}
@@ -0,0 +1,95 @@
+//! Runs the `wasmer-run` binary against the bundled `add.wasm` and the
+//! fixtures.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Returns the exit code, stdout and stderr of `wasmer-run args...`, with
+/// module paths taken relative to the crate root.
+fn run(args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_wasmer-run"))
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn results_are_printed() {
+    assert_eq!(
+        run(&["src/add.wasm", "add_one", "42"]),
+        (0, "43\n".to_string(), String::new())
+    );
+    assert_eq!(
+        run(&["run", "src/add.wasm", "add_one", "-0x80000000"]),
+        (0, "-2147483647\n".to_string(), String::new())
+    );
+    assert_eq!(
+        run(&[
+            "tests/fixtures/arithmetic.wat",
+            "add_i64",
+            "-1",
+            "0xffffffff"
+        ]),
+        (0, "4294967294\n".to_string(), String::new())
+    );
+}
+
+#[test]
+fn traps_exit_with_an_error() {
+    let (code, stdout, stderr) = run(&["tests/fixtures/traps.wat", "unreachable"]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "");
+    assert!(stderr.starts_with("trap: "), "{}", stderr);
+
+    let (code, _, stderr) = run(&["tests/fixtures/traps.wat", "div_by_zero", "0"]);
+    assert_eq!(code, 1);
+    assert!(stderr.starts_with("trap: "), "{}", stderr);
+}
+
+#[test]
+fn bad_arguments_exit_with_an_error() {
+    let (code, _, stderr) = run(&["src/add.wasm", "add_one", "4294967296"]);
+    assert_eq!(code, 1);
+    assert_eq!(stderr, "error: add_one: `4294967296` is not a valid i32\n");
+
+    let (code, _, stderr) = run(&["src/add.wasm", "add_one"]);
+    assert_eq!(code, 1);
+    assert!(
+        stderr.starts_with("error: add_one: expected 1 argument"),
+        "{}",
+        stderr
+    );
+
+    let (code, _, stderr) = run(&["src/add.wasm", "missing"]);
+    assert_eq!(code, 1);
+    assert!(stderr.starts_with("error: "), "{}", stderr);
+
+    let (code, _, stderr) = run(&["missing.wasm", "add_one"]);
+    assert_eq!(code, 1);
+    assert!(
+        stderr.starts_with("error: could not read missing.wasm"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn usage_errors_exit_with_two() {
+    for args in [
+        &[][..],
+        &["src/add.wasm"],
+        &["run", "src/add.wasm"],
+        &["inspect"],
+    ] {
+        let (code, stdout, stderr) = run(args);
+        assert_eq!(code, 2, "{:?}", args);
+        assert_eq!(stdout, "");
+        assert!(stderr.starts_with("usage:"), "{}", stderr);
+    }
+}
@@ -0,0 +1,243 @@
+use adder::snapshot::{Snapshot, SnapshotError, TableElement};
+use adder::{func, imports, Engine, Instance, Linker, Module, Value};
+
+/// Keeps a counter in a global and memory, and dispatches through a table
+/// holding an imported and two local functions.
+const PLUGIN: &str = r#"(module
+    (import "env" "three" (func $three (result i32)))
+    (type $get (func (result i32)))
+    (memory (export "memory") 1 4)
+    (table (export "table") 4 funcref)
+    (elem (i32.const 0) $three $one $two)
+    (global $count (mut i32) (i32.const 0))
+    (global $limit i32 (i32.const 10))
+    (func $one (result i32) i32.const 1)
+    (func $two (result i32) i32.const 2)
+    (func (export "bump") (result i32)
+        (global.set $count (i32.add (global.get $count) (i32.const 1)))
+        (i32.store (i32.const 64) (global.get $count))
+        (global.get $count))
+    (func (export "stored") (result i32) (i32.load (i32.const 64)))
+    (func (export "dispatch") (param i32) (result i32)
+        (call_indirect (type $get) (local.get 0)))
+    (func (export "grow") (param i32) (result i32)
+        (memory.grow (local.get 0))))"#;
+
+fn module() -> Module {
+    Engine::new().compile(PLUGIN.as_bytes()).unwrap()
+}
+
+fn instantiate(module: &Module) -> Instance {
+    module
+        .instantiate_with(&imports! { "env" => { "three" => func!(|| -> i32 { 3 }), }, })
+        .unwrap()
+}
+
+fn call(instance: &Instance, name: &str, args: &[Value]) -> Value {
+    instance.call(name, args).unwrap()[0]
+}
+
+fn dispatch(instance: &Instance, slot: i32) -> adder::Result<Vec<Value>> {
+    instance.call("dispatch", &[Value::I32(slot)])
+}
+
+#[test]
+fn captured_state_is_restored() {
+    let module = module();
+    let mut instance = instantiate(&module);
+    call(&instance, "bump", &[]);
+    let snapshot = Snapshot::capture(&instance);
+    assert_eq!(snapshot.memories.len(), 1);
+    assert_eq!(snapshot.memories[0].len(), 65536);
+    // Only the mutable global is captured.
+    assert_eq!(snapshot.globals, [(0, Value::I32(1))]);
+    assert_eq!(
+        snapshot.tables,
+        [[
+            TableElement::Function(0),
+            TableElement::Function(1),
+            TableElement::Function(2),
+            TableElement::Null,
+        ]]
+    );
+
+    call(&instance, "bump", &[]);
+    call(&instance, "bump", &[]);
+    snapshot.restore(&mut instance).unwrap();
+    assert_eq!(call(&instance, "stored", &[]), Value::I32(1));
+    assert_eq!(call(&instance, "bump", &[]), Value::I32(2));
+
+    // Into a fresh instance of the same module.
+    let mut other = instantiate(&module);
+    snapshot.restore(&mut other).unwrap();
+    assert_eq!(call(&other, "stored", &[]), Value::I32(1));
+    assert_eq!(Snapshot::capture(&other), snapshot);
+}
+
+#[test]
+fn table_elements_point_at_the_target_instance() {
+    let module = module();
+    let mut instance = instantiate(&module);
+    let mut snapshot = Snapshot::capture(&instance);
+    // Move the imported function to the empty slot and swap the others.
+    snapshot.tables[0] = vec![
+        TableElement::Null,
+        TableElement::Function(2),
+        TableElement::Function(1),
+        TableElement::Function(0),
+    ];
+    snapshot.restore(&mut instance).unwrap();
+    assert!(dispatch(&instance, 0).is_err());
+    assert_eq!(dispatch(&instance, 1).unwrap(), [Value::I32(2)]);
+    assert_eq!(dispatch(&instance, 2).unwrap(), [Value::I32(1)]);
+    assert_eq!(dispatch(&instance, 3).unwrap(), [Value::I32(3)]);
+    assert_eq!(Snapshot::capture(&instance), snapshot);
+
+    // A bumping function is called on the target's own state.
+    let mut other = instantiate(&module);
+    snapshot.tables[0][0] = TableElement::Function(3);
+    snapshot.restore(&mut other).unwrap();
+    assert_eq!(dispatch(&other, 0).unwrap(), [Value::I32(1)]);
+    assert_eq!(call(&other, "stored", &[]), Value::I32(1));
+    assert_eq!(call(&instance, "stored", &[]), Value::I32(0));
+
+    snapshot.tables[0][0] = TableElement::Function(9);
+    assert!(matches!(
+        snapshot.restore(&mut other),
+        Err(SnapshotError::Mismatch(_))
+    ));
+}
+
+#[test]
+fn elements_from_other_instances_cannot_be_restored() {
+    let mut linker = Linker::new();
+    linker
+        .imports(imports! { "env" => { "three" => func!(|| -> i32 { 3 }), }, })
+        .unwrap();
+    linker.instantiate("plugin", &module()).unwrap();
+    linker
+        .instantiate(
+            "patch",
+            &Engine::new()
+                .compile(
+                    br#"(module
+                        (import "plugin" "table" (table 4 funcref))
+                        (func $four (result i32) i32.const 4)
+                        (elem (i32.const 3) $four))"#,
+                )
+                .unwrap(),
+        )
+        .unwrap();
+    let plugin = linker.get("plugin").unwrap();
+    assert_eq!(dispatch(plugin, 3).unwrap(), [Value::I32(4)]);
+
+    let snapshot = Snapshot::capture(plugin);
+    assert_eq!(snapshot.tables[0][3], TableElement::Foreign);
+    let err = snapshot.restore(&mut instantiate(&module())).unwrap_err();
+    assert_eq!(
+        err,
+        SnapshotError::Mismatch("table 0 element 3 is a function of another instance".to_string())
+    );
+}
+
+#[test]
+fn memory_is_grown_to_the_snapshot_but_never_shrunk() {
+    let module = module();
+    let grown = instantiate(&module);
+    assert_eq!(call(&grown, "grow", &[Value::I32(2)]), Value::I32(1));
+    call(&grown, "bump", &[]);
+    let snapshot = Snapshot::capture(&grown);
+
+    let mut fresh = instantiate(&module);
+    snapshot.restore(&mut fresh).unwrap();
+    assert_eq!(fresh.memory_pages().unwrap(), 3);
+    assert_eq!(call(&fresh, "stored", &[]), Value::I32(1));
+
+    let small = Snapshot::capture(&instantiate(&module));
+    let err = small.restore(&mut fresh).unwrap_err();
+    assert!(matches!(err, SnapshotError::Mismatch(_)), "{:?}", err);
+    // Nothing was written.
+    assert_eq!(call(&fresh, "stored", &[]), Value::I32(1));
+
+    // Four pages is the memory's maximum.
+    let mut too_big = snapshot.clone();
+    too_big.memories[0].resize(5 * 65536, 0);
+    let err = too_big.restore(&mut fresh).unwrap_err();
+    assert!(matches!(err, SnapshotError::Mismatch(_)), "{:?}", err);
+}
+
+#[test]
+fn bytes_round_trip() {
+    let instance = instantiate(&module());
+    call(&instance, "bump", &[]);
+    let mut snapshot = Snapshot::capture(&instance);
+    snapshot.tables[0][3] = TableElement::Foreign;
+    snapshot.globals.push((1, Value::F64(-0.5)));
+
+    let bytes = snapshot.to_bytes();
+    assert_eq!(&bytes[..5], b"WSNP\x02");
+    assert_eq!(Snapshot::from_bytes(&bytes).unwrap(), snapshot);
+}
+
+#[test]
+fn truncated_and_malformed_blobs_are_rejected() {
+    let bytes = Snapshot::capture(&instantiate(&module())).to_bytes();
+    for len in 0..bytes.len() {
+        let err = Snapshot::from_bytes(&bytes[..len]).unwrap_err();
+        assert!(
+            matches!(err, SnapshotError::Malformed(_)),
+            "{} bytes: {:?}",
+            len,
+            err
+        );
+    }
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(
+        Snapshot::from_bytes(&trailing),
+        Err(SnapshotError::Malformed("trailing bytes"))
+    );
+
+    let mut magic = bytes.clone();
+    magic[0] = b'X';
+    assert_eq!(
+        Snapshot::from_bytes(&magic),
+        Err(SnapshotError::Malformed("missing snapshot header"))
+    );
+
+    let mut version = bytes.clone();
+    version[4] = 1;
+    assert_eq!(
+        Snapshot::from_bytes(&version),
+        Err(SnapshotError::UnsupportedVersion(1))
+    );
+
+    // A memory claiming more bytes than the blob has.
+    let mut length = bytes.clone();
+    length[9..13].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(
+        Snapshot::from_bytes(&length),
+        Err(SnapshotError::Malformed("unexpected end of snapshot"))
+    );
+
+    // The last byte is the tag of the null table element.
+    let mut element = bytes.clone();
+    *element.last_mut().unwrap() = 7;
+    assert_eq!(
+        Snapshot::from_bytes(&element),
+        Err(SnapshotError::Malformed("unknown table element"))
+    );
+}
+
+#[test]
+fn snapshots_of_other_modules_are_rejected() {
+    let snapshot = Snapshot::capture(&instantiate(&module()));
+    let mut other = Engine::new()
+        .compile(br#"(module (memory 1) (table 4 funcref))"#)
+        .unwrap()
+        .instantiate()
+        .unwrap();
+    let err = snapshot.restore(&mut other).unwrap_err();
+    assert_eq!(err, SnapshotError::Mismatch("no global 0".to_string()),);
+}
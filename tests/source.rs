@@ -0,0 +1,47 @@
+use adder::{source, Engine, Error, Value};
+
+const ADD_ONE: &str = r#"
+(module
+  (func (export "add_one") (param i32) (result i32)
+    local.get 0
+    i32.const 1
+    i32.add))
+"#;
+
+#[test]
+fn text_module_is_compiled_and_callable() {
+    let module = Engine::new().compile(ADD_ONE.as_bytes()).unwrap();
+    let instance = module.instantiate().unwrap();
+    let result = instance.call("add_one", &[Value::I32(41)]).unwrap();
+    assert_eq!(result, vec![Value::I32(42)]);
+}
+
+#[test]
+fn binary_module_is_passed_through() {
+    let wasm = include_bytes!("../src/add.wasm");
+    assert!(source::is_binary(wasm));
+    assert_eq!(&*source::wasm_bytes(wasm).unwrap(), &wasm[..]);
+
+    let instance = Engine::new().compile(wasm).unwrap().instantiate().unwrap();
+    let result = instance.call("add_one", &[Value::I32(42)]).unwrap();
+    assert_eq!(result, vec![Value::I32(43)]);
+}
+
+#[test]
+fn parse_errors_report_line_and_column() {
+    let text = "(module\n  (func (export \"f\")\n    i32.bogus))";
+    let err = source::wasm_bytes(text.as_bytes()).unwrap_err();
+    assert_eq!((err.line, err.column), (3, 5));
+    assert!(err.message.contains("unknown operator"), "{}", err.message);
+
+    match Engine::new().compile(text.as_bytes()) {
+        Err(Error::Parse(parse)) => assert_eq!(parse, err),
+        other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn non_utf8_input_is_rejected() {
+    let err = source::wasm_bytes(b"(module)\n\xff").unwrap_err();
+    assert_eq!((err.line, err.column), (2, 1));
+}
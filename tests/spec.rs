@@ -0,0 +1,454 @@
+//! Runs the `.wast` scripts under `tests/spec` through the crate's own
+//! `Engine`, `Module` and `Instance` API and prints a pass/fail table per
+//! proposal.
+//!
+//! The scripts are the upstream testsuite, copied in by `tests/spec/vendor.sh`.
+//! Scripts in `tests/spec/core` cover the MVP; each directory under
+//! `tests/spec/proposals` covers one post-MVP proposal. Only `assert_return`,
+//! `assert_trap`, `assert_invalid`, bare `invoke` and module definitions are
+//! run; other directives are counted as skipped, by kind. Every failure is
+//! listed. Failures in a proposal that is not listed in `SUPPORTED`, and
+//! those recorded in `tests/spec/known_failures.txt`, do not fail the run.
+//! Run with `SPEC_BLESS=1` to rewrite that file with the current failures.
+//!
+//! Pass a substring as the first argument to only run matching scripts, e.g.
+//! `cargo test --test spec -- memory`.
+
+use adder::{func, imports, Engine, Error, ImportObject, Instance, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use wasm_encoder::RawSection;
+use wasmer_runtime::types::{ElementType, MemoryDescriptor, TableDescriptor};
+use wasmer_runtime::units::Pages;
+use wasmer_runtime::{Global, Memory, Table};
+use wasmparser::{Parser, Payload};
+use wast::core::{NanPattern, WastArgCore, WastRetCore};
+use wast::parser::{self, ParseBuffer};
+use wast::{QuoteWat, Wast, WastArg, WastDirective, WastExecute, WastInvoke, WastRet, Wat};
+
+/// Proposals the runtime implements. A failure in any of these, or in
+/// `core`, fails the run.
+const SUPPORTED: &[&str] = &[
+    "core",
+    "nontrapping-float-to-int-conversions",
+    "sign-extension-ops",
+];
+
+#[derive(Default)]
+struct Report {
+    passed: usize,
+    /// Each failure's `script:line` and description.
+    failed: Vec<(String, String)>,
+    /// Skipped directives by reason.
+    skipped: BTreeMap<&'static str, usize>,
+}
+
+/// The result of running something, or why it was skipped.
+type Run = Result<Result<Vec<Value>, Error>, &'static str>;
+
+enum Outcome {
+    Pass,
+    Fail(String),
+    /// The directive can't be run, for this reason.
+    Skip(&'static str),
+}
+
+fn main() {
+    let filter = std::env::args()
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .unwrap_or_default();
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/spec");
+    if !root.join("core").is_dir() {
+        println!("no spec scripts vendored; run tests/spec/vendor.sh to fetch them");
+        return;
+    }
+
+    let mut suites = vec![("core".to_string(), root.join("core"))];
+    let mut proposals: Vec<_> = fs::read_dir(root.join("proposals"))
+        .into_iter()
+        .flatten()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    proposals.sort();
+    for path in proposals {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        suites.push((name, path));
+    }
+
+    let known_path = root.join("known_failures.txt");
+    let known: BTreeSet<String> = fs::read_to_string(&known_path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    let bless = env::var_os("SPEC_BLESS").is_some();
+
+    let mut failed = false;
+    let mut blessed = Vec::new();
+    println!(
+        "{:<40} {:>7} {:>7} {:>7} {:>7}",
+        "proposal", "passed", "failed", "known", "skipped"
+    );
+    let mut details = String::new();
+    for (name, dir) in suites {
+        let mut report = Report::default();
+        for script in scripts(&dir) {
+            if script.to_string_lossy().contains(&filter) {
+                run_script(&script, &mut report);
+            }
+        }
+        let supported = SUPPORTED.contains(&name.as_str());
+        let known_count = report
+            .failed
+            .iter()
+            .filter(|(at, _)| known.contains(at))
+            .count();
+        println!(
+            "{:<40} {:>7} {:>7} {:>7} {:>7}{}",
+            name,
+            report.passed,
+            report.failed.len() - known_count,
+            known_count,
+            report.skipped.values().sum::<usize>(),
+            if supported { "" } else { "  (unsupported)" }
+        );
+        for (at, why) in &report.failed {
+            let expected = !supported || known.contains(at);
+            if supported {
+                blessed.push(at.clone());
+            }
+            failed |= !expected;
+            let note = if !supported {
+                " (unsupported)"
+            } else if expected {
+                " (known)"
+            } else {
+                ""
+            };
+            writeln!(details, "  {}: {}{}", at, why, note).unwrap();
+        }
+        for (kind, count) in &report.skipped {
+            writeln!(details, "  {}: skipped {}: {}", name, count, kind).unwrap();
+        }
+    }
+
+    if !details.is_empty() {
+        println!("\n{}", details);
+    }
+    if bless {
+        let mut text = "# Failures in supported suites, one `script:line` per line. Regenerate\n\
+                        # with `SPEC_BLESS=1 cargo test --test spec`.\n"
+            .to_string();
+        for at in blessed {
+            writeln!(text, "{}", at).unwrap();
+        }
+        fs::write(&known_path, text).unwrap();
+    } else if failed {
+        println!("new failures in supported suites; see above");
+        process::exit(1);
+    }
+}
+
+fn scripts(dir: &Path) -> Vec<PathBuf> {
+    let mut scripts: Vec<_> = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("{}: {}", dir.display(), err))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wast"))
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+fn run_script(path: &Path, report: &mut Report) {
+    let text = fs::read_to_string(path).unwrap();
+    let name = path
+        .strip_prefix(env!("CARGO_MANIFEST_DIR"))
+        .unwrap_or(path)
+        .display()
+        .to_string();
+    let buf = match ParseBuffer::new(&text) {
+        Ok(buf) => buf,
+        Err(err) => return report.failed.push((name, err.to_string())),
+    };
+    let wast = match parser::parse::<Wast>(&buf) {
+        Ok(wast) => wast,
+        Err(mut err) => {
+            err.set_path(path);
+            err.set_text(&text);
+            return report.failed.push((name, err.to_string()));
+        }
+    };
+
+    let mut runner = Runner::default();
+    for directive in wast.directives {
+        let (line, _) = directive.span().linecol_in(&text);
+        match runner.run(directive) {
+            Outcome::Pass => report.passed += 1,
+            Outcome::Fail(why) => report.failed.push((format!("{}:{}", name, line + 1), why)),
+            Outcome::Skip(kind) => *report.skipped.entry(kind).or_default() += 1,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Runner {
+    instances: Vec<Instance>,
+    named: HashMap<String, usize>,
+    current: Option<usize>,
+}
+
+impl Runner {
+    fn run(&mut self, directive: WastDirective) -> Outcome {
+        match directive {
+            WastDirective::Module(mut module) => {
+                let name = module.name().map(|id| id.name().to_string());
+                match instantiate(&mut module) {
+                    Ok(instance) => {
+                        let index = self.instances.len();
+                        self.instances.push(instance);
+                        if let Some(name) = name {
+                            self.named.insert(name, index);
+                        }
+                        self.current = Some(index);
+                        Outcome::Pass
+                    }
+                    Err(err) => {
+                        self.current = None;
+                        Outcome::Fail(err.to_string())
+                    }
+                }
+            }
+            WastDirective::Invoke(invoke) => match self.invoke(&invoke) {
+                Ok(Ok(_)) => Outcome::Pass,
+                Ok(Err(err)) => Outcome::Fail(err.to_string()),
+                Err(why) => Outcome::Skip(why),
+            },
+            WastDirective::AssertReturn { exec, results, .. } => {
+                let expected: Option<Vec<_>> = results
+                    .iter()
+                    .map(|ret| match ret {
+                        WastRet::Core(ret) => Some(ret),
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    })
+                    .collect();
+                let expected = match expected {
+                    Some(expected) => expected,
+                    None => return Outcome::Skip("non-core results"),
+                };
+                let actual = match self.execute(exec) {
+                    Ok(actual) => actual,
+                    Err(why) => return Outcome::Skip(why),
+                };
+                match actual {
+                    Ok(actual) if matches(&actual, &expected) => Outcome::Pass,
+                    Ok(actual) => {
+                        Outcome::Fail(format!("expected {:?}, got {:?}", expected, actual))
+                    }
+                    Err(err) => Outcome::Fail(err.to_string()),
+                }
+            }
+            WastDirective::AssertTrap {
+                exec: WastExecute::Wat(wat),
+                message,
+                ..
+            } => start_trap(wat, message),
+            WastDirective::AssertTrap { exec, message, .. } => match self.execute(exec) {
+                // The runtime does not say which trap occurred, so only the
+                // fact that one did is checked.
+                Ok(Err(Error::Trap(_))) => Outcome::Pass,
+                Ok(Err(err)) => Outcome::Fail(format!("expected {:?}, got {}", message, err)),
+                Ok(Ok(results)) => {
+                    Outcome::Fail(format!("expected {:?}, returned {:?}", message, results))
+                }
+                Err(why) => Outcome::Skip(why),
+            },
+            WastDirective::AssertInvalid {
+                mut module,
+                message,
+                ..
+            } => match module.encode() {
+                Ok(wasm) => match Engine::new().compile(&wasm) {
+                    Err(Error::Compile(_)) => Outcome::Pass,
+                    Err(err) => Outcome::Fail(format!("expected {:?}, got {}", message, err)),
+                    Ok(_) => Outcome::Fail(format!("expected {:?}, module compiled", message)),
+                },
+                Err(_) => Outcome::Skip("module that can't be encoded"),
+            },
+            other => Outcome::Skip(kind(&other)),
+        }
+    }
+
+    /// Runs `exec`, or says why it can't be run through the crate's API.
+    fn execute(&mut self, exec: WastExecute) -> Run {
+        match exec {
+            WastExecute::Invoke(invoke) => self.invoke(&invoke),
+            WastExecute::Wat(mut wat) => {
+                let wasm = wat.encode().map_err(|_| "module that can't be encoded")?;
+                Ok(Engine::new()
+                    .compile(&wasm)
+                    .and_then(|module| module.instantiate_with(&spectest()))
+                    .map(|_| Vec::new()))
+            }
+            WastExecute::Get { module, global, .. } => {
+                let instance = self.instance(module.map(|id| id.name()))?;
+                Ok(instance.global(global).map(|value| vec![value]))
+            }
+        }
+    }
+
+    fn invoke(&mut self, invoke: &WastInvoke) -> Run {
+        let args = invoke
+            .args
+            .iter()
+            .map(|arg| match arg {
+                WastArg::Core(WastArgCore::I32(x)) => Ok(Value::I32(*x)),
+                WastArg::Core(WastArgCore::I64(x)) => Ok(Value::I64(*x)),
+                WastArg::Core(WastArgCore::F32(x)) => Ok(Value::F32(f32::from_bits(x.bits))),
+                WastArg::Core(WastArgCore::F64(x)) => Ok(Value::F64(f64::from_bits(x.bits))),
+                _ => Err("non-numeric arguments"),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let instance = self.instance(invoke.module.map(|id| id.name()))?;
+        Ok(instance.call(invoke.name, &args))
+    }
+
+    fn instance(&self, name: Option<&str>) -> Result<&Instance, &'static str> {
+        let index = match name {
+            Some(name) => *self.named.get(name).ok_or("unregistered module name")?,
+            None => self.current.ok_or("no module, the last one failed")?,
+        };
+        Ok(&self.instances[index])
+    }
+}
+
+fn start_trap(mut wat: Wat, message: &str) -> Outcome {
+    let wasm = match wat.encode() {
+        Ok(wasm) => wasm,
+        Err(_) => return Outcome::Skip("module that can't be encoded"),
+    };
+    let module = match Engine::new().compile(&wasm) {
+        Ok(module) => module,
+        Err(err) => return Outcome::Fail(format!("expected {:?}, got {}", message, err)),
+    };
+    // Without its start function, the module must instantiate; otherwise the
+    // failure is not a trap in the start function.
+    let linked = Engine::new()
+        .compile(&without_start(&wasm))
+        .and_then(|module| module.instantiate_with(&spectest()));
+    if let Err(err) = linked {
+        return Outcome::Fail(format!(
+            "expected {:?} from the start function, got {}",
+            message, err
+        ));
+    }
+    match module.instantiate_with(&spectest()) {
+        Err(Error::Instantiate(_)) => Outcome::Pass,
+        Err(err) => Outcome::Fail(format!("expected {:?}, got {}", message, err)),
+        Ok(_) => Outcome::Fail(format!("expected {:?}, module instantiated", message)),
+    }
+}
+
+fn without_start(wasm: &[u8]) -> Vec<u8> {
+    let mut module = wasm_encoder::Module::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload.expect("the module compiled");
+        if let Payload::StartSection { .. } = payload {
+            continue;
+        }
+        if let Some((id, range)) = payload.as_section() {
+            module.section(&RawSection {
+                id,
+                data: &wasm[range],
+            });
+        }
+    }
+    module.finish()
+}
+
+/// Names a kind of directive the runner doesn't run.
+fn kind(directive: &WastDirective) -> &'static str {
+    match directive {
+        WastDirective::Module(_) | WastDirective::ModuleDefinition(_) => "module",
+        WastDirective::ModuleInstance { .. } => "module instance",
+        WastDirective::AssertMalformed { .. } => "assert_malformed",
+        WastDirective::AssertInvalid { .. } => "assert_invalid",
+        WastDirective::Register { .. } => "register",
+        WastDirective::Invoke(_) => "invoke",
+        WastDirective::AssertTrap { .. } => "assert_trap",
+        WastDirective::AssertReturn { .. } => "assert_return",
+        WastDirective::AssertExhaustion { .. } => "assert_exhaustion",
+        WastDirective::AssertUnlinkable { .. } => "assert_unlinkable",
+        WastDirective::AssertException { .. } => "assert_exception",
+        _ => "other directive",
+    }
+}
+
+fn instantiate(module: &mut QuoteWat) -> Result<Instance, Error> {
+    let wasm = module
+        .encode()
+        .map_err(|err| Error::Compile(err.to_string()))?;
+    Engine::new().compile(&wasm)?.instantiate_with(&spectest())
+}
+
+fn matches(actual: &[Value], expected: &[&WastRetCore]) -> bool {
+    actual.len() == expected.len()
+        && actual
+            .iter()
+            .zip(expected)
+            .all(|(actual, expected)| match (actual, expected) {
+                (Value::I32(x), WastRetCore::I32(y)) => x == y,
+                (Value::I64(x), WastRetCore::I64(y)) => x == y,
+                (Value::F32(x), WastRetCore::F32(pattern)) => match pattern {
+                    NanPattern::CanonicalNan => x.to_bits() & 0x7fff_ffff == 0x7fc0_0000,
+                    NanPattern::ArithmeticNan => x.to_bits() & 0x7fc0_0000 == 0x7fc0_0000,
+                    NanPattern::Value(y) => x.to_bits() == y.bits,
+                },
+                (Value::F64(x), WastRetCore::F64(pattern)) => match pattern {
+                    NanPattern::CanonicalNan => {
+                        x.to_bits() & 0x7fff_ffff_ffff_ffff == 0x7ff8_0000_0000_0000
+                    }
+                    NanPattern::ArithmeticNan => {
+                        x.to_bits() & 0x7ff8_0000_0000_0000 == 0x7ff8_0000_0000_0000
+                    }
+                    NanPattern::Value(y) => x.to_bits() == y.bits,
+                },
+                _ => false,
+            })
+}
+
+/// The `spectest` module the scripts import from.
+fn spectest() -> ImportObject {
+    let memory =
+        Memory::new(MemoryDescriptor::new(Pages(1), Some(Pages(2)), false).unwrap()).unwrap();
+    let table = Table::new(TableDescriptor {
+        element: ElementType::Anyfunc,
+        minimum: 10,
+        maximum: Some(20),
+    })
+    .unwrap();
+    imports! {
+        "spectest" => {
+            "print" => func!(|| {}),
+            "print_i32" => func!(|_: i32| {}),
+            "print_i64" => func!(|_: i64| {}),
+            "print_f32" => func!(|_: f32| {}),
+            "print_f64" => func!(|_: f64| {}),
+            "print_i32_f32" => func!(|_: i32, _: f32| {}),
+            "print_f64_f64" => func!(|_: f64, _: f64| {}),
+            "global_i32" => Global::new(wasmer_runtime::Value::I32(666)),
+            "global_i64" => Global::new(wasmer_runtime::Value::I64(666)),
+            "global_f32" => Global::new(wasmer_runtime::Value::F32(666.6)),
+            "global_f64" => Global::new(wasmer_runtime::Value::F64(666.6)),
+            "memory" => memory,
+            "table" => table,
+        },
+    }
+}
@@ -0,0 +1,32 @@
+#!/bin/sh
+# Replaces the scripts under tests/spec with the upstream spec test suite,
+# unmodified, from https://github.com/WebAssembly/testsuite at the given ref
+# (default: main), or from a local clone of it when given a directory, and
+# records the commit in tests/spec/UPSTREAM.
+#
+# Afterwards, record failures the runtime is known to have with
+#   SPEC_BLESS=1 cargo test --test spec
+set -eu
+
+ref=${1:-main}
+spec=$(cd "$(dirname "$0")" && pwd)
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+if [ -d "$ref" ]; then
+    git clone --quiet "$ref" "$tmp/testsuite"
+else
+    git clone --quiet https://github.com/WebAssembly/testsuite "$tmp/testsuite"
+    git -C "$tmp/testsuite" checkout --quiet "$ref"
+fi
+
+rm -rf "$spec/core" "$spec/proposals"
+mkdir -p "$spec/core" "$spec/proposals"
+cp "$tmp"/testsuite/*.wast "$spec/core/"
+for proposal in "$tmp"/testsuite/proposals/*/; do
+    name=$(basename "$proposal")
+    mkdir -p "$spec/proposals/$name"
+    cp "$proposal"*.wast "$spec/proposals/$name/"
+done
+git -C "$tmp/testsuite" rev-parse HEAD > "$spec/UPSTREAM"
+echo "vendored testsuite $(cat "$spec/UPSTREAM")"
@@ -0,0 +1,223 @@
+use adder::snapshot::{Snapshot, SnapshotError};
+use adder::state_file::{self, StateFileError};
+use adder::{Engine, Instance, Value};
+use flate2::write::DeflateEncoder;
+use std::convert::TryInto;
+use std::io::Write;
+use std::path::PathBuf;
+
+const COUNTER: &str = r#"(module
+    (memory (export "memory") 1)
+    (global $count (mut i32) (i32.const 0))
+    (func (export "bump") (result i32)
+        (global.set $count (i32.add (global.get $count) (i32.const 1)))
+        (i32.store (i32.const 64) (global.get $count))
+        (global.get $count))
+    (func (export "stored") (result i32) (i32.load (i32.const 64))))"#;
+
+/// Where the payload length and snapshot length are in the header.
+const PAYLOAD_LEN: std::ops::Range<usize> = 39..47;
+const SNAPSHOT_LEN: std::ops::Range<usize> = 47..55;
+
+fn wasm() -> Vec<u8> {
+    adder::source::wasm_bytes(COUNTER.as_bytes())
+        .unwrap()
+        .into_owned()
+}
+
+fn instance() -> Instance {
+    Engine::new()
+        .compile(&wasm())
+        .unwrap()
+        .instantiate()
+        .unwrap()
+}
+
+fn stored(instance: &Instance) -> Value {
+    instance.call("stored", &[]).unwrap()[0]
+}
+
+fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+fn bumped_state(compress: bool) -> Vec<u8> {
+    let instance = instance();
+    instance.call("bump", &[]).unwrap();
+    instance.call("bump", &[]).unwrap();
+    let hash = state_file::module_hash(&wasm());
+    state_file::encode(&hash, &Snapshot::capture(&instance), compress)
+}
+
+fn decode(bytes: &[u8]) -> Result<Snapshot, StateFileError> {
+    state_file::decode(&state_file::module_hash(&wasm()), bytes)
+}
+
+fn malformed(result: Result<Snapshot, StateFileError>) -> &'static str {
+    match result {
+        Err(StateFileError::Malformed(what)) => what,
+        other => panic!("expected a malformed state file, got {:?}", other),
+    }
+}
+
+#[test]
+fn saved_state_loads_into_a_fresh_instance() {
+    for compress in [false, true] {
+        let path = path(&format!("counter-{}.state", compress));
+        let saved = instance();
+        saved.call("bump", &[]).unwrap();
+        saved.call("bump", &[]).unwrap();
+        state_file::save(&path, &wasm(), &saved, compress).unwrap();
+
+        let mut loaded = instance();
+        state_file::load(&path, &wasm(), &mut loaded).unwrap();
+        assert_eq!(stored(&loaded), Value::I32(2));
+        assert_eq!(loaded.call("bump", &[]).unwrap(), [Value::I32(3)]);
+    }
+
+    // Compression pays off for a mostly empty page.
+    assert!(bumped_state(true).len() < bumped_state(false).len() / 10);
+}
+
+#[test]
+fn state_for_another_module_is_rejected() {
+    let bytes = bumped_state(true);
+    let other = state_file::module_hash(b"(module)");
+    match state_file::decode(&other, &bytes) {
+        Err(StateFileError::ModuleMismatch { expected, found }) => {
+            assert_eq!(expected, other);
+            assert_eq!(found, state_file::module_hash(&wasm()));
+        }
+        other => panic!("expected a module mismatch, got {:?}", other),
+    }
+
+    // Through `load`, the instance is left alone.
+    let path = path("other-module.state");
+    std::fs::write(&path, &bytes).unwrap();
+    let mut instance = instance();
+    instance.call("bump", &[]).unwrap();
+    let err = state_file::load(&path, b"(module)", &mut instance).unwrap_err();
+    assert!(
+        matches!(err, StateFileError::ModuleMismatch { .. }),
+        "{:?}",
+        err
+    );
+    assert_eq!(stored(&instance), Value::I32(1));
+}
+
+#[test]
+fn other_versions_are_rejected() {
+    let mut bytes = bumped_state(false);
+    bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+    assert!(matches!(
+        decode(&bytes),
+        Err(StateFileError::UnsupportedVersion(1))
+    ));
+
+    let mut magic = bumped_state(false);
+    magic[..4].copy_from_slice(b"WSNP");
+    assert_eq!(malformed(decode(&magic)), "missing state file header");
+    assert_eq!(malformed(decode(&magic[..20])), "missing state file header");
+}
+
+#[test]
+fn corrupt_lengths_are_rejected() {
+    for compress in [false, true] {
+        let bytes = bumped_state(compress);
+        assert!(decode(&bytes).is_ok());
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert_eq!(
+            malformed(decode(truncated)),
+            "payload length does not match"
+        );
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert_eq!(
+            malformed(decode(&extended)),
+            "payload length does not match"
+        );
+
+        for delta in [-1i64, 1] {
+            let mut lying = bytes.clone();
+            let len = u64::from_le_bytes(lying[SNAPSHOT_LEN].try_into().unwrap());
+            let len = (len as i64 + delta) as u64;
+            lying[SNAPSHOT_LEN].copy_from_slice(&len.to_le_bytes());
+            assert_eq!(malformed(decode(&lying)), "snapshot length does not match");
+        }
+    }
+
+    let mut corrupt = bumped_state(true);
+    let end = corrupt.len();
+    corrupt[end - 8..].fill(0xff);
+    assert_eq!(malformed(decode(&corrupt)), "corrupt compressed payload");
+}
+
+#[test]
+fn inflation_stops_at_the_declared_length() {
+    // A hundred megabytes of zeros deflate to about a hundred kilobytes.
+    let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+    for _ in 0..100 {
+        encoder.write_all(&vec![0; 1 << 20]).unwrap();
+    }
+    let payload = encoder.finish().unwrap();
+
+    let mut bytes = bumped_state(true);
+    bytes.truncate(SNAPSHOT_LEN.end);
+    bytes[PAYLOAD_LEN].copy_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes[SNAPSHOT_LEN].copy_from_slice(&1024u64.to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    assert_eq!(malformed(decode(&bytes)), "snapshot length does not match");
+}
+
+#[test]
+fn declared_lengths_over_the_limit_are_rejected_before_inflating() {
+    let hash = state_file::module_hash(&wasm());
+    let mut bytes = bumped_state(true);
+    let len = u64::from_le_bytes(bytes[SNAPSHOT_LEN].try_into().unwrap());
+    assert!(state_file::decode_with_limit(&hash, &bytes, len).is_ok());
+    match state_file::decode_with_limit(&hash, &bytes, len - 1) {
+        Err(StateFileError::TooLarge { len: found, limit }) => {
+            assert_eq!((found, limit), (len, len - 1));
+        }
+        other => panic!("expected a snapshot over the limit, got {:?}", other),
+    }
+
+    // A hostile file can claim any length, but not one past the default
+    // ceiling.
+    let huge = state_file::MAX_SNAPSHOT_LEN + 1;
+    bytes[SNAPSHOT_LEN].copy_from_slice(&huge.to_le_bytes());
+    assert!(matches!(
+        decode(&bytes),
+        Err(StateFileError::TooLarge { len, limit })
+            if len == huge && limit == state_file::MAX_SNAPSHOT_LEN
+    ));
+    bytes[SNAPSHOT_LEN].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(matches!(
+        decode(&bytes),
+        Err(StateFileError::TooLarge { .. })
+    ));
+
+    // Uncompressed files are held to the limit too.
+    let path = path("limited.state");
+    std::fs::write(&path, bumped_state(false)).unwrap();
+    let mut instance = instance();
+    let err = state_file::load_with_limit(&path, &wasm(), &mut instance, 1024).unwrap_err();
+    assert!(
+        matches!(err, StateFileError::TooLarge { limit: 1024, .. }),
+        "{:?}",
+        err
+    );
+    assert_eq!(stored(&instance), Value::I32(0));
+}
+
+#[test]
+fn bad_snapshots_are_reported() {
+    let mut bytes = bumped_state(false);
+    // The snapshot's own magic follows the header.
+    bytes[SNAPSHOT_LEN.end] = b'X';
+    assert!(matches!(
+        decode(&bytes),
+        Err(StateFileError::Snapshot(SnapshotError::Malformed(_)))
+    ));
+}
@@ -0,0 +1,91 @@
+//! Checks that `libadder.a` keeps every exported symbol by linking a C
+//! program against it with the system C compiler.
+#![cfg(unix)]
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Returns the system libraries the Rust standard library needs when linked
+/// statically, which depend on the target and toolchain. rustc only prints
+/// them while building a static library, so build an empty one.
+fn native_libs() -> Vec<String> {
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join("libnative_probe.a");
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let mut child = Command::new(&rustc)
+        .args(["--crate-type", "staticlib", "--crate-name", "native_probe"])
+        .args(["--print", "native-static-libs", "-o"])
+        .arg(&out)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|err| panic!("could not run {}: {}", rustc, err));
+    // An empty crate; closing stdin ends it.
+    drop(child.stdin.take());
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{} failed:\n{}", rustc, stderr);
+    let libs = stderr
+        .lines()
+        .find_map(|line| line.split("native-static-libs:").nth(1))
+        .unwrap_or_else(|| panic!("{} printed no native-static-libs:\n{}", rustc, stderr));
+    libs.split_whitespace().map(str::to_string).collect()
+}
+
+/// The directory holding this profile's build outputs, e.g. `target/debug`.
+fn profile_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn c_program_links_against_static_library() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    // Integration tests only build the rlib, so build the staticlib for the
+    // same profile first.
+    let mut build = Command::new(env!("CARGO"));
+    build
+        .current_dir(crate_dir)
+        .args(["build", "--lib", "--quiet"]);
+    if profile_dir().ends_with("release") {
+        build.arg("--release");
+    }
+    let status = build.status().expect("could not run cargo");
+    assert!(status.success(), "cargo build --lib failed");
+    let library = profile_dir().join("libadder.a");
+    assert!(library.exists(), "{} was not built", library.display());
+
+    let exe = Path::new(env!("CARGO_TARGET_TMPDIR")).join("c_api");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let output = Command::new(&compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(crate_dir.join("include"))
+        .arg(crate_dir.join("tests/c/api.c"))
+        .arg(&library)
+        .args(native_libs())
+        .arg("-o")
+        .arg(&exe)
+        .output()
+        .unwrap_or_else(|err| panic!("could not run {}: {}", compiler, err));
+    assert!(
+        output.status.success(),
+        "linking failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = Command::new(&exe).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "C program failed:\n{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("Result: 43"), "{}", stdout);
+    assert!(stdout.contains("c api ok"), "{}", stdout);
+}
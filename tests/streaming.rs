@@ -0,0 +1,150 @@
+use adder::{source, Engine, Error, Value};
+use std::fs;
+use std::path::Path;
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    let text = fs::read(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+    source::wasm_bytes(&text).unwrap().into_owned()
+}
+
+fn wasm(wat: &str) -> Vec<u8> {
+    source::wasm_bytes(wat.as_bytes()).unwrap().into_owned()
+}
+
+fn compile_error(err: Error) -> String {
+    match err {
+        Error::Compile(message) => message,
+        err => panic!("expected a compile error, got {:?}", err),
+    }
+}
+
+#[test]
+fn modules_compile_from_chunks_of_any_size() {
+    for name in ["arithmetic.wat", "filters.wat", "globals.wat", "memory.wat"] {
+        let wasm = fixture(name);
+        let whole = Engine::new().compile(&wasm).unwrap();
+        for chunk_len in [1, 7, 64, wasm.len()] {
+            let mut stream = Engine::new().begin_streaming();
+            for chunk in wasm.chunks(chunk_len) {
+                stream.push(chunk).unwrap();
+            }
+            stream.push(&[]).unwrap();
+            assert_eq!(stream.received(), wasm.len());
+            let module = stream.finish().unwrap();
+            assert_eq!(
+                module.exports(),
+                whole.exports(),
+                "{} in {}",
+                name,
+                chunk_len
+            );
+        }
+    }
+
+    let mut stream = Engine::new().begin_streaming();
+    stream.push(&fixture("arithmetic.wat")).unwrap();
+    let instance = stream.finish().unwrap().instantiate().unwrap();
+    assert_eq!(
+        instance.call("add_one", &[Value::I32(41)]).unwrap(),
+        [Value::I32(42)]
+    );
+}
+
+#[test]
+fn invalid_functions_fail_before_the_rest_arrives() {
+    let wasm = wasm(
+        r#"(module
+            (memory 1)
+            (func (export "ok") (result i32) i32.const 1)
+            (func (export "bad") (result i32) i64.const 1)
+            (data (i32.const 0) "a long data section that has not arrived yet"))"#,
+    );
+    let bad_body_end = wasm.len() - 50;
+    let mut stream = Engine::new().begin_streaming();
+    let mut pushed = 0;
+    let err = loop {
+        match stream.push(&wasm[pushed..pushed + 1]) {
+            Ok(()) => pushed += 1,
+            Err(err) => break err,
+        }
+    };
+    assert!(pushed < bad_body_end, "failed after {} bytes", pushed);
+    assert!(
+        compile_error(err.clone()).contains("type mismatch"),
+        "{}",
+        err
+    );
+
+    // The stream stays failed and holds no input.
+    assert_eq!(stream.received(), 0);
+    assert_eq!(stream.push(&wasm[pushed..]).unwrap_err(), err);
+    assert_eq!(stream.finish().unwrap_err(), err);
+}
+
+#[test]
+fn bad_headers_fail_on_the_first_chunk() {
+    let mut stream = Engine::new().begin_streaming();
+    let err = stream.push(b"(module)").unwrap_err();
+    assert!(compile_error(err).contains("magic header"));
+
+    let mut stream = Engine::new().begin_streaming();
+    let err = stream.push(b"\0asm\x02\0\0\0").unwrap_err();
+    assert!(compile_error(err).contains("version"));
+}
+
+#[test]
+fn bad_sections_fail_on_the_chunk_that_completes_them() {
+    // A type section declaring one type and ending before it.
+    let mut stream = Engine::new().begin_streaming();
+    stream.push(b"\0asm\x01\0\0\0").unwrap();
+    stream.push(b"\x01\x01").unwrap();
+    let err = stream.push(b"\x01").unwrap_err();
+    assert!(compile_error(err).contains("offset"));
+
+    // Sections out of order.
+    let mut stream = Engine::new().begin_streaming();
+    stream.push(b"\0asm\x01\0\0\0\x05\x03\x01\0\x01").unwrap();
+    let err = stream.push(b"\x01\x01\0").unwrap_err();
+    assert!(compile_error(err).contains("out of order"));
+}
+
+#[test]
+fn truncated_modules_fail_when_finished() {
+    let wasm = fixture("arithmetic.wat");
+    let mut stream = Engine::new().begin_streaming();
+    stream.push(&wasm[..wasm.len() - 3]).unwrap();
+    let err = stream.finish().unwrap_err();
+    assert!(compile_error(err).contains("unexpected end"));
+
+    let err = Engine::new().begin_streaming().finish().unwrap_err();
+    assert!(matches!(err, Error::Compile(_)), "{:?}", err);
+}
+
+#[test]
+fn trailing_bytes_fail_when_finished() {
+    let mut stream = Engine::new().begin_streaming();
+    stream.push(&fixture("globals.wat")).unwrap();
+    stream.push(b"\0").unwrap();
+    let err = stream.finish().unwrap_err();
+    assert!(compile_error(err).contains("unexpected end"));
+}
+
+#[test]
+fn the_engine_configuration_applies() {
+    let wasm = wasm(
+        r#"(module
+            (func (export "nan") (result i32)
+                (i32.reinterpret_f32
+                    (f32.div (f32.const 0) (f32.const 0)))))"#,
+    );
+    let mut stream = Engine::new().canonicalize_nans(true).begin_streaming();
+    stream.push(&wasm).unwrap();
+    let instance = stream.finish().unwrap().instantiate().unwrap();
+    assert_eq!(
+        instance.call("nan", &[]).unwrap(),
+        [Value::I32(0x7fc0_0000)]
+    );
+}
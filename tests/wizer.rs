@@ -0,0 +1,150 @@
+use adder::wizer::{WizenError, Wizer};
+use adder::{imports, source, Engine, ImportObject, Instance, Value};
+use wasmer_runtime::types::MemoryDescriptor;
+use wasmer_runtime::units::Pages;
+use wasmer_runtime::Memory;
+use wasmparser::{Parser, Payload};
+
+/// Counts its start function runs in `$started`, and grows and fills memory
+/// in `init`.
+const COUNTER: &str = r#"(module
+    (memory (export "memory") 1)
+    (global $count (mut i32) (i32.const 0))
+    (global $started (mut i32) (i32.const 0))
+    (func $start
+        (global.set $started (i32.add (global.get $started) (i32.const 1))))
+    (start $start)
+    (func (export "init")
+        (drop (memory.grow (i32.const 1)))
+        (global.set $count (i32.const 41))
+        (i32.store (i32.const 100) (i32.const 0x01020304))
+        (i32.store (i32.const 70000) (i32.const 7)))
+    (func (export "count") (result i32) (global.get $count))
+    (func (export "started") (result i32) (global.get $started))
+    (func (export "load") (param i32) (result i32) (i32.load (local.get 0))))"#;
+
+fn instantiate(wasm: &[u8], imports: &ImportObject) -> Instance {
+    Engine::new()
+        .compile(wasm)
+        .unwrap()
+        .instantiate_with(imports)
+        .unwrap()
+}
+
+fn call(instance: &Instance, name: &str, args: &[Value]) -> Value {
+    instance.call(name, args).unwrap()[0]
+}
+
+/// The ids of the module's sections, with custom sections by name.
+fn sections(wasm: &[u8]) -> Vec<String> {
+    Parser::new(0)
+        .parse_all(wasm)
+        .filter_map(|payload| match payload.unwrap() {
+            Payload::CustomSection(reader) => Some(reader.name().to_string()),
+            payload => payload.as_section().map(|(id, _)| id.to_string()),
+        })
+        .collect()
+}
+
+fn exports(wasm: &[u8]) -> Vec<String> {
+    Engine::new()
+        .compile(wasm)
+        .unwrap()
+        .exports()
+        .into_iter()
+        .map(|export| export.name)
+        .collect()
+}
+
+fn memory() -> Memory {
+    Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap()
+}
+
+#[test]
+fn post_init_memory_and_globals_are_kept() {
+    let wasm = Wizer::new("init")
+        .run(COUNTER.as_bytes(), &imports! {})
+        .unwrap();
+    let instance = instantiate(&wasm, &imports! {});
+
+    assert_eq!(call(&instance, "count", &[]), Value::I32(41));
+    assert_eq!(
+        call(&instance, "load", &[Value::I32(100)]),
+        Value::I32(0x01020304)
+    );
+    assert_eq!(call(&instance, "load", &[Value::I32(70000)]), Value::I32(7));
+    assert_eq!(instance.memory_pages().unwrap(), 2);
+}
+
+#[test]
+fn the_start_section_and_init_export_are_dropped() {
+    let wasm = Wizer::new("init")
+        .run(COUNTER.as_bytes(), &imports! {})
+        .unwrap();
+    // Section 8 is the start section.
+    assert!(!sections(&wasm).contains(&"8".to_string()));
+    assert_eq!(exports(&wasm), ["memory", "count", "started", "load"]);
+    // The start function ran once, while wizening.
+    let instance = instantiate(&wasm, &imports! {});
+    assert_eq!(call(&instance, "started", &[]), Value::I32(1));
+
+    let kept = Wizer::new("init")
+        .keep_init_export(true)
+        .run(COUNTER.as_bytes(), &imports! {})
+        .unwrap();
+    assert_eq!(
+        exports(&kept),
+        ["memory", "init", "count", "started", "load"]
+    );
+}
+
+#[test]
+fn data_goes_before_trailing_custom_sections() {
+    // The names of `$count` and `$started` end up in a trailing `name`
+    // section.
+    let original = source::wasm_bytes(COUNTER.as_bytes()).unwrap();
+    assert_eq!(sections(&original).last().unwrap(), "name");
+
+    let wasm = Wizer::new("init").run(&original, &imports! {}).unwrap();
+    let sections = sections(&wasm);
+    assert_eq!(&sections[sections.len() - 3..], ["10", "11", "name"]);
+}
+
+#[test]
+fn data_segments_of_imported_memories_are_kept() {
+    let wat = r#"(module
+        (import "env" "memory" (memory 1))
+        (global $ready (mut i32) (i32.const 0))
+        (data (i32.const 8) "hello")
+        (func (export "init") (global.set $ready (i32.const 1)))
+        (func (export "ready") (result i32) (global.get $ready)))"#;
+    let wasm = Wizer::new("init")
+        .run(
+            wat.as_bytes(),
+            &imports! { "env" => { "memory" => memory(), }, },
+        )
+        .unwrap();
+
+    let memory = memory();
+    let instance = instantiate(
+        &wasm,
+        &imports! { "env" => { "memory" => memory.clone(), }, },
+    );
+    assert_eq!(call(&instance, "ready", &[]), Value::I32(1));
+    let bytes: Vec<u8> = memory.view::<u8>()[8..13].iter().map(|b| b.get()).collect();
+    assert_eq!(bytes, b"hello");
+}
+
+#[test]
+fn failing_init_functions_are_reported() {
+    let wat = r#"(module (func (export "init") unreachable))"#;
+    let err = Wizer::new("init")
+        .run(wat.as_bytes(), &imports! {})
+        .unwrap_err();
+    assert!(matches!(err, WizenError::Init(_)), "{:?}", err);
+
+    let err = Wizer::new("missing")
+        .run(wat.as_bytes(), &imports! {})
+        .unwrap_err();
+    assert!(matches!(err, WizenError::Init(_)), "{:?}", err);
+}